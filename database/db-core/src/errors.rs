@@ -20,6 +20,8 @@ use std::error::Error as StdError;
 //use derive_more::{error, Error as DeriveError};
 use thiserror::Error;
 
+use crate::snapshot::SnapshotCounts;
+
 /// Error data structure grouping various error subtypes
 #[derive(Debug, Error)]
 pub enum DBError {
@@ -58,6 +60,31 @@ pub enum DBError {
     /// Comment with specified characteristics not found
     #[error("Comment with specified characteristics not found")]
     CommentNotFound,
+
+    /// Access token with specified characteristics not found
+    #[error("Access token with specified characteristics not found")]
+    AccessTokenNotFound,
+
+    /// user already has an access token with the same name
+    #[error("Access token name not available")]
+    DuplicateAccessTokenName,
+
+    /// unknown access token scope
+    #[error("Unknown access token scope {}", _0)]
+    UnknownAccessTokenScope(String),
+
+    /// database already contains data
+    #[error("Database is not empty")]
+    DatabaseNotEmpty,
+
+    /// rows went missing while copying data between databases
+    #[error("Copied {found:?} rows, expected {expected:?}")]
+    RowCountMismatch {
+        /// number of rows in the source database
+        expected: SnapshotCounts,
+        /// number of rows in the destination database
+        found: SnapshotCounts,
+    },
 }
 
 /// Convenience type alias for grouping driver-specific errors
@@ -29,13 +29,15 @@
 //!
 //! - [errors](crate::auth): error data structures used in this crate
 //! - [ops](crate::ops): meta operations like connection pool creation, migrations and getting
-//! connection from pool
+//!   connection from pool
+//! - [snapshot](crate::snapshot): backend-neutral snapshot of all data in the database
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
 pub mod errors;
 pub mod ops;
+pub mod snapshot;
 #[cfg(feature = "test")]
 pub mod tests;
 
@@ -46,6 +48,7 @@ pub mod prelude {
 
     pub use super::errors::*;
     pub use super::ops::*;
+    pub use super::snapshot::*;
     pub use super::*;
 }
 
@@ -85,6 +88,20 @@ pub struct CreateGist<'a> {
     pub visibility: &'a GistVisibility,
 }
 
+#[derive(Clone, Debug)]
+/// Data required to update a gist in DB
+/// update time defaults to time at which update method is called
+pub struct UpdateGist<'a> {
+    /// owner of the gist
+    pub owner: &'a str,
+    /// public ID of the gist
+    pub public_id: &'a str,
+    /// description of the gist
+    pub description: Option<&'a str>,
+    /// gist visibility
+    pub visibility: &'a GistVisibility,
+}
+
 /// Gist visibility
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -151,6 +168,40 @@ pub struct Gist {
     pub visibility: GistVisibility,
 }
 
+/// Gist timestamp by which a list of gists is sorted, most recent first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GistSort {
+    /// sort by gist creation time
+    #[default]
+    Created,
+    /// sort by gist updated time
+    Updated,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Position in a sorted list of gists, used for keyset pagination.
+/// Gists are ordered by timestamp and then by public ID, so that gists with the same
+/// timestamp are paginated deterministically
+pub struct GistCursor<'a> {
+    /// timestamp of the last gist on the previous page. Creation or updated time,
+    /// depending on [GistSort]
+    pub timestamp: i64,
+    /// public ID of the last gist on the previous page
+    pub public_id: &'a str,
+}
+
+#[derive(Clone, Debug)]
+/// Text of a gist that is indexed for search
+pub struct GistSearchDocument<'a> {
+    /// public ID of the gist
+    pub public_id: &'a str,
+    /// names of files in the gist, separated by newlines
+    pub filenames: &'a str,
+    /// contents of text files in the gist
+    pub content: &'a str,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 /// Represents a comment on a Gist
 pub struct GistComment {
@@ -178,6 +229,94 @@ pub struct CreateGistComment<'a> {
     pub comment: &'a str,
 }
 
+/// Operations that a personal access token can be used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccessTokenScope {
+    /// read gists, including private gists of the token owner
+    #[serde(rename = "read:gists")]
+    ReadGists,
+    /// create, update and delete gists and comments
+    #[serde(rename = "write:gists")]
+    WriteGists,
+    /// manage account settings
+    #[serde(rename = "account")]
+    Account,
+}
+
+impl AccessTokenScope {
+    /// Convert [AccessTokenScope] to [str]
+    pub const fn to_str(&self) -> &'static str {
+        match self {
+            AccessTokenScope::ReadGists => "read:gists",
+            AccessTokenScope::WriteGists => "write:gists",
+            AccessTokenScope::Account => "account",
+        }
+    }
+
+    /// Serialize a list of scopes, for storage in a single column
+    pub fn join(scopes: &[Self]) -> String {
+        scopes
+            .iter()
+            .map(|s| s.to_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parse a list of scopes serialized with [AccessTokenScope::join]
+    pub fn split(scopes: &str) -> DBResult<Vec<Self>> {
+        scopes.split_whitespace().map(Self::from_str).collect()
+    }
+}
+
+impl FromStr for AccessTokenScope {
+    type Err = DBError;
+
+    /// Convert [str] to [AccessTokenScope]
+    fn from_str(s: &str) -> DBResult<Self> {
+        const READ_GISTS: &str = AccessTokenScope::ReadGists.to_str();
+        const WRITE_GISTS: &str = AccessTokenScope::WriteGists.to_str();
+        const ACCOUNT: &str = AccessTokenScope::Account.to_str();
+        let s = s.trim();
+        match s {
+            READ_GISTS => Ok(Self::ReadGists),
+            WRITE_GISTS => Ok(Self::WriteGists),
+            ACCOUNT => Ok(Self::Account),
+            _ => Err(DBError::UnknownAccessTokenScope(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Represents a personal access token. The token itself is never stored, only its hash
+pub struct AccessToken {
+    /// Unique identifier, possible database assigned, auto-incremented ID
+    pub id: i64,
+    /// owner of the token
+    pub owner: String,
+    /// name of the token, unique for each user
+    pub name: String,
+    /// operations that the token can be used for
+    pub scopes: Vec<AccessTokenScope>,
+    /// token creation time
+    pub created: i64,
+    /// time at which the token was last used
+    pub last_used: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+/// Data required to create a personal access token
+/// creation date defaults to time at which creation method is called
+pub struct CreateAccessToken<'a> {
+    /// owner of the token
+    pub owner: &'a str,
+    /// name of the token
+    pub name: &'a str,
+    /// hash of the token
+    pub token_hash: &'a str,
+    /// operations that the token can be used for
+    pub scopes: &'a [AccessTokenScope],
+}
+
 /// payload to register a user with username _and_ email
 pub struct EmailRegisterPayload<'a> {
     /// username of new user
@@ -254,10 +393,25 @@ pub trait GPDatabse: std::marker::Send + std::marker::Sync + CloneGPDatabse {
     async fn new_gist(&self, gist: &CreateGist) -> DBResult<()>;
     /// Retrieve gist from database
     async fn get_gist(&self, public_id: &str) -> DBResult<Gist>;
+    /// Update description and visibility of a gist
+    async fn update_gist(&self, gist: &UpdateGist) -> DBResult<()>;
+    /// Set last modified time of a gist to current time
+    async fn touch_gist(&self, public_id: &str) -> DBResult<()>;
+    /// Set creation and last modified time of a gist. Used to preserve timestamps of
+    /// gists that are imported from elsewhere.
+    async fn set_gist_timestamps(
+        &self,
+        public_id: &str,
+        created: i64,
+        updated: i64,
+    ) -> DBResult<()>;
 
     /// Retrieve gists belonging to user
     async fn get_user_gists(&self, owner: &str) -> DBResult<Vec<Gist>>;
 
+    /// Retrieve all gists, regardless of their visibility, oldest first
+    async fn get_all_gists(&self) -> DBResult<Vec<Gist>>;
+
     /// Retrieve gists belonging to user that are [GistVisibility::Public]
     async fn get_user_public_gists(&self, owner: &str) -> DBResult<Vec<Gist>>;
 
@@ -268,15 +422,75 @@ pub trait GPDatabse: std::marker::Send + std::marker::Sync + CloneGPDatabse {
     /// Delete gist
     async fn delete_gist(&self, owner: &str, public_id: &str) -> DBResult<()>;
 
+    /// Record that gist `fork` was forked from gist `parent`
+    async fn new_fork(&self, parent: &str, fork: &str) -> DBResult<()>;
+    /// Get gist from which a gist was forked. Returns `None` when the gist isn't a
+    /// fork or when the gist it was forked from was deleted
+    async fn get_fork_parent(&self, public_id: &str) -> DBResult<Option<Gist>>;
+    /// Get forks of a gist
+    async fn get_forks(&self, public_id: &str) -> DBResult<Vec<Gist>>;
+
+    /// Star a gist. Starring a gist that is already starred is a no-op
+    async fn star_gist(&self, username: &str, public_id: &str) -> DBResult<()>;
+    /// Remove star from a gist. Unstarring a gist that isn't starred is a no-op
+    async fn unstar_gist(&self, username: &str, public_id: &str) -> DBResult<()>;
+    /// Check if user has starred a gist
+    async fn is_starred(&self, username: &str, public_id: &str) -> DBResult<bool>;
+    /// Get number of users that have starred a gist
+    async fn get_star_count(&self, public_id: &str) -> DBResult<i64>;
+    /// Get gists starred by user, most recently starred first
+    async fn get_starred_gists(&self, username: &str) -> DBResult<Vec<Gist>>;
+
+    /// Get at most `limit` [GistVisibility::Public] gists sorted by `sort`, most recent first.
+    /// When `after` is set, only gists that come after the cursor are returned
+    async fn get_public_gists(
+        &self,
+        sort: GistSort,
+        after: Option<&GistCursor>,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>>;
+
+    /// Index gist for search, replacing its previous index. Gist description is indexed
+    /// along with `doc` and is kept in sync when the gist is updated
+    async fn index_gist(&self, doc: &GistSearchDocument) -> DBResult<()>;
+    /// Search gist descriptions, filenames and contents, most relevant first. Only
+    /// [GistVisibility::Public] gists and gists owned by `viewer` are returned
+    async fn search_gists(
+        &self,
+        query: &str,
+        viewer: Option<&str>,
+        offset: u32,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>>;
+
     /// Create new comment, returns database ID of the newly created comment
     async fn new_comment(&self, comment: &CreateGistComment) -> DBResult<i64>;
     /// Get comments on a gist
     async fn get_comments_on_gist(&self, public_id: &str) -> DBResult<Vec<GistComment>>;
+    /// Get comments authored by user, oldest first
+    async fn get_user_comments(&self, owner: &str) -> DBResult<Vec<GistComment>>;
     /// Get a specific comment using its database assigned ID
     async fn get_comment_by_id(&self, id: i64) -> DBResult<GistComment>;
     /// Delete comment
     async fn delete_comment(&self, owner: &str, id: i64) -> DBResult<()>;
 
+    /// Create new personal access token, returns database ID of the newly created token
+    async fn new_access_token(&self, token: &CreateAccessToken) -> DBResult<i64>;
+    /// Get personal access tokens belonging to user, most recently created first
+    async fn get_access_tokens(&self, owner: &str) -> DBResult<Vec<AccessToken>>;
+    /// Get personal access token using the hash of the token
+    async fn get_access_token_by_hash(&self, token_hash: &str) -> DBResult<AccessToken>;
+    /// Set last used time of a personal access token to current time
+    async fn touch_access_token(&self, id: i64) -> DBResult<()>;
+    /// Delete personal access token
+    async fn delete_access_token(&self, owner: &str, id: i64) -> DBResult<()>;
+
+    /// Get snapshot of all data in the database. Rows are read in a single transaction,
+    /// so the snapshot is consistent even when the database is in use.
+    async fn snapshot(&self) -> DBResult<Snapshot>;
+    /// Restore snapshot into an empty database, preserving IDs of rows
+    async fn restore(&self, snapshot: &Snapshot) -> DBResult<()>;
+
     /// check if visibility mode exists
     async fn visibility_exists(&self, visibility: &GistVisibility) -> DBResult<bool>;
 }
@@ -348,10 +562,33 @@ impl GPDatabse for Box<dyn GPDatabse> {
         (**self).get_gist(public_id).await
     }
 
+    async fn update_gist(&self, gist: &UpdateGist) -> DBResult<()> {
+        (**self).update_gist(gist).await
+    }
+
+    async fn touch_gist(&self, public_id: &str) -> DBResult<()> {
+        (**self).touch_gist(public_id).await
+    }
+
+    async fn set_gist_timestamps(
+        &self,
+        public_id: &str,
+        created: i64,
+        updated: i64,
+    ) -> DBResult<()> {
+        (**self)
+            .set_gist_timestamps(public_id, created, updated)
+            .await
+    }
+
     async fn get_user_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
         (**self).get_user_gists(owner).await
     }
 
+    async fn get_all_gists(&self) -> DBResult<Vec<Gist>> {
+        (**self).get_all_gists().await
+    }
+
     async fn get_user_public_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
         (**self).get_user_public_gists(owner).await
     }
@@ -364,6 +601,61 @@ impl GPDatabse for Box<dyn GPDatabse> {
         (**self).delete_gist(owner, public_id).await
     }
 
+    async fn new_fork(&self, parent: &str, fork: &str) -> DBResult<()> {
+        (**self).new_fork(parent, fork).await
+    }
+
+    async fn get_fork_parent(&self, public_id: &str) -> DBResult<Option<Gist>> {
+        (**self).get_fork_parent(public_id).await
+    }
+
+    async fn get_forks(&self, public_id: &str) -> DBResult<Vec<Gist>> {
+        (**self).get_forks(public_id).await
+    }
+
+    async fn star_gist(&self, username: &str, public_id: &str) -> DBResult<()> {
+        (**self).star_gist(username, public_id).await
+    }
+
+    async fn unstar_gist(&self, username: &str, public_id: &str) -> DBResult<()> {
+        (**self).unstar_gist(username, public_id).await
+    }
+
+    async fn is_starred(&self, username: &str, public_id: &str) -> DBResult<bool> {
+        (**self).is_starred(username, public_id).await
+    }
+
+    async fn get_star_count(&self, public_id: &str) -> DBResult<i64> {
+        (**self).get_star_count(public_id).await
+    }
+
+    async fn get_starred_gists(&self, username: &str) -> DBResult<Vec<Gist>> {
+        (**self).get_starred_gists(username).await
+    }
+
+    async fn get_public_gists(
+        &self,
+        sort: GistSort,
+        after: Option<&GistCursor>,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>> {
+        (**self).get_public_gists(sort, after, limit).await
+    }
+
+    async fn index_gist(&self, doc: &GistSearchDocument) -> DBResult<()> {
+        (**self).index_gist(doc).await
+    }
+
+    async fn search_gists(
+        &self,
+        query: &str,
+        viewer: Option<&str>,
+        offset: u32,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>> {
+        (**self).search_gists(query, viewer, offset, limit).await
+    }
+
     async fn new_comment(&self, comment: &CreateGistComment) -> DBResult<i64> {
         (**self).new_comment(comment).await
     }
@@ -372,6 +664,10 @@ impl GPDatabse for Box<dyn GPDatabse> {
         (**self).get_comments_on_gist(public_id).await
     }
 
+    async fn get_user_comments(&self, owner: &str) -> DBResult<Vec<GistComment>> {
+        (**self).get_user_comments(owner).await
+    }
+
     async fn get_comment_by_id(&self, id: i64) -> DBResult<GistComment> {
         (**self).get_comment_by_id(id).await
     }
@@ -380,6 +676,34 @@ impl GPDatabse for Box<dyn GPDatabse> {
         (**self).delete_comment(owner, id).await
     }
 
+    async fn new_access_token(&self, token: &CreateAccessToken) -> DBResult<i64> {
+        (**self).new_access_token(token).await
+    }
+
+    async fn get_access_tokens(&self, owner: &str) -> DBResult<Vec<AccessToken>> {
+        (**self).get_access_tokens(owner).await
+    }
+
+    async fn get_access_token_by_hash(&self, token_hash: &str) -> DBResult<AccessToken> {
+        (**self).get_access_token_by_hash(token_hash).await
+    }
+
+    async fn touch_access_token(&self, id: i64) -> DBResult<()> {
+        (**self).touch_access_token(id).await
+    }
+
+    async fn delete_access_token(&self, owner: &str, id: i64) -> DBResult<()> {
+        (**self).delete_access_token(owner, id).await
+    }
+
+    async fn snapshot(&self) -> DBResult<Snapshot> {
+        (**self).snapshot().await
+    }
+
+    async fn restore(&self, snapshot: &Snapshot) -> DBResult<()> {
+        (**self).restore(snapshot).await
+    }
+
     async fn visibility_exists(&self, visibility: &GistVisibility) -> DBResult<bool> {
         (**self).visibility_exists(visibility).await
     }
@@ -0,0 +1,185 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Backend-neutral snapshot of all data stored in the database
+//!
+//! Snapshots are used to back up instances and to move them between database backends.
+//! Rows keep their database assigned IDs, rows that refer to other rows do so using
+//! those IDs.
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::{AccessTokenScope, GPDatabse, GistVisibility};
+
+/// Snapshot of all data stored in the database, except for the search index, which can be
+/// rebuilt from gist repositories
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// registered users
+    pub users: Vec<UserRecord>,
+    /// gists
+    pub gists: Vec<GistRecord>,
+    /// comments on gists
+    pub comments: Vec<CommentRecord>,
+    /// fork relationships between gists
+    pub forks: Vec<ForkRecord>,
+    /// stars on gists
+    pub stars: Vec<StarRecord>,
+    /// personal access tokens
+    pub access_tokens: Vec<AccessTokenRecord>,
+}
+
+/// Number of rows of each kind in a [Snapshot]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotCounts {
+    /// number of users
+    pub users: usize,
+    /// number of gists
+    pub gists: usize,
+    /// number of comments
+    pub comments: usize,
+    /// number of forks
+    pub forks: usize,
+    /// number of stars
+    pub stars: usize,
+    /// number of personal access tokens
+    pub access_tokens: usize,
+}
+
+impl Snapshot {
+    /// Get number of rows of each kind in the snapshot
+    pub fn counts(&self) -> SnapshotCounts {
+        SnapshotCounts {
+            users: self.users.len(),
+            gists: self.gists.len(),
+            comments: self.comments.len(),
+            forks: self.forks.len(),
+            stars: self.stars.len(),
+            access_tokens: self.access_tokens.len(),
+        }
+    }
+}
+
+/// Copy all data from `source` to `destination`, which must be empty, and verify that no
+/// rows went missing. IDs, timestamps and secrets are preserved. The search index isn't
+/// copied.
+pub async fn copy_database<S: GPDatabse, D: GPDatabse>(
+    source: &S,
+    destination: &D,
+) -> DBResult<SnapshotCounts> {
+    let snapshot = source.snapshot().await?;
+    destination.restore(&snapshot).await?;
+    let expected = snapshot.counts();
+    let found = destination.snapshot().await?.counts();
+    if found != expected {
+        return Err(DBError::RowCountMismatch { expected, found });
+    }
+    Ok(found)
+}
+
+/// User, as stored in the database
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserRecord {
+    /// database assigned ID
+    pub id: i64,
+    /// username
+    pub username: String,
+    /// email
+    pub email: Option<String>,
+    /// whether the email was verified
+    pub email_verified: Option<bool>,
+    /// account secret
+    pub secret: String,
+    /// password hash
+    pub password: String,
+}
+
+/// Gist, as stored in the database
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GistRecord {
+    /// database assigned ID
+    pub id: i64,
+    /// ID of the gist's owner
+    pub owner_id: i64,
+    /// description of the gist
+    pub description: Option<String>,
+    /// gist visibility
+    pub visibility: GistVisibility,
+    /// gist creation time
+    pub created: i64,
+    /// gist updated time
+    pub updated: i64,
+    /// public ID of the gist
+    pub public_id: String,
+}
+
+/// Comment, as stored in the database
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommentRecord {
+    /// database assigned ID
+    pub id: i64,
+    /// ID of the comment's author
+    pub owner_id: i64,
+    /// ID of the gist on which the comment was made
+    pub gist_id: i64,
+    /// comment text
+    pub comment: Option<String>,
+    /// comment creation time
+    pub created: i64,
+}
+
+/// Fork relationship, as stored in the database
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForkRecord {
+    /// database assigned ID
+    pub id: i64,
+    /// ID of the fork
+    pub gist_id: i64,
+    /// ID of the gist that was forked
+    pub parent_id: i64,
+}
+
+/// Star, as stored in the database
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StarRecord {
+    /// database assigned ID
+    pub id: i64,
+    /// ID of the user who starred the gist
+    pub owner_id: i64,
+    /// ID of the starred gist
+    pub gist_id: i64,
+    /// time at which the gist was starred
+    pub created: i64,
+}
+
+/// Personal access token, as stored in the database
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccessTokenRecord {
+    /// database assigned ID
+    pub id: i64,
+    /// ID of the token's owner
+    pub owner_id: i64,
+    /// name of the token
+    pub name: String,
+    /// hash of the token
+    pub token_hash: String,
+    /// operations that the token can be used for
+    pub scopes: Vec<AccessTokenScope>,
+    /// token creation time
+    pub created: i64,
+    /// time at which the token was last used
+    pub last_used: Option<i64>,
+}
@@ -130,6 +130,74 @@ pub async fn gists_work<T: GPDatabse>(
     let gist = gists.pop().unwrap();
     assert_gists(&create_gist, &gist);
 
+    // update gist
+    let mut update_gist = UpdateGist {
+        owner: username,
+        public_id: create_gist.public_id,
+        description: Some("bar"),
+        visibility: &GistVisibility::Unlisted,
+    };
+    db.update_gist(&update_gist).await.unwrap();
+    let updated = db.get_gist(create_gist.public_id).await.unwrap();
+    assert_eq!(updated.description.as_deref(), Some("bar"));
+    assert_eq!(updated.visibility, GistVisibility::Unlisted);
+    assert_eq!(updated.created, db_gist.created);
+    assert!(updated.updated >= db_gist.updated);
+
+    update_gist.description = None;
+    db.update_gist(&update_gist).await.unwrap();
+    assert!(db
+        .get_gist(create_gist.public_id)
+        .await
+        .unwrap()
+        .description
+        .is_none());
+
+    // only owner can update gist
+    update_gist.owner = "nonexistentuser";
+    assert!(matches!(
+        db.update_gist(&update_gist).await.err(),
+        Some(DBError::GistNotFound)
+    ));
+
+    // restore gist
+    update_gist.owner = username;
+    update_gist.description = create_gist.description;
+    update_gist.visibility = create_gist.visibility;
+    db.update_gist(&update_gist).await.unwrap();
+    assert_gists(
+        &create_gist,
+        &db.get_gist(create_gist.public_id).await.unwrap(),
+    );
+
+    db.touch_gist(create_gist.public_id).await.unwrap();
+    assert!(db.get_gist(create_gist.public_id).await.unwrap().updated >= updated.updated);
+    assert!(matches!(
+        db.touch_gist("nonexistentgist").await.err(),
+        Some(DBError::GistNotFound)
+    ));
+
+    db.set_gist_timestamps(create_gist.public_id, 1000, 2000)
+        .await
+        .unwrap();
+    let gist = db.get_gist(create_gist.public_id).await.unwrap();
+    assert_eq!(gist.created, 1000);
+    assert_eq!(gist.updated, 2000);
+    assert!(matches!(
+        db.set_gist_timestamps("nonexistentgist", 1000, 2000)
+            .await
+            .err(),
+        Some(DBError::GistNotFound)
+    ));
+
+    // all gists
+    assert!(db
+        .get_all_gists()
+        .await
+        .unwrap()
+        .iter()
+        .any(|g| g.public_id == create_gist.public_id));
+
     // comment on gist
     let create_comment = CreateGistComment {
         owner: username,
@@ -152,8 +220,14 @@ pub async fn gists_work<T: GPDatabse>(
     let comment_from_id = db.get_comment_by_id(comment_id).await.unwrap();
     assert_comments(&create_comment, &comment_from_id);
 
+    // get all comments by user
+    let mut comments = db.get_user_comments(username).await.unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_comments(&create_comment, &comments.pop().unwrap());
+
     // delete comment
     db.delete_comment(username, comment.id).await.unwrap();
+    assert!(db.get_user_comments(username).await.unwrap().is_empty());
 
     assert!(matches!(
         db.get_comment_by_id(comment.id).await.err().unwrap(),
@@ -203,10 +277,185 @@ pub async fn gists_work<T: GPDatabse>(
         }
     }
 
-    //  delete gist
+    // fork gist
+    let create_fork = CreateGist {
+        owner: username,
+        description: Some("foo"),
+        public_id: &format!("{}fork", public_id),
+        visibility: &GistVisibility::Public,
+    };
+    db.new_gist(&create_fork).await.unwrap();
+    assert!(db
+        .get_fork_parent(create_fork.public_id)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(db
+        .get_forks(create_gist.public_id)
+        .await
+        .unwrap()
+        .is_empty());
+    db.new_fork(create_gist.public_id, create_fork.public_id)
+        .await
+        .unwrap();
+    assert!(db
+        .new_fork(create_gist.public_id, "nonexistentgist")
+        .await
+        .is_err());
+    let parent = db
+        .get_fork_parent(create_fork.public_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_gists(&create_gist, &parent);
+    let mut forks = db.get_forks(create_gist.public_id).await.unwrap();
+    assert_eq!(forks.len(), 1);
+    assert_gists(&create_fork, &forks.pop().unwrap());
+
+    // star gist
+    assert!(!db
+        .is_starred(username, create_gist.public_id)
+        .await
+        .unwrap());
+    assert_eq!(db.get_star_count(create_gist.public_id).await.unwrap(), 0);
+    assert!(db.get_starred_gists(username).await.unwrap().is_empty());
+    db.star_gist(username, create_gist.public_id).await.unwrap();
+    // starring twice is a no-op
+    db.star_gist(username, create_gist.public_id).await.unwrap();
+    assert!(db.star_gist(username, "nonexistentgist").await.is_err());
+    assert!(db
+        .is_starred(username, create_gist.public_id)
+        .await
+        .unwrap());
+    assert_eq!(db.get_star_count(create_gist.public_id).await.unwrap(), 1);
+    db.star_gist(username, create_fork.public_id).await.unwrap();
+    let starred = db.get_starred_gists(username).await.unwrap();
+    assert_eq!(starred.len(), 2);
+    assert!(starred.iter().any(|g| g.public_id == create_gist.public_id));
+    assert!(starred.iter().any(|g| g.public_id == create_fork.public_id));
+
+    // unstar gist
+    db.unstar_gist(username, create_fork.public_id)
+        .await
+        .unwrap();
+    db.unstar_gist(username, create_fork.public_id)
+        .await
+        .unwrap();
+    assert!(!db
+        .is_starred(username, create_fork.public_id)
+        .await
+        .unwrap());
+    assert_eq!(db.get_star_count(create_fork.public_id).await.unwrap(), 0);
+    let mut starred = db.get_starred_gists(username).await.unwrap();
+    assert_eq!(starred.len(), 1);
+    assert_gists(&create_gist, &starred.pop().unwrap());
+
+    // public gists, paginated with a cursor
+    for sort in [GistSort::Created, GistSort::Updated] {
+        let mut gists: Vec<Gist> = Vec::default();
+        loop {
+            let cursor = gists.last().map(|g| GistCursor {
+                timestamp: match sort {
+                    GistSort::Created => g.created,
+                    GistSort::Updated => g.updated,
+                },
+                public_id: &g.public_id,
+            });
+            let page = db.get_public_gists(sort, cursor.as_ref(), 2).await.unwrap();
+            assert!(page.len() <= 2);
+            if page.is_empty() {
+                break;
+            }
+            gists.extend(page);
+        }
+        assert!(gists.iter().all(|g| g.visibility == GistVisibility::Public));
+        for w in gists.windows(2) {
+            let (a, b) = match sort {
+                GistSort::Created => (w[0].created, w[1].created),
+                GistSort::Updated => (w[0].updated, w[1].updated),
+            };
+            assert!(a >= b);
+            assert_ne!(w[0].public_id, w[1].public_id);
+        }
+        for gist in [&create_gist, &create_fork] {
+            assert!(gists.iter().any(|g| g.public_id == gist.public_id));
+        }
+        for gist in [&create_unlisted_gist, &create_private_gist] {
+            assert!(!gists.iter().any(|g| g.public_id == gist.public_id));
+        }
+    }
+
+    // search gists
+    const TERM: &str = "xylophonequasar";
+    let doc = GistSearchDocument {
+        public_id: "nonexistentgist",
+        filenames: "foo.rs",
+        content: TERM,
+    };
+    assert!(matches!(
+        db.index_gist(&doc).await.err(),
+        Some(DBError::GistNotFound)
+    ));
+    for gist in [&create_gist, &create_private_gist] {
+        let doc = GistSearchDocument {
+            public_id: gist.public_id,
+            filenames: "searchable_file.rs\nbar.md",
+            content: TERM,
+        };
+        db.index_gist(&doc).await.unwrap();
+    }
+    // private gists are only visible to their owner
+    let results = db.search_gists(TERM, None, 0, 10).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_gists(&create_gist, &results[0]);
+    let results = db.search_gists(TERM, Some(username), 0, 10).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        db.search_gists(TERM, Some(username), 1, 10)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    // filenames
+    let results = db.search_gists("searchable", None, 0, 10).await.unwrap();
+    assert!(results.iter().any(|g| g.public_id == create_gist.public_id));
+    // search index is updated along with gist description
+    update_gist.description = Some("zanzibarmeerkat");
+    db.update_gist(&update_gist).await.unwrap();
+    let results = db
+        .search_gists("zanzibarmeerkat", None, 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].public_id, create_gist.public_id);
+    update_gist.description = create_gist.description;
+    db.update_gist(&update_gist).await.unwrap();
+    assert!(db
+        .search_gists("zanzibarmeerkat", None, 0, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    // reindexing replaces previous index
+    let doc = GistSearchDocument {
+        public_id: create_gist.public_id,
+        filenames: "foo.rs",
+        content: "foo",
+    };
+    db.index_gist(&doc).await.unwrap();
+    assert!(db.search_gists(TERM, None, 0, 10).await.unwrap().is_empty());
+    // query syntax in user input is not interpreted
+    db.search_gists("\"foo AND (", None, 0, 10).await.unwrap();
+
+    //  delete gist; comments on it are deleted along with it
+    let comment_id = db.new_comment(&create_comment).await.unwrap();
     db.delete_gist(username, create_gist.public_id)
         .await
         .unwrap();
+    assert!(matches!(
+        db.get_comment_by_id(comment_id).await.err().unwrap(),
+        DBError::CommentNotFound
+    ));
     assert!(matches!(
         db.get_gist(create_gist.public_id).await.err().unwrap(),
         DBError::GistNotFound
@@ -216,6 +465,291 @@ pub async fn gists_work<T: GPDatabse>(
         .await
         .unwrap()
         .is_empty());
+    assert!(matches!(
+        db.delete_gist(username, create_gist.public_id)
+            .await
+            .err()
+            .unwrap(),
+        DBError::GistNotFound
+    ));
+    // search index is deleted along with the gist
+    assert!(db
+        .search_gists(TERM, Some(username), 0, 10)
+        .await
+        .unwrap()
+        .iter()
+        .all(|g| g.public_id != create_gist.public_id));
+    // stars are deleted along with the gist
+    assert!(db.get_starred_gists(username).await.unwrap().is_empty());
+    // forks outlive the gist they were forked from
+    assert!(db.gist_exists(create_fork.public_id).await.unwrap());
+    assert!(db
+        .get_fork_parent(create_fork.public_id)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// test all personal access token methods
+pub async fn access_tokens_work<T: GPDatabse>(
+    db: &T,
+    username: &str,
+    username2: &str,
+    password: &str,
+    secret: &str,
+    secret2: &str,
+) {
+    let _ = db.delete_account(username).await;
+    let _ = db.delete_account(username2).await;
+    for (username, secret) in [(username, secret), (username2, secret2)] {
+        let register_payload = UsernameRegisterPayload {
+            username,
+            password,
+            secret,
+        };
+        db.username_register(&register_payload).await.unwrap();
+    }
+
+    assert!(db.get_access_tokens(username).await.unwrap().is_empty());
+    assert!(matches!(
+        db.get_access_token_by_hash("nonexistent").await.err(),
+        Some(DBError::AccessTokenNotFound)
+    ));
+
+    let scopes = [AccessTokenScope::ReadGists, AccessTokenScope::WriteGists];
+    let mut create_token = CreateAccessToken {
+        owner: username,
+        name: "ci",
+        token_hash: "accesstokenhash1",
+        scopes: &scopes,
+    };
+    let id = db.new_access_token(&create_token).await.unwrap();
+    let token = db
+        .get_access_token_by_hash("accesstokenhash1")
+        .await
+        .unwrap();
+    assert_eq!(token.id, id);
+    assert_eq!(token.owner, username);
+    assert_eq!(token.name, "ci");
+    assert_eq!(token.scopes, scopes);
+    assert!(token.last_used.is_none());
+
+    // names are unique per user
+    create_token.token_hash = "accesstokenhash2";
+    assert!(matches!(
+        db.new_access_token(&create_token).await.err(),
+        Some(DBError::DuplicateAccessTokenName)
+    ));
+    create_token.owner = username2;
+    db.new_access_token(&create_token).await.unwrap();
+
+    create_token.owner = username;
+    create_token.name = "account";
+    create_token.token_hash = "accesstokenhash3";
+    create_token.scopes = &[AccessTokenScope::Account];
+    let id2 = db.new_access_token(&create_token).await.unwrap();
+    let tokens = db.get_access_tokens(username).await.unwrap();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].id, id2);
+    assert_eq!(tokens[0].scopes, [AccessTokenScope::Account]);
+    assert_eq!(tokens[1].id, id);
+
+    db.touch_access_token(id).await.unwrap();
+    let token = db
+        .get_access_token_by_hash("accesstokenhash1")
+        .await
+        .unwrap();
+    assert!(token.last_used.is_some());
+
+    // tokens can only be deleted by their owners
+    assert!(matches!(
+        db.delete_access_token(username2, id).await.err(),
+        Some(DBError::AccessTokenNotFound)
+    ));
+    db.delete_access_token(username, id).await.unwrap();
+    assert!(matches!(
+        db.delete_access_token(username, id).await.err(),
+        Some(DBError::AccessTokenNotFound)
+    ));
+    assert!(matches!(
+        db.get_access_token_by_hash("accesstokenhash1").await.err(),
+        Some(DBError::AccessTokenNotFound)
+    ));
+
+    // tokens are deleted along with their owners
+    db.delete_account(username).await.unwrap();
+    assert!(matches!(
+        db.get_access_token_by_hash("accesstokenhash3").await.err(),
+        Some(DBError::AccessTokenNotFound)
+    ));
+    db.delete_account(username2).await.unwrap();
+}
+
+/// test snapshot implementation, returns the snapshot for use with [restore_works]
+pub async fn snapshot_works<T: GPDatabse>(
+    db: &T,
+    username: &str,
+    password: &str,
+    secret: &str,
+    public_id: &str,
+    fork_public_id: &str,
+) -> Snapshot {
+    let _ = db.delete_account(username).await;
+    let register_payload = UsernameRegisterPayload {
+        username,
+        password,
+        secret,
+    };
+    db.username_register(&register_payload).await.unwrap();
+
+    let mut create_gist = CreateGist {
+        owner: username,
+        description: Some("foo"),
+        public_id,
+        visibility: &GistVisibility::Public,
+    };
+    db.new_gist(&create_gist).await.unwrap();
+    create_gist.public_id = fork_public_id;
+    create_gist.visibility = &GistVisibility::Private;
+    db.new_gist(&create_gist).await.unwrap();
+    db.new_fork(public_id, fork_public_id).await.unwrap();
+    db.star_gist(username, public_id).await.unwrap();
+    let comment = CreateGistComment {
+        owner: username,
+        gist_public_id: public_id,
+        comment: "foo",
+    };
+    let comment_id = db.new_comment(&comment).await.unwrap();
+    let create_token = CreateAccessToken {
+        owner: username,
+        name: "snapshot",
+        token_hash: secret,
+        scopes: &[AccessTokenScope::ReadGists],
+    };
+    let token_id = db.new_access_token(&create_token).await.unwrap();
+
+    let snapshot = db.snapshot().await.unwrap();
+    let user = snapshot
+        .users
+        .iter()
+        .find(|u| u.username == username)
+        .unwrap();
+    assert_eq!(user.password, password);
+    assert_eq!(user.secret, secret);
+
+    let gist = snapshot
+        .gists
+        .iter()
+        .find(|g| g.public_id == public_id)
+        .unwrap();
+    assert_eq!(gist.owner_id, user.id);
+    assert_eq!(gist.description.as_deref(), Some("foo"));
+    assert_eq!(gist.visibility, GistVisibility::Public);
+    let fork = snapshot
+        .gists
+        .iter()
+        .find(|g| g.public_id == fork_public_id)
+        .unwrap();
+    assert_eq!(fork.visibility, GistVisibility::Private);
+    assert!(snapshot
+        .forks
+        .iter()
+        .any(|f| f.gist_id == fork.id && f.parent_id == gist.id));
+    assert!(snapshot
+        .stars
+        .iter()
+        .any(|s| s.owner_id == user.id && s.gist_id == gist.id));
+    let snapshot_comment = snapshot
+        .comments
+        .iter()
+        .find(|c| c.id == comment_id)
+        .unwrap();
+    assert_eq!(snapshot_comment.owner_id, user.id);
+    assert_eq!(snapshot_comment.gist_id, gist.id);
+    assert_eq!(snapshot_comment.comment.as_deref(), Some("foo"));
+    let token = snapshot
+        .access_tokens
+        .iter()
+        .find(|t| t.id == token_id)
+        .unwrap();
+    assert_eq!(token.owner_id, user.id);
+    assert_eq!(token.token_hash, secret);
+    assert_eq!(token.scopes, [AccessTokenScope::ReadGists]);
+
+    // snapshots can only be restored into empty databases
+    assert!(matches!(
+        db.restore(&snapshot).await.err(),
+        Some(DBError::DatabaseNotEmpty)
+    ));
+    snapshot
+}
+
+/// test restore implementation. `db` must be empty
+pub async fn restore_works<T: GPDatabse>(
+    db: &T,
+    snapshot: &Snapshot,
+    username: &str,
+    password: &str,
+    secret: &str,
+) {
+    db.restore(snapshot).await.unwrap();
+    assert_eq!(&db.snapshot().await.unwrap(), snapshot);
+    assert!(matches!(
+        db.restore(snapshot).await.err(),
+        Some(DBError::DatabaseNotEmpty)
+    ));
+
+    for gist in snapshot.gists.iter() {
+        let owner = snapshot
+            .users
+            .iter()
+            .find(|u| u.id == gist.owner_id)
+            .unwrap();
+        let restored = db.get_gist(&gist.public_id).await.unwrap();
+        assert_eq!(restored.owner, owner.username);
+        assert_eq!(restored.created, gist.created);
+        assert_eq!(restored.updated, gist.updated);
+    }
+
+    // IDs assigned after restoring don't collide with restored rows
+    let register_payload = UsernameRegisterPayload {
+        username,
+        password,
+        secret,
+    };
+    db.username_register(&register_payload).await.unwrap();
+    let user = db
+        .snapshot()
+        .await
+        .unwrap()
+        .users
+        .into_iter()
+        .find(|u| u.username == username)
+        .unwrap();
+    assert!(snapshot.users.iter().all(|u| u.id < user.id));
+}
+
+/// test copying data between databases. `destination` must be empty
+pub async fn copy_database_works<S: GPDatabse, D: GPDatabse>(source: &S, destination: &D) {
+    let counts = copy_database(source, destination).await.unwrap();
+    let snapshot = destination.snapshot().await.unwrap();
+    assert_eq!(snapshot.counts(), counts);
+    for user in snapshot.users.iter() {
+        assert_eq!(
+            destination.get_secret(&user.username).await.unwrap(),
+            user.secret
+        );
+    }
+    for gist in snapshot.gists.iter() {
+        let copied = destination.get_gist(&gist.public_id).await.unwrap();
+        assert_eq!(copied.created, gist.created);
+        assert_eq!(copied.updated, gist.updated);
+    }
+
+    assert!(matches!(
+        copy_database(source, destination).await.err(),
+        Some(DBError::DatabaseNotEmpty)
+    ));
 }
 
 /// test username registration implementation
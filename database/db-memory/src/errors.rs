@@ -0,0 +1,30 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! errors that are specific to the in-memory database
+use db_core::dev::*;
+use thiserror::Error;
+
+/// Violation of a constraint that the SQL backends enforce in their schema and that
+/// doesn't have a dedicated [DBError] variant
+#[derive(Debug, Error)]
+#[error("{0} constraint failed")]
+pub struct ConstraintError(pub &'static str);
+
+/// Wrap a constraint violation in [DBError]
+pub fn constraint_err(constraint: &'static str) -> DBError {
+    DBError::DBError(Box::new(ConstraintError(constraint)))
+}
@@ -0,0 +1,986 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+#![deny(missing_docs)]
+//! # In-memory database for gitpad
+//!
+//! Implements [GPDatabse] without an external database server, data is lost when the
+//! last handle to the database is dropped. Useful for running tests and trying out
+//! gitpad. Semantics of the SQL backends, like unique constraints and cascading
+//! deletes, are replicated.
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use db_core::dev::*;
+
+pub mod errors;
+#[cfg(test)]
+mod tests;
+
+/// In-memory database. Clones share data, like clones of a connection pool
+#[derive(Clone, Default)]
+pub struct Database {
+    tables: Arc<RwLock<Tables>>,
+}
+
+/// Use an existing database
+pub struct Conn(pub Database);
+
+/// Connect to databse
+pub enum ConnectionOptions {
+    /// new, empty database
+    Fresh,
+    /// existing database
+    Existing(Conn),
+}
+
+pub mod dev {
+    //! useful imports for working with the in-memory database
+    pub use super::errors::*;
+    pub use super::prelude::*;
+    pub use db_core::dev::*;
+}
+
+pub mod prelude {
+    //! useful imports for users of the in-memory database
+    pub use super::{Conn, ConnectionOptions, Database};
+    pub use db_core::prelude::*;
+}
+
+#[async_trait]
+impl Connect for ConnectionOptions {
+    type Pool = Database;
+    async fn connect(self) -> DBResult<Self::Pool> {
+        let db = match self {
+            Self::Fresh => Database::default(),
+            Self::Existing(conn) => conn.0,
+        };
+        Ok(db)
+    }
+}
+
+use dev::*;
+
+#[async_trait]
+impl Migrate for Database {
+    /// Tables are created along with the database, there is nothing to migrate
+    async fn migrate(&self) -> DBResult<()> {
+        Ok(())
+    }
+}
+
+/// Search index entry of a gist
+struct SearchRecord {
+    description: Option<String>,
+    filenames: String,
+    content: String,
+}
+
+/// Rows of all tables, keyed by their IDs
+#[derive(Default)]
+struct Tables {
+    users: BTreeMap<i64, UserRecord>,
+    gists: BTreeMap<i64, GistRecord>,
+    comments: BTreeMap<i64, CommentRecord>,
+    forks: BTreeMap<i64, ForkRecord>,
+    stars: BTreeMap<i64, StarRecord>,
+    access_tokens: BTreeMap<i64, AccessTokenRecord>,
+    /// keyed by gist ID
+    search: BTreeMap<i64, SearchRecord>,
+    sequences: Sequences,
+}
+
+/// Last ID assigned in each table
+#[derive(Default)]
+struct Sequences {
+    users: i64,
+    gists: i64,
+    comments: i64,
+    forks: i64,
+    stars: i64,
+    access_tokens: i64,
+}
+
+impl Database {
+    fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap()
+    }
+}
+
+impl Tables {
+    fn user_id(&self, username: &str) -> Option<i64> {
+        self.users
+            .values()
+            .find(|u| u.username == username)
+            .map(|u| u.id)
+    }
+
+    fn gist_id(&self, public_id: &str) -> Option<i64> {
+        self.gists
+            .values()
+            .find(|g| g.public_id == public_id)
+            .map(|g| g.id)
+    }
+
+    /// Check unique constraints on users, ignoring user with ID `skip`
+    fn check_user_unique(
+        &self,
+        skip: Option<i64>,
+        username: Option<&str>,
+        email: Option<&str>,
+        secret: Option<&str>,
+    ) -> DBResult<()> {
+        for u in self.users.values().filter(|u| Some(u.id) != skip) {
+            if username == Some(u.username.as_str()) {
+                return Err(DBError::DuplicateUsername);
+            }
+            if email.is_some() && email == u.email.as_deref() {
+                return Err(DBError::DuplicateEmail);
+            }
+            if secret == Some(u.secret.as_str()) {
+                return Err(DBError::DuplicateSecret);
+            }
+        }
+        Ok(())
+    }
+
+    fn register(
+        &mut self,
+        username: &str,
+        password: &str,
+        email: Option<&str>,
+        secret: &str,
+    ) -> DBResult<()> {
+        self.check_user_unique(None, Some(username), email, Some(secret))?;
+        let id = next_id(&mut self.sequences.users);
+        self.users.insert(
+            id,
+            UserRecord {
+                id,
+                username: username.into(),
+                email: email.map(|e| e.into()),
+                email_verified: None,
+                secret: secret.into(),
+                password: password.into(),
+            },
+        );
+        Ok(())
+    }
+
+    fn user_mut(&mut self, username: &str) -> DBResult<&mut UserRecord> {
+        self.users
+            .values_mut()
+            .find(|u| u.username == username)
+            .ok_or(DBError::AccountNotFound)
+    }
+
+    fn to_gist(&self, g: &GistRecord) -> Gist {
+        Gist {
+            owner: self
+                .users
+                .get(&g.owner_id)
+                .map(|u| u.username.clone())
+                .unwrap_or_default(),
+            description: g.description.clone(),
+            public_id: g.public_id.clone(),
+            created: g.created,
+            updated: g.updated,
+            visibility: g.visibility.clone(),
+        }
+    }
+
+    fn to_gists<'a>(&self, gists: impl Iterator<Item = &'a GistRecord>) -> Vec<Gist> {
+        gists.map(|g| self.to_gist(g)).collect()
+    }
+
+    fn to_comment(&self, c: &CommentRecord) -> GistComment {
+        GistComment {
+            id: c.id,
+            owner: self
+                .users
+                .get(&c.owner_id)
+                .map(|u| u.username.clone())
+                .unwrap_or_default(),
+            gist_public_id: self
+                .gists
+                .get(&c.gist_id)
+                .map(|g| g.public_id.clone())
+                .unwrap_or_default(),
+            comment: c.comment.clone().unwrap_or_default(),
+            created: c.created,
+        }
+    }
+
+    fn to_comments<'a>(
+        &self,
+        comments: impl Iterator<Item = &'a CommentRecord>,
+    ) -> Vec<GistComment> {
+        let mut comments: Vec<&CommentRecord> = comments.collect();
+        comments.sort_by_key(|c| (c.created, c.id));
+        comments.iter().map(|c| self.to_comment(c)).collect()
+    }
+
+    fn to_access_token(&self, t: &AccessTokenRecord) -> AccessToken {
+        AccessToken {
+            id: t.id,
+            owner: self
+                .users
+                .get(&t.owner_id)
+                .map(|u| u.username.clone())
+                .unwrap_or_default(),
+            name: t.name.clone(),
+            scopes: t.scopes.clone(),
+            created: t.created,
+            last_used: t.last_used,
+        }
+    }
+
+    /// Delete gists and rows that refer to them
+    fn delete_gists(&mut self, ids: &[i64]) {
+        for id in ids {
+            self.gists.remove(id);
+            self.search.remove(id);
+        }
+        self.comments.retain(|_, c| !ids.contains(&c.gist_id));
+        self.stars.retain(|_, s| !ids.contains(&s.gist_id));
+        self.forks
+            .retain(|_, f| !ids.contains(&f.gist_id) && !ids.contains(&f.parent_id));
+    }
+
+    /// Delete user and rows that refer to them
+    fn delete_user(&mut self, id: i64) {
+        self.users.remove(&id);
+        let gists: Vec<i64> = self
+            .gists
+            .values()
+            .filter(|g| g.owner_id == id)
+            .map(|g| g.id)
+            .collect();
+        self.delete_gists(&gists);
+        self.comments.retain(|_, c| c.owner_id != id);
+        self.stars.retain(|_, s| s.owner_id != id);
+        self.access_tokens.retain(|_, t| t.owner_id != id);
+    }
+}
+
+#[async_trait]
+impl GPDatabse for Database {
+    async fn email_login(&self, email: &str) -> DBResult<Creds> {
+        self.read()
+            .users
+            .values()
+            .find(|u| u.email.as_deref() == Some(email))
+            .map(|u| Creds {
+                username: u.username.clone(),
+                password: u.password.clone(),
+            })
+            .ok_or(DBError::AccountNotFound)
+    }
+
+    async fn username_login(&self, username: &str) -> DBResult<Password> {
+        self.read()
+            .users
+            .values()
+            .find(|u| u.username == username)
+            .map(|u| Password {
+                password: u.password.clone(),
+            })
+            .ok_or(DBError::AccountNotFound)
+    }
+
+    async fn email_register(&self, payload: &EmailRegisterPayload) -> DBResult<()> {
+        self.write().register(
+            payload.username,
+            payload.password,
+            Some(payload.email),
+            payload.secret,
+        )
+    }
+
+    async fn username_register(&self, payload: &UsernameRegisterPayload) -> DBResult<()> {
+        self.write()
+            .register(payload.username, payload.password, None, payload.secret)
+    }
+
+    async fn update_email(&self, payload: &UpdateEmailPayload) -> DBResult<()> {
+        let mut tables = self.write();
+        let id = tables
+            .user_id(payload.username)
+            .ok_or(DBError::AccountNotFound)?;
+        tables.check_user_unique(Some(id), None, Some(payload.email), None)?;
+        tables.user_mut(payload.username)?.email = Some(payload.email.into());
+        Ok(())
+    }
+
+    async fn update_password(&self, payload: &Creds) -> DBResult<()> {
+        self.write().user_mut(&payload.username)?.password = payload.password.clone();
+        Ok(())
+    }
+
+    async fn email_exists(&self, email: &str) -> DBResult<bool> {
+        Ok(self
+            .read()
+            .users
+            .values()
+            .any(|u| u.email.as_deref() == Some(email)))
+    }
+
+    async fn delete_account(&self, username: &str) -> DBResult<()> {
+        let mut tables = self.write();
+        if let Some(id) = tables.user_id(username) {
+            tables.delete_user(id);
+        }
+        Ok(())
+    }
+
+    async fn username_exists(&self, username: &str) -> DBResult<bool> {
+        Ok(self.read().user_id(username).is_some())
+    }
+
+    async fn update_username(&self, payload: &UpdateUsernamePayload) -> DBResult<()> {
+        let mut tables = self.write();
+        let id = tables
+            .user_id(payload.old_username)
+            .ok_or(DBError::AccountNotFound)?;
+        tables.check_user_unique(Some(id), Some(payload.new_username), None, None)?;
+        tables.user_mut(payload.old_username)?.username = payload.new_username.into();
+        Ok(())
+    }
+
+    async fn update_secret(&self, username: &str, secret: &str) -> DBResult<()> {
+        let mut tables = self.write();
+        let id = tables.user_id(username).ok_or(DBError::AccountNotFound)?;
+        tables.check_user_unique(Some(id), None, None, Some(secret))?;
+        tables.user_mut(username)?.secret = secret.into();
+        Ok(())
+    }
+
+    async fn get_secret(&self, username: &str) -> DBResult<String> {
+        self.read()
+            .users
+            .values()
+            .find(|u| u.username == username)
+            .map(|u| u.secret.clone())
+            .ok_or(DBError::AccountNotFound)
+    }
+
+    /// ping DB
+    async fn ping(&self) -> bool {
+        true
+    }
+
+    /// Check if a Gist with the given ID exists
+    async fn gist_exists(&self, public_id: &str) -> DBResult<bool> {
+        Ok(self.read().gist_id(public_id).is_some())
+    }
+
+    /// Create new gists
+    async fn new_gist(&self, gist: &CreateGist) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        let mut tables = self.write();
+        let owner_id = tables
+            .user_id(gist.owner)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_gists.owner_id"))?;
+        if tables.gist_id(gist.public_id).is_some() {
+            return Err(DBError::GistIDTaken);
+        }
+        let id = next_id(&mut tables.sequences.gists);
+        tables.gists.insert(
+            id,
+            GistRecord {
+                id,
+                owner_id,
+                description: gist.description.map(|d| d.into()),
+                visibility: gist.visibility.clone(),
+                created: now,
+                updated: now,
+                public_id: gist.public_id.into(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Retrieve gist from database
+    async fn get_gist(&self, public_id: &str) -> DBResult<Gist> {
+        let tables = self.read();
+        tables
+            .gists
+            .values()
+            .find(|g| g.public_id == public_id)
+            .map(|g| tables.to_gist(g))
+            .ok_or(DBError::GistNotFound)
+    }
+
+    /// Update description and visibility of a gist
+    async fn update_gist(&self, gist: &UpdateGist) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        let mut tables = self.write();
+        let owner_id = tables.user_id(gist.owner);
+        let record = tables
+            .gists
+            .values_mut()
+            .find(|g| g.public_id == gist.public_id && Some(g.owner_id) == owner_id)
+            .ok_or(DBError::GistNotFound)?;
+        record.description = gist.description.map(|d| d.into());
+        record.visibility = gist.visibility.clone();
+        record.updated = now;
+        let id = record.id;
+        if let Some(search) = tables.search.get_mut(&id) {
+            search.description = gist.description.map(|d| d.into());
+        }
+        Ok(())
+    }
+
+    /// Set last modified time of a gist to current time
+    async fn touch_gist(&self, public_id: &str) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        let mut tables = self.write();
+        let record = tables
+            .gists
+            .values_mut()
+            .find(|g| g.public_id == public_id)
+            .ok_or(DBError::GistNotFound)?;
+        record.updated = now;
+        Ok(())
+    }
+
+    /// Set creation and last modified time of a gist
+    async fn set_gist_timestamps(
+        &self,
+        public_id: &str,
+        created: i64,
+        updated: i64,
+    ) -> DBResult<()> {
+        let mut tables = self.write();
+        let record = tables
+            .gists
+            .values_mut()
+            .find(|g| g.public_id == public_id)
+            .ok_or(DBError::GistNotFound)?;
+        record.created = created;
+        record.updated = updated;
+        Ok(())
+    }
+
+    /// Retrieve gists belonging to user from database
+    async fn get_user_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
+        let tables = self.read();
+        let owner_id = tables.user_id(owner);
+        Ok(tables.to_gists(
+            tables
+                .gists
+                .values()
+                .filter(|g| Some(g.owner_id) == owner_id),
+        ))
+    }
+
+    /// Retrieve all gists, regardless of their visibility, oldest first
+    async fn get_all_gists(&self) -> DBResult<Vec<Gist>> {
+        let tables = self.read();
+        let mut gists: Vec<&GistRecord> = tables.gists.values().collect();
+        gists.sort_by_key(|g| g.created);
+        Ok(tables.to_gists(gists.into_iter()))
+    }
+
+    /// Retrieve gists belonging to user from database
+    async fn get_user_public_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
+        let tables = self.read();
+        let owner_id = tables.user_id(owner);
+        Ok(tables.to_gists(
+            tables
+                .gists
+                .values()
+                .filter(|g| Some(g.owner_id) == owner_id && g.visibility == GistVisibility::Public),
+        ))
+    }
+
+    /// Retrieve gists belonging to user from database
+    async fn get_user_public_unlisted_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
+        let tables = self.read();
+        let owner_id = tables.user_id(owner);
+        Ok(tables.to_gists(
+            tables.gists.values().filter(|g| {
+                Some(g.owner_id) == owner_id && g.visibility != GistVisibility::Private
+            }),
+        ))
+    }
+
+    async fn delete_gist(&self, owner: &str, public_id: &str) -> DBResult<()> {
+        let mut tables = self.write();
+        let owner_id = tables.user_id(owner);
+        let id = tables
+            .gists
+            .values()
+            .find(|g| g.public_id == public_id && Some(g.owner_id) == owner_id)
+            .map(|g| g.id)
+            .ok_or(DBError::GistNotFound)?;
+        tables.delete_gists(&[id]);
+        Ok(())
+    }
+
+    /// Record that gist `fork` was forked from gist `parent`
+    async fn new_fork(&self, parent: &str, fork: &str) -> DBResult<()> {
+        let mut tables = self.write();
+        let gist_id = tables
+            .gist_id(fork)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_forks.gist_id"))?;
+        let parent_id = tables
+            .gist_id(parent)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_forks.parent_id"))?;
+        if tables.forks.values().any(|f| f.gist_id == gist_id) {
+            return Err(constraint_err("UNIQUE: gists_forks.gist_id"));
+        }
+        let id = next_id(&mut tables.sequences.forks);
+        tables.forks.insert(
+            id,
+            ForkRecord {
+                id,
+                gist_id,
+                parent_id,
+            },
+        );
+        Ok(())
+    }
+
+    /// Get gist from which a gist was forked
+    async fn get_fork_parent(&self, public_id: &str) -> DBResult<Option<Gist>> {
+        let tables = self.read();
+        let gist_id = tables.gist_id(public_id);
+        Ok(tables
+            .forks
+            .values()
+            .find(|f| Some(f.gist_id) == gist_id)
+            .and_then(|f| tables.gists.get(&f.parent_id))
+            .map(|g| tables.to_gist(g)))
+    }
+
+    /// Get forks of a gist
+    async fn get_forks(&self, public_id: &str) -> DBResult<Vec<Gist>> {
+        let tables = self.read();
+        let parent_id = tables.gist_id(public_id);
+        let mut forks: Vec<&GistRecord> = tables
+            .forks
+            .values()
+            .filter(|f| Some(f.parent_id) == parent_id)
+            .filter_map(|f| tables.gists.get(&f.gist_id))
+            .collect();
+        forks.sort_by_key(|g| Reverse(g.created));
+        Ok(tables.to_gists(forks.into_iter()))
+    }
+
+    /// Star a gist
+    async fn star_gist(&self, username: &str, public_id: &str) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        let mut tables = self.write();
+        let owner_id = tables
+            .user_id(username)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_stars.owner_id"))?;
+        let gist_id = tables
+            .gist_id(public_id)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_stars.gist_id"))?;
+        if tables
+            .stars
+            .values()
+            .any(|s| s.owner_id == owner_id && s.gist_id == gist_id)
+        {
+            return Ok(());
+        }
+        let id = next_id(&mut tables.sequences.stars);
+        tables.stars.insert(
+            id,
+            StarRecord {
+                id,
+                owner_id,
+                gist_id,
+                created: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// Remove star from a gist
+    async fn unstar_gist(&self, username: &str, public_id: &str) -> DBResult<()> {
+        let mut tables = self.write();
+        let owner_id = tables.user_id(username);
+        let gist_id = tables.gist_id(public_id);
+        tables
+            .stars
+            .retain(|_, s| !(Some(s.owner_id) == owner_id && Some(s.gist_id) == gist_id));
+        Ok(())
+    }
+
+    /// Check if user has starred a gist
+    async fn is_starred(&self, username: &str, public_id: &str) -> DBResult<bool> {
+        let tables = self.read();
+        let owner_id = tables.user_id(username);
+        let gist_id = tables.gist_id(public_id);
+        Ok(tables
+            .stars
+            .values()
+            .any(|s| Some(s.owner_id) == owner_id && Some(s.gist_id) == gist_id))
+    }
+
+    /// Get number of users that have starred a gist
+    async fn get_star_count(&self, public_id: &str) -> DBResult<i64> {
+        let tables = self.read();
+        let gist_id = tables.gist_id(public_id);
+        Ok(tables
+            .stars
+            .values()
+            .filter(|s| Some(s.gist_id) == gist_id)
+            .count() as i64)
+    }
+
+    /// Get gists starred by user, most recently starred first
+    async fn get_starred_gists(&self, username: &str) -> DBResult<Vec<Gist>> {
+        let tables = self.read();
+        let owner_id = tables.user_id(username);
+        let mut stars: Vec<&StarRecord> = tables
+            .stars
+            .values()
+            .filter(|s| Some(s.owner_id) == owner_id)
+            .collect();
+        stars.sort_by_key(|s| Reverse((s.created, s.id)));
+        Ok(tables.to_gists(stars.iter().filter_map(|s| tables.gists.get(&s.gist_id))))
+    }
+
+    async fn get_public_gists(
+        &self,
+        sort: GistSort,
+        after: Option<&GistCursor>,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>> {
+        let timestamp = |g: &GistRecord| match sort {
+            GistSort::Created => g.created,
+            GistSort::Updated => g.updated,
+        };
+        let tables = self.read();
+        let mut gists: Vec<&GistRecord> = tables
+            .gists
+            .values()
+            .filter(|g| g.visibility == GistVisibility::Public)
+            .filter(|g| {
+                after.is_none_or(|c| {
+                    (timestamp(g), g.public_id.as_str()) < (c.timestamp, c.public_id)
+                })
+            })
+            .collect();
+        gists.sort_by(|a, b| (timestamp(b), &b.public_id).cmp(&(timestamp(a), &a.public_id)));
+        Ok(tables.to_gists(gists.into_iter().take(limit as usize)))
+    }
+
+    async fn index_gist(&self, doc: &GistSearchDocument) -> DBResult<()> {
+        let mut tables = self.write();
+        let gist = tables
+            .gists
+            .values()
+            .find(|g| g.public_id == doc.public_id)
+            .ok_or(DBError::GistNotFound)?;
+        let id = gist.id;
+        let search = SearchRecord {
+            description: gist.description.clone(),
+            filenames: doc.filenames.into(),
+            content: doc.content.into(),
+        };
+        tables.search.insert(id, search);
+        Ok(())
+    }
+
+    /// Every term in `query` must occur in the gist. Terms are matched against whole
+    /// words, ignoring case; unlike the SQL backends, words aren't stemmed
+    async fn search_gists(
+        &self,
+        query: &str,
+        viewer: Option<&str>,
+        offset: u32,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>> {
+        let terms: Vec<Vec<String>> = query
+            .split_whitespace()
+            .map(tokenize)
+            .filter(|t| !t.is_empty())
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::default());
+        }
+
+        let tables = self.read();
+        let viewer_id = viewer.and_then(|v| tables.user_id(v));
+        let mut results: Vec<(usize, &GistRecord)> = tables
+            .search
+            .iter()
+            .filter_map(|(id, search)| {
+                let gist = tables.gists.get(id)?;
+                if gist.visibility != GistVisibility::Public && Some(gist.owner_id) != viewer_id {
+                    return None;
+                }
+                let description = tokenize(search.description.as_deref().unwrap_or_default());
+                let filenames = tokenize(&search.filenames);
+                let content = tokenize(&search.content);
+                let mut score = 0;
+                for term in terms.iter() {
+                    // weighted like the ranking of the SQL backends: matches in
+                    // descriptions and filenames count more than matches in contents
+                    let hits = 10 * count_phrase(&description, term)
+                        + 10 * count_phrase(&filenames, term)
+                        + count_phrase(&content, term);
+                    if hits == 0 {
+                        return None;
+                    }
+                    score += hits;
+                }
+                Some((score, gist))
+            })
+            .collect();
+        results
+            .sort_by(|(a_score, a), (b_score, b)| (b_score, b.updated).cmp(&(a_score, a.updated)));
+        Ok(tables.to_gists(
+            results
+                .into_iter()
+                .map(|(_, g)| g)
+                .skip(offset as usize)
+                .take(limit as usize),
+        ))
+    }
+
+    /// Create new comment
+    async fn new_comment(&self, comment: &CreateGistComment) -> DBResult<i64> {
+        let now = now_unix_time_stamp();
+        let mut tables = self.write();
+        let owner_id = tables
+            .user_id(comment.owner)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_comments.owner_id"))?;
+        let gist_id = tables
+            .gist_id(comment.gist_public_id)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_comments.gist_id"))?;
+        let id = next_id(&mut tables.sequences.comments);
+        tables.comments.insert(
+            id,
+            CommentRecord {
+                id,
+                owner_id,
+                gist_id,
+                comment: Some(comment.comment.into()),
+                created: now,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Get comments on a gist
+    async fn get_comments_on_gist(&self, public_id: &str) -> DBResult<Vec<GistComment>> {
+        let tables = self.read();
+        let gist_id = tables.gist_id(public_id);
+        Ok(tables.to_comments(
+            tables
+                .comments
+                .values()
+                .filter(|c| Some(c.gist_id) == gist_id),
+        ))
+    }
+
+    /// Get comments authored by user, oldest first
+    async fn get_user_comments(&self, owner: &str) -> DBResult<Vec<GistComment>> {
+        let tables = self.read();
+        let owner_id = tables.user_id(owner);
+        Ok(tables.to_comments(
+            tables
+                .comments
+                .values()
+                .filter(|c| Some(c.owner_id) == owner_id),
+        ))
+    }
+
+    /// Get a specific comment using its database assigned ID
+    async fn get_comment_by_id(&self, id: i64) -> DBResult<GistComment> {
+        let tables = self.read();
+        tables
+            .comments
+            .get(&id)
+            .map(|c| tables.to_comment(c))
+            .ok_or(DBError::CommentNotFound)
+    }
+
+    /// Delete comment
+    async fn delete_comment(&self, owner: &str, id: i64) -> DBResult<()> {
+        let mut tables = self.write();
+        let owner_id = tables.user_id(owner);
+        if tables
+            .comments
+            .get(&id)
+            .is_some_and(|c| Some(c.owner_id) == owner_id)
+        {
+            tables.comments.remove(&id);
+        }
+        Ok(())
+    }
+
+    async fn new_access_token(&self, token: &CreateAccessToken) -> DBResult<i64> {
+        let now = now_unix_time_stamp();
+        let mut tables = self.write();
+        let owner_id = tables
+            .user_id(token.owner)
+            .ok_or_else(|| constraint_err("NOT NULL: gists_access_tokens.owner_id"))?;
+        for t in tables.access_tokens.values() {
+            if t.owner_id == owner_id && t.name == token.name {
+                return Err(DBError::DuplicateAccessTokenName);
+            }
+            if t.token_hash == token.token_hash {
+                return Err(constraint_err("UNIQUE: gists_access_tokens.token_hash"));
+            }
+        }
+        let id = next_id(&mut tables.sequences.access_tokens);
+        tables.access_tokens.insert(
+            id,
+            AccessTokenRecord {
+                id,
+                owner_id,
+                name: token.name.into(),
+                token_hash: token.token_hash.into(),
+                scopes: token.scopes.to_vec(),
+                created: now,
+                last_used: None,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn get_access_tokens(&self, owner: &str) -> DBResult<Vec<AccessToken>> {
+        let tables = self.read();
+        let owner_id = tables.user_id(owner);
+        let mut tokens: Vec<&AccessTokenRecord> = tables
+            .access_tokens
+            .values()
+            .filter(|t| Some(t.owner_id) == owner_id)
+            .collect();
+        tokens.sort_by_key(|t| Reverse((t.created, t.id)));
+        Ok(tokens.iter().map(|t| tables.to_access_token(t)).collect())
+    }
+
+    async fn get_access_token_by_hash(&self, token_hash: &str) -> DBResult<AccessToken> {
+        let tables = self.read();
+        tables
+            .access_tokens
+            .values()
+            .find(|t| t.token_hash == token_hash)
+            .map(|t| tables.to_access_token(t))
+            .ok_or(DBError::AccessTokenNotFound)
+    }
+
+    async fn touch_access_token(&self, id: i64) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        if let Some(token) = self.write().access_tokens.get_mut(&id) {
+            token.last_used = Some(now);
+        }
+        Ok(())
+    }
+
+    async fn delete_access_token(&self, owner: &str, id: i64) -> DBResult<()> {
+        let mut tables = self.write();
+        let owner_id = tables.user_id(owner);
+        match tables.access_tokens.get(&id) {
+            Some(t) if Some(t.owner_id) == owner_id => {
+                tables.access_tokens.remove(&id);
+                Ok(())
+            }
+            _ => Err(DBError::AccessTokenNotFound),
+        }
+    }
+
+    async fn snapshot(&self) -> DBResult<Snapshot> {
+        let tables = self.read();
+        Ok(Snapshot {
+            users: tables.users.values().cloned().collect(),
+            gists: tables.gists.values().cloned().collect(),
+            comments: tables.comments.values().cloned().collect(),
+            forks: tables.forks.values().cloned().collect(),
+            stars: tables.stars.values().cloned().collect(),
+            access_tokens: tables.access_tokens.values().cloned().collect(),
+        })
+    }
+
+    async fn restore(&self, snapshot: &Snapshot) -> DBResult<()> {
+        let mut tables = self.write();
+        if !tables.users.is_empty() {
+            return Err(DBError::DatabaseNotEmpty);
+        }
+        tables.users = snapshot.users.iter().map(|u| (u.id, u.clone())).collect();
+        tables.gists = snapshot.gists.iter().map(|g| (g.id, g.clone())).collect();
+        tables.comments = snapshot
+            .comments
+            .iter()
+            .map(|c| (c.id, c.clone()))
+            .collect();
+        tables.forks = snapshot.forks.iter().map(|f| (f.id, f.clone())).collect();
+        tables.stars = snapshot.stars.iter().map(|s| (s.id, s.clone())).collect();
+        tables.access_tokens = snapshot
+            .access_tokens
+            .iter()
+            .map(|t| (t.id, t.clone()))
+            .collect();
+        tables.search.clear();
+        tables.sequences = Sequences {
+            users: last_id(&tables.users),
+            gists: last_id(&tables.gists),
+            comments: last_id(&tables.comments),
+            forks: last_id(&tables.forks),
+            stars: last_id(&tables.stars),
+            access_tokens: last_id(&tables.access_tokens),
+        };
+        Ok(())
+    }
+
+    /// All visibility modes are always available
+    async fn visibility_exists(&self, _visibility: &GistVisibility) -> DBResult<bool> {
+        Ok(true)
+    }
+}
+
+fn now_unix_time_stamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// ID for a new row. Like sequences of SQL databases, IDs of deleted rows aren't reused.
+fn next_id(sequence: &mut i64) -> i64 {
+    *sequence += 1;
+    *sequence
+}
+
+/// Largest ID in `table`, sequences continue from it after a restore
+fn last_id<T>(table: &BTreeMap<i64, T>) -> i64 {
+    table.keys().next_back().copied().unwrap_or_default()
+}
+
+/// Split `text` into lowercase words
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Number of times the words of `phrase` occur consecutively in `words`
+fn count_phrase(words: &[String], phrase: &[String]) -> usize {
+    words.windows(phrase.len()).filter(|w| *w == phrase).count()
+}
@@ -0,0 +1,187 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::*;
+
+use db_core::tests::*;
+
+async fn get_db() -> Database {
+    let db = ConnectionOptions::Fresh.connect().await.unwrap();
+    db.migrate().await.unwrap();
+    db
+}
+
+#[actix_rt::test]
+async fn everyting_works() {
+    const EMAIL: &str = "memoryuser@foo.com";
+    const EMAIL2: &str = "memoryuse2r@foo.com";
+    const NAME: &str = "memoryuser";
+    const NAME2: &str = "memoryuser2";
+    const NAME3: &str = "memoryuser3";
+    const NAME4: &str = "memoryuser4";
+    const NAME5: &str = "memoryuser5";
+    const NAME6: &str = "memoryuser6";
+    const NAME7: &str = "memoryuser7";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET1: &str = "memorysecret1";
+    const SECRET2: &str = "memorysecret2";
+    const SECRET3: &str = "memorysecret3";
+    const SECRET4: &str = "memorysecret4";
+
+    let db = get_db().await;
+    email_register_works(&db, EMAIL, NAME, PASSWORD, SECRET1, NAME5).await;
+    username_register_works(&db, NAME2, PASSWORD, SECRET2).await;
+    duplicate_secret_guard_works(&db, NAME3, PASSWORD, NAME4, SECRET3, SECRET2).await;
+    duplicate_username_and_email(&db, NAME6, NAME7, EMAIL2, PASSWORD, SECRET4, NAME, EMAIL).await;
+    let creds = Creds {
+        username: NAME.into(),
+        password: SECRET4.into(),
+    };
+    db.update_password(&creds).await.unwrap();
+}
+
+#[actix_rt::test]
+async fn visibility_test() {
+    let db = get_db().await;
+    visibility_works(&db).await;
+}
+
+#[actix_rt::test]
+async fn gist_test() {
+    const NAME: &str = "memorygisttest";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "memorygisttestsecret";
+    const PUBLIC_ID: &str = "memorygisttestsecret";
+
+    let db = get_db().await;
+    gists_work(&db, NAME, PASSWORD, SECRET, PUBLIC_ID).await;
+}
+
+#[actix_rt::test]
+async fn access_token_test() {
+    const NAME: &str = "memoryaccesstokentest";
+    const NAME2: &str = "memoryaccesstokentest2";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "memoryaccesstokentestsecret";
+    const SECRET2: &str = "memoryaccesstokentestsecret2";
+
+    let db = get_db().await;
+    access_tokens_work(&db, NAME, NAME2, PASSWORD, SECRET, SECRET2).await;
+}
+
+#[actix_rt::test]
+async fn snapshot_test() {
+    const NAME: &str = "memorysnapshottest";
+    const NAME2: &str = "memorysnapshottest2";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "memorysnapshottestsecret";
+    const SECRET2: &str = "memorysnapshottestsecret2";
+    const PUBLIC_ID: &str = "memorysnapshottest";
+    const FORK_PUBLIC_ID: &str = "memorysnapshottestfork";
+
+    let db = get_db().await;
+    let snapshot = snapshot_works(&db, NAME, PASSWORD, SECRET, PUBLIC_ID, FORK_PUBLIC_ID).await;
+
+    let empty = get_db().await;
+    restore_works(&empty, &snapshot, NAME2, PASSWORD, SECRET2).await;
+}
+
+#[actix_rt::test]
+async fn copy_database_test() {
+    const NAME: &str = "memorycopytest";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "memorycopytestsecret";
+    const PUBLIC_ID: &str = "memorycopytest";
+    const FORK_PUBLIC_ID: &str = "memorycopytestfork";
+
+    let db = get_db().await;
+    snapshot_works(&db, NAME, PASSWORD, SECRET, PUBLIC_ID, FORK_PUBLIC_ID).await;
+
+    let empty = get_db().await;
+    copy_database_works(&db, &empty).await;
+}
+
+#[actix_rt::test]
+async fn clones_share_data() {
+    const NAME: &str = "memoryclonetest";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "memoryclonetestsecret";
+
+    let db = get_db().await;
+    let clone = ConnectionOptions::Existing(Conn(db.clone()))
+        .connect()
+        .await
+        .unwrap();
+    username_register_works(&clone, NAME, PASSWORD, SECRET).await;
+    assert!(db.username_exists(NAME).await.unwrap());
+    assert!(!get_db().await.username_exists(NAME).await.unwrap());
+}
+
+#[actix_rt::test]
+async fn ids_are_not_reused() {
+    const NAME: &str = "memoryidtest";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "memoryidtestsecret";
+    const PUBLIC_ID: &str = "memoryidtest";
+
+    let db = get_db().await;
+    db.username_register(&UsernameRegisterPayload {
+        username: NAME,
+        password: PASSWORD,
+        secret: SECRET,
+    })
+    .await
+    .unwrap();
+    db.new_gist(&CreateGist {
+        owner: NAME,
+        description: None,
+        public_id: PUBLIC_ID,
+        visibility: &GistVisibility::Public,
+    })
+    .await
+    .unwrap();
+
+    let comment = CreateGistComment {
+        owner: NAME,
+        gist_public_id: PUBLIC_ID,
+        comment: "foo",
+    };
+    db.new_comment(&comment).await.unwrap();
+    let newest = db.new_comment(&comment).await.unwrap();
+    db.delete_comment(NAME, newest).await.unwrap();
+    assert!(db.new_comment(&comment).await.unwrap() > newest);
+
+    let mut token = CreateAccessToken {
+        owner: NAME,
+        name: "first",
+        token_hash: "memoryidtesthash1",
+        scopes: &[AccessTokenScope::ReadGists],
+    };
+    let newest = db.new_access_token(&token).await.unwrap();
+    db.delete_access_token(NAME, newest).await.unwrap();
+    token.name = "second";
+    token.token_hash = "memoryidtesthash2";
+    let newest = db.new_access_token(&token).await.unwrap();
+    assert!(newest > 1);
+
+    // restored databases continue from the largest restored ID
+    let snapshot = db.snapshot().await.unwrap();
+    let restored = get_db().await;
+    restored.restore(&snapshot).await.unwrap();
+    token.name = "third";
+    token.token_hash = "memoryidtesthash3";
+    assert_eq!(restored.new_access_token(&token).await.unwrap(), newest + 1);
+}
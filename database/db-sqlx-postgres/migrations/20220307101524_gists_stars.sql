@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS gists_stars (
+	owner_id INTEGER NOT NULL references gists_users(ID) ON DELETE CASCADE,
+	gist_id INTEGER NOT NULL references gists_gists(ID) ON DELETE CASCADE,
+	created timestamptz NOT NULL DEFAULT now(),
+	ID SERIAL PRIMARY KEY NOT NULL,
+	UNIQUE (owner_id, gist_id)
+);
+
+CREATE INDEX ON gists_stars(gist_id);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS gists_search (
+	gist_id INTEGER NOT NULL UNIQUE references gists_gists(ID) ON DELETE CASCADE,
+	description TEXT DEFAULT NULL,
+	filenames TEXT NOT NULL,
+	content TEXT NOT NULL,
+	document tsvector GENERATED ALWAYS AS (
+		setweight(to_tsvector('english', coalesce(description, '')), 'A') ||
+		setweight(to_tsvector('english', regexp_replace(filenames, '[._/-]', ' ', 'g')), 'A') ||
+		setweight(to_tsvector('english', content), 'B')
+	) STORED,
+	ID SERIAL PRIMARY KEY NOT NULL
+);
+
+CREATE INDEX ON gists_search USING GIN (document);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS gists_access_tokens (
+	owner_id INTEGER NOT NULL references gists_users(ID) ON DELETE CASCADE,
+	name VARCHAR(100) NOT NULL,
+	token_hash VARCHAR(64) NOT NULL UNIQUE,
+	scopes TEXT NOT NULL,
+	created timestamptz NOT NULL DEFAULT now(),
+	last_used timestamptz DEFAULT NULL,
+	ID SERIAL PRIMARY KEY NOT NULL,
+	UNIQUE (owner_id, name)
+);
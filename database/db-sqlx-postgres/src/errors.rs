@@ -33,6 +33,8 @@ pub fn map_register_err(e: Error) -> DBError {
                 DBError::DuplicateSecret
             } else if msg.contains("gists_gists_public_id") {
                 DBError::GistIDTaken
+            } else if msg.contains("gists_access_tokens_owner_id_name_key") {
+                DBError::DuplicateAccessTokenName
             } else {
                 DBError::DBError(Box::new(Error::Database(err)))
             }
@@ -81,3 +81,37 @@ async fn gist_test() {
     db.migrate().await.unwrap();
     gists_work(&db, NAME, PASSWORD, SECRET, PUBLIC_ID).await;
 }
+
+#[actix_rt::test]
+async fn access_token_test() {
+    const NAME: &str = "postgresaccesstokentest";
+    const NAME2: &str = "postgresaccesstokentest2";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "postgresaccesstokentestsecret";
+    const SECRET2: &str = "postgresaccesstokentestsecret2";
+
+    let url = env::var("POSTGRES_DATABASE_URL").unwrap();
+    let pool_options = PgPoolOptions::new().max_connections(2);
+    let connection_options = ConnectionOptions::Fresh(Fresh { pool_options, url });
+    let db = connection_options.connect().await.unwrap();
+
+    db.migrate().await.unwrap();
+    access_tokens_work(&db, NAME, NAME2, PASSWORD, SECRET, SECRET2).await;
+}
+
+#[actix_rt::test]
+async fn snapshot_test() {
+    const NAME: &str = "postgressnapshottest";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "postgressnapshottestsecret";
+    const PUBLIC_ID: &str = "postgressnapshottest";
+    const FORK_PUBLIC_ID: &str = "postgressnapshottestfork";
+
+    let url = env::var("POSTGRES_DATABASE_URL").unwrap();
+    let pool_options = PgPoolOptions::new().max_connections(2);
+    let connection_options = ConnectionOptions::Fresh(Fresh { pool_options, url });
+    let db = connection_options.connect().await.unwrap();
+
+    db.migrate().await.unwrap();
+    snapshot_works(&db, NAME, PASSWORD, SECRET, PUBLIC_ID, FORK_PUBLIC_ID).await;
+}
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS gists_forks (
+	gist_id INTEGER NOT NULL UNIQUE references gists_gists(ID) ON DELETE CASCADE,
+	parent_id INTEGER NOT NULL references gists_gists(ID) ON DELETE CASCADE,
+	ID INTEGER PRIMARY KEY NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS gists_forks_parent_id_index ON gists_forks (parent_id);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS gists_stars (
+	owner_id INTEGER NOT NULL references gists_users(ID) ON DELETE CASCADE,
+	gist_id INTEGER NOT NULL references gists_gists(ID) ON DELETE CASCADE,
+	created INTEGER NOT NULL,
+	ID INTEGER PRIMARY KEY NOT NULL,
+	UNIQUE (owner_id, gist_id)
+);
+
+CREATE INDEX IF NOT EXISTS gists_stars_gist_id_index ON gists_stars (gist_id);
@@ -0,0 +1,13 @@
+CREATE VIRTUAL TABLE IF NOT EXISTS gists_search USING fts5(
+	public_id UNINDEXED,
+	description,
+	filenames,
+	content,
+	tokenize = 'porter unicode61'
+);
+
+-- FTS5 tables can't have foreign keys
+CREATE TRIGGER IF NOT EXISTS gists_search_delete AFTER DELETE ON gists_gists
+BEGIN
+	DELETE FROM gists_search WHERE public_id = old.public_id;
+END;
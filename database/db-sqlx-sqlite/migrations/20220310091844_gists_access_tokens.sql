@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS gists_access_tokens (
+	owner_id INTEGER NOT NULL references gists_users(ID) ON DELETE CASCADE,
+	name VARCHAR(100) NOT NULL,
+	token_hash VARCHAR(64) NOT NULL UNIQUE,
+	scopes TEXT NOT NULL,
+	created INTEGER NOT NULL,
+	last_used INTEGER DEFAULT NULL,
+	ID INTEGER PRIMARY KEY NOT NULL,
+	UNIQUE (owner_id, name)
+);
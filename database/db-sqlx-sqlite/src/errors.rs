@@ -32,6 +32,8 @@ pub fn map_register_err(e: Error) -> DBError {
                 DBError::DuplicateSecret
             } else if msg.contains("gists_gists.public_id") {
                 DBError::GistIDTaken
+            } else if msg.contains("gists_access_tokens.name") {
+                DBError::DuplicateAccessTokenName
             } else {
                 DBError::DBError(Box::new(Error::Database(err)))
             }
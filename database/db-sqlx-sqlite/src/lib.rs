@@ -48,14 +48,13 @@ pub struct Fresh {
 
 pub mod dev {
     pub use super::errors::*;
-    pub use super::Database;
+    pub use super::prelude::*;
     pub use db_core::dev::*;
-    pub use prelude::*;
     pub use sqlx::Error;
 }
 
 pub mod prelude {
-    pub use super::*;
+    pub use super::{Conn, ConnectionOptions, Database, Fresh};
     pub use db_core::prelude::*;
 }
 
@@ -347,6 +346,83 @@ impl GPDatabse for Database {
         res.into_gist()
     }
 
+    /// Update description and visibility of a gist
+    async fn update_gist(&self, gist: &UpdateGist) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        let visibility = gist.visibility.to_str();
+        let res = sqlx::query!(
+            "UPDATE gists_gists
+        SET
+            description = $1,
+            visibility = (SELECT ID FROM gists_visibility WHERE name = $2),
+            updated = $3
+        WHERE
+            public_id = $4
+        AND
+            owner_id = (SELECT ID FROM gists_users WHERE username = $5)
+        ",
+            gist.description,
+            visibility,
+            now,
+            gist.public_id,
+            gist.owner
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(map_register_err)?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::GistNotFound);
+        }
+        sqlx::query!(
+            "UPDATE gists_search SET description = $1 WHERE public_id = $2",
+            gist.description,
+            gist.public_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        Ok(())
+    }
+
+    /// Set last modified time of a gist to current time
+    async fn touch_gist(&self, public_id: &str) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        let res = sqlx::query!(
+            "UPDATE gists_gists SET updated = $1 WHERE public_id = $2",
+            now,
+            public_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(map_register_err)?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::GistNotFound);
+        }
+        Ok(())
+    }
+
+    /// Set creation and last modified time of a gist
+    async fn set_gist_timestamps(
+        &self,
+        public_id: &str,
+        created: i64,
+        updated: i64,
+    ) -> DBResult<()> {
+        let res = sqlx::query!(
+            "UPDATE gists_gists SET created = $1, updated = $2 WHERE public_id = $3",
+            created,
+            updated,
+            public_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(map_register_err)?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::GistNotFound);
+        }
+        Ok(())
+    }
+
     /// Retrieve gists belonging to user from database
     async fn get_user_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
         let mut res = sqlx::query_as!(
@@ -378,6 +454,36 @@ impl GPDatabse for Database {
         Ok(gists)
     }
 
+    /// Retrieve all gists, regardless of their visibility, oldest first
+    async fn get_all_gists(&self) -> DBResult<Vec<Gist>> {
+        let mut res = sqlx::query_as!(
+            InnerGist,
+            "SELECT
+                owner,
+                visibility,
+                created,
+                updated,
+                public_id,
+                description
+            FROM
+                gists_gists_view
+            ORDER BY created
+            "
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| match e {
+            Error::RowNotFound => DBError::GistNotFound,
+            e => DBError::DBError(Box::new(e)),
+        })?;
+
+        let mut gists = Vec::with_capacity(res.len());
+        for r in res.drain(..) {
+            gists.push(r.into_gist()?);
+        }
+        Ok(gists)
+    }
+
     /// Retrieve gists belonging to user from database
     async fn get_user_public_gists(&self, owner: &str) -> DBResult<Vec<Gist>> {
         const PUBLIC: &str = GistVisibility::Public.to_str();
@@ -451,7 +557,7 @@ impl GPDatabse for Database {
     }
 
     async fn delete_gist(&self, owner: &str, public_id: &str) -> DBResult<()> {
-        sqlx::query!(
+        let x = sqlx::query!(
             "DELETE FROM gists_gists 
         WHERE 
             public_id = $1
@@ -464,9 +570,355 @@ impl GPDatabse for Database {
         .execute(&self.pool)
         .await
         .map_err(map_register_err)?;
+        if x.rows_affected() == 0 {
+            return Err(DBError::GistNotFound);
+        }
+        Ok(())
+    }
+
+    /// Record that gist `fork` was forked from gist `parent`
+    async fn new_fork(&self, parent: &str, fork: &str) -> DBResult<()> {
+        sqlx::query!(
+            "INSERT INTO gists_forks (gist_id, parent_id)
+            VALUES (
+                (SELECT ID FROM gists_gists WHERE public_id = $1),
+                (SELECT ID FROM gists_gists WHERE public_id = $2)
+            )",
+            fork,
+            parent
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(map_register_err)?;
+        Ok(())
+    }
+
+    /// Get gist from which a gist was forked
+    async fn get_fork_parent(&self, public_id: &str) -> DBResult<Option<Gist>> {
+        let res = sqlx::query_as!(
+            InnerGist,
+            "SELECT
+                owner,
+                visibility,
+                created,
+                updated,
+                public_id,
+                description
+            FROM
+                gists_gists_view
+            WHERE public_id = (
+                SELECT gists_gists.public_id
+                FROM gists_forks
+                INNER JOIN gists_gists ON gists_gists.ID = gists_forks.parent_id
+                WHERE gists_forks.gist_id = (SELECT ID FROM gists_gists WHERE public_id = $1)
+            )
+            ",
+            public_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        res.map(|g| g.into_gist()).transpose()
+    }
+
+    /// Get forks of a gist
+    async fn get_forks(&self, public_id: &str) -> DBResult<Vec<Gist>> {
+        let mut res = sqlx::query_as!(
+            InnerGist,
+            "SELECT
+                owner,
+                visibility,
+                created,
+                updated,
+                public_id,
+                description
+            FROM
+                gists_gists_view
+            WHERE public_id IN (
+                SELECT gists_gists.public_id
+                FROM gists_forks
+                INNER JOIN gists_gists ON gists_gists.ID = gists_forks.gist_id
+                WHERE gists_forks.parent_id = (SELECT ID FROM gists_gists WHERE public_id = $1)
+            )
+            ORDER BY created DESC
+            ",
+            public_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        let mut gists = Vec::with_capacity(res.len());
+        for r in res.drain(..) {
+            gists.push(r.into_gist()?);
+        }
+        Ok(gists)
+    }
+
+    /// Star a gist
+    async fn star_gist(&self, username: &str, public_id: &str) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        sqlx::query!(
+            "INSERT INTO gists_stars (owner_id, gist_id, created)
+            VALUES (
+                (SELECT ID FROM gists_users WHERE username = $1),
+                (SELECT ID FROM gists_gists WHERE public_id = $2),
+                $3
+            )
+            ON CONFLICT (owner_id, gist_id) DO NOTHING",
+            username,
+            public_id,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(map_register_err)?;
+        Ok(())
+    }
+
+    /// Remove star from a gist
+    async fn unstar_gist(&self, username: &str, public_id: &str) -> DBResult<()> {
+        sqlx::query!(
+            "DELETE FROM gists_stars
+            WHERE
+                owner_id = (SELECT ID FROM gists_users WHERE username = $1)
+            AND
+                gist_id = (SELECT ID FROM gists_gists WHERE public_id = $2)",
+            username,
+            public_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        Ok(())
+    }
+
+    /// Check if user has starred a gist
+    async fn is_starred(&self, username: &str, public_id: &str) -> DBResult<bool> {
+        match sqlx::query!(
+            "SELECT ID FROM gists_stars
+            WHERE
+                owner_id = (SELECT ID FROM gists_users WHERE username = $1)
+            AND
+                gist_id = (SELECT ID FROM gists_gists WHERE public_id = $2)",
+            username,
+            public_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(Error::RowNotFound) => Ok(false),
+            Err(e) => Err(DBError::DBError(Box::new(e))),
+        }
+    }
+
+    /// Get number of users that have starred a gist
+    async fn get_star_count(&self, public_id: &str) -> DBResult<i64> {
+        let res = sqlx::query!(
+            "SELECT COUNT(*) as count FROM gists_stars
+            WHERE gist_id = (SELECT ID FROM gists_gists WHERE public_id = $1)",
+            public_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        Ok(res.count as i64)
+    }
+
+    /// Get gists starred by user, most recently starred first
+    async fn get_starred_gists(&self, username: &str) -> DBResult<Vec<Gist>> {
+        let mut res = sqlx::query_as!(
+            InnerGist,
+            "SELECT
+                gists_gists_view.owner,
+                gists_gists_view.visibility,
+                gists_gists_view.created,
+                gists_gists_view.updated,
+                gists_gists_view.public_id,
+                gists_gists_view.description
+            FROM
+                gists_stars
+            INNER JOIN gists_gists ON gists_gists.ID = gists_stars.gist_id
+            INNER JOIN gists_gists_view ON gists_gists_view.public_id = gists_gists.public_id
+            WHERE gists_stars.owner_id = (SELECT ID FROM gists_users WHERE username = $1)
+            ORDER BY gists_stars.created DESC
+            ",
+            username
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        let mut gists = Vec::with_capacity(res.len());
+        for r in res.drain(..) {
+            gists.push(r.into_gist()?);
+        }
+        Ok(gists)
+    }
+
+    async fn get_public_gists(
+        &self,
+        sort: GistSort,
+        after: Option<&GistCursor>,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>> {
+        const PUBLIC: &str = GistVisibility::Public.to_str();
+        let timestamp = after.map(|c| c.timestamp);
+        let public_id = after.map(|c| c.public_id);
+        let limit = limit as i64;
+        let mut res = match sort {
+            GistSort::Created => {
+                sqlx::query_as!(
+                    InnerGist,
+                    "SELECT
+                        owner,
+                        visibility,
+                        created,
+                        updated,
+                        public_id,
+                        description
+                    FROM
+                        gists_gists_view
+                    WHERE
+                        visibility = $1
+                    AND
+                        ($2 IS NULL OR (created, public_id) < ($3, $4))
+                    ORDER BY created DESC, public_id DESC
+                    LIMIT $5
+                    ",
+                    PUBLIC,
+                    timestamp,
+                    timestamp,
+                    public_id,
+                    limit
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+            GistSort::Updated => {
+                sqlx::query_as!(
+                    InnerGist,
+                    "SELECT
+                        owner,
+                        visibility,
+                        created,
+                        updated,
+                        public_id,
+                        description
+                    FROM
+                        gists_gists_view
+                    WHERE
+                        visibility = $1
+                    AND
+                        ($2 IS NULL OR (updated, public_id) < ($3, $4))
+                    ORDER BY updated DESC, public_id DESC
+                    LIMIT $5
+                    ",
+                    PUBLIC,
+                    timestamp,
+                    timestamp,
+                    public_id,
+                    limit
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+        }
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        let mut gists = Vec::with_capacity(res.len());
+        for r in res.drain(..) {
+            gists.push(r.into_gist()?);
+        }
+        Ok(gists)
+    }
+
+    async fn index_gist(&self, doc: &GistSearchDocument) -> DBResult<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        sqlx::query!(
+            "DELETE FROM gists_search WHERE public_id = $1",
+            doc.public_id
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        let res = sqlx::query!(
+            "INSERT INTO gists_search (public_id, description, filenames, content)
+            SELECT public_id, description, $2, $3 FROM gists_gists WHERE public_id = $1",
+            doc.public_id,
+            doc.filenames,
+            doc.content
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::GistNotFound);
+        }
+        tx.commit()
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
         Ok(())
     }
 
+    async fn search_gists(
+        &self,
+        query: &str,
+        viewer: Option<&str>,
+        offset: u32,
+        limit: u32,
+    ) -> DBResult<Vec<Gist>> {
+        const PUBLIC: &str = GistVisibility::Public.to_str();
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(Vec::default());
+        }
+        let limit = limit as i64;
+        let offset = offset as i64;
+        let mut res = sqlx::query_as!(
+            InnerGist,
+            "SELECT
+                gists_gists_view.owner,
+                gists_gists_view.visibility,
+                gists_gists_view.created,
+                gists_gists_view.updated,
+                gists_gists_view.public_id,
+                gists_gists_view.description
+            FROM
+                gists_search
+            INNER JOIN gists_gists_view ON gists_gists_view.public_id = gists_search.public_id
+            WHERE
+                gists_search MATCH $1
+            AND
+                (gists_gists_view.visibility = $2 OR gists_gists_view.owner = $3)
+            ORDER BY
+                bm25(gists_search, 0.0, 10.0, 10.0, 1.0),
+                gists_gists_view.updated DESC
+            LIMIT $4 OFFSET $5
+            ",
+            query,
+            PUBLIC,
+            viewer,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        let mut gists = Vec::with_capacity(res.len());
+        for r in res.drain(..) {
+            gists.push(r.into_gist()?);
+        }
+        Ok(gists)
+    }
+
     /// Create new comment
     async fn new_comment(&self, comment: &CreateGistComment) -> DBResult<i64> {
         let now = now_unix_time_stamp();
@@ -551,6 +1003,36 @@ impl GPDatabse for Database {
         res.drain(..).for_each(|r| comments.push(r.into()));
         Ok(comments)
     }
+    /// Get comments authored by user, oldest first
+    async fn get_user_comments(&self, owner: &str) -> DBResult<Vec<GistComment>> {
+        let mut res = sqlx::query_as!(
+            InnerGistComment,
+            "
+            SELECT
+                ID,
+                comment,
+                owner,
+                created,
+                gist_public_id
+            FROM
+                gists_comments_view
+            WHERE
+                owner = $1
+            ORDER BY created;
+            ",
+            owner,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| match e {
+            Error::RowNotFound => DBError::CommentNotFound,
+            e => DBError::DBError(Box::new(e)),
+        })?;
+
+        let mut comments: Vec<GistComment> = Vec::with_capacity(res.len());
+        res.drain(..).for_each(|r| comments.push(r.into()));
+        Ok(comments)
+    }
     /// Get a specific comment using its database assigned ID
     async fn get_comment_by_id(&self, id: i64) -> DBResult<GistComment> {
         let res = sqlx::query_as!(
@@ -597,6 +1079,371 @@ impl GPDatabse for Database {
         Ok(())
     }
 
+    async fn new_access_token(&self, token: &CreateAccessToken) -> DBResult<i64> {
+        let now = now_unix_time_stamp();
+        let scopes = AccessTokenScope::join(token.scopes);
+        sqlx::query!(
+            "INSERT INTO gists_access_tokens (owner_id, name, token_hash, scopes, created)
+            VALUES (
+                (SELECT ID FROM gists_users WHERE username = $1),
+                $2,
+                $3,
+                $4,
+                $5
+            )",
+            token.owner,
+            token.name,
+            token.token_hash,
+            scopes,
+            now,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(map_register_err)?;
+
+        let token = self.get_access_token_by_hash(token.token_hash).await?;
+        Ok(token.id)
+    }
+
+    async fn get_access_tokens(&self, owner: &str) -> DBResult<Vec<AccessToken>> {
+        let mut res = sqlx::query_as!(
+            InnerAccessToken,
+            "SELECT
+                gists_access_tokens.ID as id,
+                gists_users.username as owner,
+                gists_access_tokens.name,
+                gists_access_tokens.scopes,
+                gists_access_tokens.created,
+                gists_access_tokens.last_used
+            FROM
+                gists_access_tokens
+            INNER JOIN gists_users ON gists_users.ID = gists_access_tokens.owner_id
+            WHERE gists_users.username = $1
+            ORDER BY gists_access_tokens.created DESC, gists_access_tokens.ID DESC
+            ",
+            owner
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        let mut tokens = Vec::with_capacity(res.len());
+        for r in res.drain(..) {
+            tokens.push(r.into_access_token()?);
+        }
+        Ok(tokens)
+    }
+
+    async fn get_access_token_by_hash(&self, token_hash: &str) -> DBResult<AccessToken> {
+        let res = sqlx::query_as!(
+            InnerAccessToken,
+            "SELECT
+                gists_access_tokens.ID as id,
+                gists_users.username as owner,
+                gists_access_tokens.name,
+                gists_access_tokens.scopes,
+                gists_access_tokens.created,
+                gists_access_tokens.last_used
+            FROM
+                gists_access_tokens
+            INNER JOIN gists_users ON gists_users.ID = gists_access_tokens.owner_id
+            WHERE gists_access_tokens.token_hash = $1
+            ",
+            token_hash
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            Error::RowNotFound => DBError::AccessTokenNotFound,
+            e => DBError::DBError(Box::new(e)),
+        })?;
+        res.into_access_token()
+    }
+
+    async fn touch_access_token(&self, id: i64) -> DBResult<()> {
+        let now = now_unix_time_stamp();
+        sqlx::query!(
+            "UPDATE gists_access_tokens SET last_used = $1 WHERE ID = $2",
+            now,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        Ok(())
+    }
+
+    async fn delete_access_token(&self, owner: &str, id: i64) -> DBResult<()> {
+        let res = sqlx::query!(
+            "DELETE FROM gists_access_tokens
+            WHERE
+                ID = $1
+            AND
+                owner_id = (SELECT ID FROM gists_users WHERE username = $2)",
+            id,
+            owner
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::AccessTokenNotFound);
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> DBResult<Snapshot> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+
+        let users = sqlx::query!(
+            "SELECT ID as id, username, email, email_verified, secret, password
+            FROM gists_users ORDER BY ID"
+        )
+        .fetch_all(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?
+        .drain(..)
+        .map(|r| UserRecord {
+            id: r.id,
+            username: r.username,
+            email: r.email,
+            email_verified: r.email_verified,
+            secret: r.secret,
+            password: r.password,
+        })
+        .collect();
+
+        let gists = sqlx::query!(
+            "SELECT
+                gists_gists.ID as id,
+                gists_gists.owner_id,
+                gists_gists.description,
+                gists_visibility.name as visibility,
+                gists_gists.created,
+                gists_gists.updated,
+                gists_gists.public_id
+            FROM
+                gists_gists
+            INNER JOIN gists_visibility ON gists_visibility.ID = gists_gists.visibility
+            ORDER BY gists_gists.ID"
+        )
+        .fetch_all(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?
+        .drain(..)
+        .map(|r| {
+            Ok(GistRecord {
+                id: r.id,
+                owner_id: r.owner_id,
+                description: r.description,
+                visibility: GistVisibility::from_str(&r.visibility)?,
+                created: r.created,
+                updated: r.updated,
+                public_id: r.public_id,
+            })
+        })
+        .collect::<DBResult<Vec<GistRecord>>>()?;
+
+        let comments = sqlx::query!(
+            "SELECT ID as id, owner_id, gist_id, comment, created
+            FROM gists_comments ORDER BY ID"
+        )
+        .fetch_all(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?
+        .drain(..)
+        .map(|r| CommentRecord {
+            id: r.id,
+            owner_id: r.owner_id,
+            gist_id: r.gist_id,
+            comment: r.comment,
+            created: r.created,
+        })
+        .collect();
+
+        let forks =
+            sqlx::query!("SELECT ID as id, gist_id, parent_id FROM gists_forks ORDER BY ID")
+                .fetch_all(&mut tx)
+                .await
+                .map_err(|e| DBError::DBError(Box::new(e)))?
+                .drain(..)
+                .map(|r| ForkRecord {
+                    id: r.id,
+                    gist_id: r.gist_id,
+                    parent_id: r.parent_id,
+                })
+                .collect();
+
+        let stars = sqlx::query!(
+            "SELECT ID as id, owner_id, gist_id, created FROM gists_stars ORDER BY ID"
+        )
+        .fetch_all(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?
+        .drain(..)
+        .map(|r| StarRecord {
+            id: r.id,
+            owner_id: r.owner_id,
+            gist_id: r.gist_id,
+            created: r.created,
+        })
+        .collect();
+
+        let access_tokens = sqlx::query!(
+            "SELECT ID as id, owner_id, name, token_hash, scopes, created, last_used
+            FROM gists_access_tokens ORDER BY ID"
+        )
+        .fetch_all(&mut tx)
+        .await
+        .map_err(|e| DBError::DBError(Box::new(e)))?
+        .drain(..)
+        .map(|r| {
+            Ok(AccessTokenRecord {
+                id: r.id,
+                owner_id: r.owner_id,
+                name: r.name,
+                token_hash: r.token_hash,
+                scopes: AccessTokenScope::split(&r.scopes)?,
+                created: r.created,
+                last_used: r.last_used,
+            })
+        })
+        .collect::<DBResult<Vec<AccessTokenRecord>>>()?;
+
+        tx.commit()
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        Ok(Snapshot {
+            users,
+            gists,
+            comments,
+            forks,
+            stars,
+            access_tokens,
+        })
+    }
+
+    async fn restore(&self, snapshot: &Snapshot) -> DBResult<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        let existing = sqlx::query!("SELECT ID as id FROM gists_users LIMIT 1")
+            .fetch_optional(&mut tx)
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        if existing.is_some() {
+            return Err(DBError::DatabaseNotEmpty);
+        }
+
+        for u in snapshot.users.iter() {
+            sqlx::query!(
+                "INSERT INTO gists_users (ID, username, email, email_verified, secret, password)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+                u.id,
+                u.username,
+                u.email,
+                u.email_verified,
+                u.secret,
+                u.password
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(map_register_err)?;
+        }
+
+        for g in snapshot.gists.iter() {
+            let visibility = g.visibility.to_str();
+            sqlx::query!(
+                "INSERT INTO gists_gists
+                (ID, owner_id, description, visibility, created, updated, public_id)
+                VALUES (
+                    $1, $2, $3, (SELECT ID FROM gists_visibility WHERE name = $4), $5, $6, $7
+                )",
+                g.id,
+                g.owner_id,
+                g.description,
+                visibility,
+                g.created,
+                g.updated,
+                g.public_id
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(map_register_err)?;
+        }
+
+        for c in snapshot.comments.iter() {
+            sqlx::query!(
+                "INSERT INTO gists_comments (ID, owner_id, gist_id, comment, created)
+                VALUES ($1, $2, $3, $4, $5)",
+                c.id,
+                c.owner_id,
+                c.gist_id,
+                c.comment,
+                c.created
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        }
+
+        for f in snapshot.forks.iter() {
+            sqlx::query!(
+                "INSERT INTO gists_forks (ID, gist_id, parent_id) VALUES ($1, $2, $3)",
+                f.id,
+                f.gist_id,
+                f.parent_id
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        }
+
+        for s in snapshot.stars.iter() {
+            sqlx::query!(
+                "INSERT INTO gists_stars (ID, owner_id, gist_id, created)
+                VALUES ($1, $2, $3, $4)",
+                s.id,
+                s.owner_id,
+                s.gist_id,
+                s.created
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        }
+
+        for t in snapshot.access_tokens.iter() {
+            let scopes = AccessTokenScope::join(&t.scopes);
+            sqlx::query!(
+                "INSERT INTO gists_access_tokens
+                (ID, owner_id, name, token_hash, scopes, created, last_used)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                t.id,
+                t.owner_id,
+                t.name,
+                t.token_hash,
+                scopes,
+                t.created,
+                t.last_used
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(map_register_err)?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| DBError::DBError(Box::new(e)))?;
+        Ok(())
+    }
+
     async fn visibility_exists(&self, visibility: &GistVisibility) -> DBResult<bool> {
         let visibility = visibility.to_str();
         match sqlx::query!(
@@ -617,6 +1464,15 @@ fn now_unix_time_stamp() -> i64 {
     OffsetDateTime::now_utc().unix_timestamp()
 }
 
+/// Quote terms in `query`, so that FTS5 query syntax in user input is matched literally
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 struct InnerGist {
     owner: String,
     description: Option<String>,
@@ -659,3 +1515,25 @@ impl From<InnerGistComment> for GistComment {
         }
     }
 }
+
+struct InnerAccessToken {
+    id: i64,
+    owner: String,
+    name: String,
+    scopes: String,
+    created: i64,
+    last_used: Option<i64>,
+}
+
+impl InnerAccessToken {
+    fn into_access_token(self) -> DBResult<AccessToken> {
+        Ok(AccessToken {
+            id: self.id,
+            owner: self.owner,
+            name: self.name,
+            scopes: AccessTokenScope::split(&self.scopes)?,
+            created: self.created,
+            last_used: self.last_used,
+        })
+    }
+}
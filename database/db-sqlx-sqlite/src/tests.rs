@@ -81,3 +81,73 @@ async fn gist_test() {
     db.migrate().await.unwrap();
     gists_work(&db, NAME, PASSWORD, SECRET, PUBLIC_ID).await;
 }
+
+#[actix_rt::test]
+async fn access_token_test() {
+    const NAME: &str = "sqliteaccesstokentest";
+    const NAME2: &str = "sqliteaccesstokentest2";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "sqliteaccesstokentestsecret";
+    const SECRET2: &str = "sqliteaccesstokentestsecret2";
+
+    let url = env::var("SQLITE_DATABASE_URL").expect("Set SQLITE_DATABASE_URL env var");
+    let pool_options = SqlitePoolOptions::new().max_connections(2);
+    let connection_options = ConnectionOptions::Fresh(Fresh { pool_options, url });
+    let db = connection_options.connect().await.unwrap();
+
+    db.migrate().await.unwrap();
+    access_tokens_work(&db, NAME, NAME2, PASSWORD, SECRET, SECRET2).await;
+}
+
+#[actix_rt::test]
+async fn snapshot_test() {
+    const NAME: &str = "sqlitesnapshottest";
+    const NAME2: &str = "sqlitesnapshottest2";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "sqlitesnapshottestsecret";
+    const SECRET2: &str = "sqlitesnapshottestsecret2";
+    const PUBLIC_ID: &str = "sqlitesnapshottest";
+    const FORK_PUBLIC_ID: &str = "sqlitesnapshottestfork";
+
+    let url = env::var("SQLITE_DATABASE_URL").expect("Set SQLITE_DATABASE_URL env var");
+    let pool_options = SqlitePoolOptions::new().max_connections(2);
+    let connection_options = ConnectionOptions::Fresh(Fresh { pool_options, url });
+    let db = connection_options.connect().await.unwrap();
+
+    db.migrate().await.unwrap();
+    let snapshot = snapshot_works(&db, NAME, PASSWORD, SECRET, PUBLIC_ID, FORK_PUBLIC_ID).await;
+
+    let pool_options = SqlitePoolOptions::new().max_connections(1);
+    let connection_options = ConnectionOptions::Fresh(Fresh {
+        pool_options,
+        url: "sqlite::memory:".into(),
+    });
+    let empty = connection_options.connect().await.unwrap();
+    empty.migrate().await.unwrap();
+    restore_works(&empty, &snapshot, NAME2, PASSWORD, SECRET2).await;
+}
+
+#[actix_rt::test]
+async fn copy_database_test() {
+    const NAME: &str = "sqlitecopytest";
+    const PASSWORD: &str = "pasdfasdfasdfadf";
+    const SECRET: &str = "sqlitecopytestsecret";
+    const PUBLIC_ID: &str = "sqlitecopytest";
+    const FORK_PUBLIC_ID: &str = "sqlitecopytestfork";
+
+    let url = env::var("SQLITE_DATABASE_URL").expect("Set SQLITE_DATABASE_URL env var");
+    let pool_options = SqlitePoolOptions::new().max_connections(2);
+    let connection_options = ConnectionOptions::Fresh(Fresh { pool_options, url });
+    let db = connection_options.connect().await.unwrap();
+    db.migrate().await.unwrap();
+    snapshot_works(&db, NAME, PASSWORD, SECRET, PUBLIC_ID, FORK_PUBLIC_ID).await;
+
+    let pool_options = SqlitePoolOptions::new().max_connections(1);
+    let connection_options = ConnectionOptions::Fresh(Fresh {
+        pool_options,
+        url: "sqlite::memory:".into(),
+    });
+    let empty = connection_options.connect().await.unwrap();
+    empty.migrate().await.unwrap();
+    copy_database_works(&db, &empty).await;
+}
@@ -0,0 +1,606 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! GitHub Gists REST API compatibility layer
+//!
+//! Tools that speak the [GitHub Gists REST API](https://docs.github.com/en/rest/gists) can
+//! be pointed at GitPad: requests are mapped onto GitPad gists and responses are shaped
+//! like GitHub's. Secret gists on GitHub correspond to [GistVisibility::Unlisted] gists,
+//! and requests are authenticated with personal access tokens, see [crate::api::v1::bearer].
+use std::collections::BTreeMap;
+
+use actix_identity::Identity;
+use actix_web::*;
+use db_core::prelude::*;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::api::v1::routes::{GetFilePath, PostCommentPath};
+use crate::data::api::v1::gists::{
+    ContentType, CreateGist, FileInfo, FileMode, FileType, GistID, GistInfo,
+};
+use crate::errors::*;
+use crate::pages::PAGES;
+use crate::utils::unescape_spaces;
+use crate::*;
+
+pub mod routes;
+#[cfg(test)]
+mod tests;
+
+pub use routes::ROUTES;
+use routes::{CommentPath, GistPath, UserGistsPath};
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(new_gist);
+    cfg.service(get_gist);
+    cfg.service(update_gist);
+    cfg.service(delete_gist);
+    cfg.service(get_user_gists);
+    cfg.service(get_comments);
+    cfg.service(new_comment);
+    cfg.service(get_comment);
+    cfg.service(delete_comment);
+}
+
+/// Format UNIX timestamp as an ISO 8601 UTC date, like GitHub does
+fn format_timestamp(timestamp: i64) -> String {
+    let t = OffsetDateTime::from_unix_timestamp(timestamp).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        t.year(),
+        u8::from(t.month()),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+/// Base URL of the instance, as seen by the client
+fn base_url(req: &HttpRequest) -> String {
+    let conn = req.connection_info();
+    format!("{}://{}", conn.scheme(), conn.host())
+}
+
+/// Get username of the authenticated user. GitHub clients expect `401` rather than a redirect
+/// to the login page when they aren't authenticated.
+fn get_username(id: &Identity) -> ServiceResult<String> {
+    id.identity()
+        .ok_or_else(|| ServiceError::UnauthorizedOperation("Requires authentication".into()))
+}
+
+/// Get gist that `viewer` can read
+async fn get_readable_gist(
+    db: &crate::DB,
+    public_id: &str,
+    viewer: Option<&str>,
+) -> ServiceResult<Gist> {
+    let gist = db.get_gist(public_id).await?;
+    if gist.visibility == GistVisibility::Private && viewer != Some(gist.owner.as_str()) {
+        return Err(ServiceError::GistNotFound);
+    }
+    Ok(gist)
+}
+
+/// Get gist that `username` can modify. Only owners can modify their gists.
+async fn get_writable_gist(db: &crate::DB, public_id: &str, username: &str) -> ServiceResult<Gist> {
+    let gist = db.get_gist(public_id).await?;
+    if gist.owner != username {
+        return match gist.visibility {
+            GistVisibility::Public | GistVisibility::Unlisted => {
+                Err(ServiceError::UnauthorizedOperation(
+                    "This user is not the owner of the gist".into(),
+                ))
+            }
+            GistVisibility::Private => Err(ServiceError::GistNotFound),
+        };
+    }
+    Ok(gist)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubUser {
+    pub login: String,
+    pub url: String,
+    pub html_url: String,
+    #[serde(rename = "type")]
+    pub user_type: String,
+}
+
+impl GitHubUser {
+    fn new(base: &str, username: &str) -> Self {
+        Self {
+            login: username.into(),
+            url: format!("{base}{}", ROUTES.get_user_gists_route(username)),
+            html_url: format!(
+                "{base}{}",
+                PAGES
+                    .gist
+                    .get_profile_route(crate::pages::routes::GistProfilePathComponent { username })
+            ),
+            user_type: "User".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubFile {
+    pub filename: String,
+    #[serde(rename = "type")]
+    pub file_type: String,
+    pub language: Option<String>,
+    pub raw_url: String,
+    pub size: usize,
+    pub truncated: bool,
+    /// unset for binary files, and when listing gists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubGist {
+    pub url: String,
+    pub id: String,
+    pub html_url: String,
+    pub git_pull_url: String,
+    pub git_push_url: String,
+    pub comments_url: String,
+    pub files: BTreeMap<String, GitHubFile>,
+    pub public: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    pub description: Option<String>,
+    pub comments: usize,
+    pub owner: GitHubUser,
+    pub truncated: bool,
+}
+
+impl GitHubGist {
+    /// Convert gist to its GitHub representation. File contents are only included when
+    /// `with_content` is set.
+    fn new(base: &str, gist: GistInfo, comments: usize, with_content: bool) -> Self {
+        let path = PostCommentPath {
+            username: gist.owner.clone(),
+            gist: gist.id.clone(),
+        };
+        let mut files = BTreeMap::new();
+        for file in gist.files.into_iter().flat_map(|f| f.into_files()) {
+            let filename = unescape_spaces(&file.filename);
+            let raw_path = GetFilePath {
+                username: gist.owner.clone(),
+                gist: gist.id.clone(),
+                file: filename.clone(),
+            };
+            let content = match file.content {
+                FileType::File(content) => content,
+                FileType::Dir(_) => continue,
+            };
+            let file_type = match &content {
+                ContentType::Text(_) => mime_guess::from_path(&filename)
+                    .first_or_text_plain()
+                    .essence_str()
+                    .to_owned(),
+                ContentType::Binary(_) => mime_guess::from_path(&filename)
+                    .first_or_octet_stream()
+                    .essence_str()
+                    .to_owned(),
+            };
+            let size = content.as_bytes().len();
+            let content = match content {
+                ContentType::Text(text) if with_content => Some(text),
+                _ => None,
+            };
+            files.insert(
+                filename.clone(),
+                GitHubFile {
+                    raw_url: format!("{base}{}", V1_API_ROUTES.gist.get_raw_route(&raw_path)),
+                    filename,
+                    file_type,
+                    language: None,
+                    size,
+                    truncated: false,
+                    content,
+                },
+            );
+        }
+
+        let html_url = format!("{base}{}", PAGES.gist.get_gist_route(&path));
+        Self {
+            url: format!("{base}{}", ROUTES.get_gist_route(&gist.id)),
+            git_pull_url: format!("{html_url}.git"),
+            git_push_url: format!("{html_url}.git"),
+            html_url,
+            comments_url: format!("{base}{}", ROUTES.get_comments_route(&gist.id)),
+            files,
+            public: gist.visibility == GistVisibility::Public,
+            created_at: format_timestamp(gist.created),
+            updated_at: format_timestamp(gist.updated),
+            description: gist.description,
+            comments,
+            owner: GitHubUser::new(base, &gist.owner),
+            truncated: false,
+            id: gist.id,
+        }
+    }
+}
+
+/// Read gist and convert it to its GitHub representation
+async fn github_gist(
+    req: &HttpRequest,
+    data: &AppData,
+    db: &crate::DB,
+    public_id: &str,
+    with_content: bool,
+) -> ServiceResult<GitHubGist> {
+    let gist = data
+        .gist_preview(db.as_ref(), &mut GistID::ID(public_id), None)
+        .await?;
+    let comments = db.get_comments_on_gist(public_id).await?.len();
+    Ok(GitHubGist::new(
+        &base_url(req),
+        gist,
+        comments,
+        with_content,
+    ))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubComment {
+    pub id: i64,
+    pub url: String,
+    pub body: String,
+    pub user: GitHubUser,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl GitHubComment {
+    fn new(base: &str, comment: GistComment) -> Self {
+        let path = CommentPath {
+            gist: comment.gist_public_id,
+            comment_id: comment.id,
+        };
+        let created_at = format_timestamp(comment.created);
+        Self {
+            id: comment.id,
+            url: format!("{base}{}", ROUTES.get_comment_route(&path)),
+            body: comment.comment,
+            user: GitHubUser::new(base, &comment.owner),
+            updated_at: created_at.clone(),
+            created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateFile {
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateGistRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// secret gists are created when unset
+    #[serde(default)]
+    pub public: bool,
+    pub files: BTreeMap<String, CreateFile>,
+}
+
+#[my_codegen::post(path = "crate::GITHUB_ROUTES.gists")]
+async fn new_gist(
+    req: HttpRequest,
+    payload: web::Json<CreateGistRequest>,
+    data: AppData,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = get_username(&id)?;
+    if payload.files.is_empty() {
+        return Err(ServiceError::GistEmpty);
+    }
+
+    let visibility = if payload.public {
+        GistVisibility::Public
+    } else {
+        GistVisibility::Unlisted
+    };
+    let msg = CreateGist {
+        owner: &username,
+        description: payload.description.as_deref(),
+        visibility: &visibility,
+    };
+    let files: Vec<FileInfo> = payload
+        .files
+        .iter()
+        .map(|(filename, file)| FileInfo {
+            filename: filename.clone(),
+            content: FileType::File(ContentType::Text(file.content.clone())),
+            mode: FileMode::Regular,
+        })
+        .collect();
+
+    let mut gist = data.new_gist(db.as_ref(), &msg).await?;
+    if let Err(e) = data
+        .write_file(
+            db.as_ref(),
+            &mut GistID::Repository(&mut gist.repository),
+            &files,
+        )
+        .await
+    {
+        data.delete_gist(db.as_ref(), &username, &gist.id).await?;
+        return Err(e);
+    }
+    let resp = github_gist(&req, &data, &db, &gist.id, true).await?;
+    Ok(HttpResponse::Created().json(resp))
+}
+
+#[my_codegen::get(path = "crate::GITHUB_ROUTES.gist")]
+async fn get_gist(
+    req: HttpRequest,
+    path: web::Path<GistPath>,
+    data: AppData,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let gist = get_readable_gist(&db, &path.gist, id.identity().as_deref()).await?;
+    let resp = github_gist(&req, &data, &db, &gist.public_id, true).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UpdateFile {
+    /// new contents of the file, unset leaves contents unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// new name of the file, unset leaves the name unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UpdateGistRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// files to change. Files set to `null` are deleted, files that aren't in the map are
+    /// left unchanged.
+    #[serde(default)]
+    pub files: BTreeMap<String, Option<UpdateFile>>,
+}
+
+/// Apply changes in `update` to `files`, which are keyed by filename. Renaming a file to the
+/// name of an existing file fails, unless that file is renamed or deleted too.
+fn apply_file_updates(
+    files: &mut BTreeMap<String, FileInfo>,
+    update: &BTreeMap<String, Option<UpdateFile>>,
+) -> ServiceResult<()> {
+    // all files that are changed are taken out first, so that the result doesn't depend on
+    // the order in which renames are applied
+    let mut changes = Vec::with_capacity(update.len());
+    for (filename, change) in update.iter() {
+        let existing = files.remove(filename);
+        match change {
+            Some(change) => changes.push((filename, change, existing)),
+            None if existing.is_some() => continue,
+            None => return Err(ServiceError::FileNotFound),
+        }
+    }
+
+    for (filename, change, existing) in changes.into_iter() {
+        let (content, mode) = match (&change.content, existing) {
+            (Some(content), existing) => (
+                FileType::File(ContentType::Text(content.clone())),
+                existing.map(|f| f.mode).unwrap_or_default(),
+            ),
+            (None, Some(existing)) => (existing.content, existing.mode),
+            (None, None) => return Err(ServiceError::FileNotFound),
+        };
+        let filename = change.filename.as_ref().unwrap_or(filename);
+        if files.contains_key(filename) {
+            return Err(ServiceError::BadRequest(format!(
+                "file {filename} already exists"
+            )));
+        }
+        files.insert(
+            filename.clone(),
+            FileInfo {
+                filename: filename.clone(),
+                content,
+                mode,
+            },
+        );
+    }
+    Ok(())
+}
+
+#[my_codegen::patch(path = "crate::GITHUB_ROUTES.gist")]
+async fn update_gist(
+    req: HttpRequest,
+    path: web::Path<GistPath>,
+    payload: web::Json<UpdateGistRequest>,
+    data: AppData,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = get_username(&id)?;
+    let gist = get_writable_gist(&db, &path.gist, &username).await?;
+
+    if !payload.files.is_empty() {
+        let current = data
+            .gist_preview(db.as_ref(), &mut GistID::ID(&gist.public_id), None)
+            .await?;
+        let mut files: BTreeMap<String, FileInfo> = current
+            .files
+            .into_iter()
+            .flat_map(|f| f.into_files())
+            .map(|mut f| {
+                f.filename = unescape_spaces(&f.filename);
+                (f.filename.clone(), f)
+            })
+            .collect();
+        apply_file_updates(&mut files, &payload.files)?;
+        let files: Vec<FileInfo> = files.into_values().collect();
+        data.replace_files(db.as_ref(), &mut GistID::ID(&gist.public_id), &files)
+            .await?;
+    }
+
+    if let Some(description) = payload.description.as_deref().map(|d| d.trim()) {
+        let msg = UpdateGist {
+            owner: &username,
+            public_id: &gist.public_id,
+            description: if description.is_empty() {
+                None
+            } else {
+                Some(description)
+            },
+            visibility: &gist.visibility,
+        };
+        db.update_gist(&msg).await?;
+    }
+
+    let resp = github_gist(&req, &data, &db, &gist.public_id, true).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::delete(path = "crate::GITHUB_ROUTES.gist")]
+async fn delete_gist(
+    path: web::Path<GistPath>,
+    data: AppData,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = get_username(&id)?;
+    let gist = get_writable_gist(&db, &path.gist, &username).await?;
+    data.delete_gist(db.as_ref(), &username, &gist.public_id)
+        .await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[my_codegen::get(path = "crate::GITHUB_ROUTES.user_gists")]
+async fn get_user_gists(
+    req: HttpRequest,
+    path: web::Path<UserGistsPath>,
+    data: AppData,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    if !db.username_exists(&path.username).await? {
+        return Err(ServiceError::AccountNotFound);
+    }
+    let gists = data
+        .get_user_gists(db.as_ref(), &path.username, id.identity().as_deref())
+        .await?;
+    let mut resp = Vec::with_capacity(gists.len());
+    for gist in gists.iter() {
+        match github_gist(&req, &data, &db, &gist.public_id, false).await {
+            Ok(gist) => resp.push(gist),
+            // gist doesn't have any files yet
+            Err(ServiceError::RevisionNotFound) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::get(path = "crate::GITHUB_ROUTES.comments")]
+async fn get_comments(
+    req: HttpRequest,
+    path: web::Path<GistPath>,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let gist = get_readable_gist(&db, &path.gist, id.identity().as_deref()).await?;
+    let base = base_url(&req);
+    let comments: Vec<GitHubComment> = db
+        .get_comments_on_gist(&gist.public_id)
+        .await?
+        .into_iter()
+        .map(|c| GitHubComment::new(&base, c))
+        .collect();
+    Ok(HttpResponse::Ok().json(comments))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCommentRequest {
+    pub body: String,
+}
+
+#[my_codegen::post(path = "crate::GITHUB_ROUTES.comments")]
+async fn new_comment(
+    req: HttpRequest,
+    path: web::Path<GistPath>,
+    payload: web::Json<CreateCommentRequest>,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = get_username(&id)?;
+    if payload.body.trim().is_empty() {
+        return Err(ServiceError::EmptyComment);
+    }
+    let gist = get_readable_gist(&db, &path.gist, Some(&username)).await?;
+    let msg = CreateGistComment {
+        owner: &username,
+        gist_public_id: &gist.public_id,
+        comment: &payload.body,
+    };
+    let comment_id = db.new_comment(&msg).await?;
+    let comment = db.get_comment_by_id(comment_id).await?;
+    Ok(HttpResponse::Created().json(GitHubComment::new(&base_url(&req), comment)))
+}
+
+/// Get comment on a gist that `viewer` can read
+async fn get_readable_comment(
+    db: &crate::DB,
+    path: &CommentPath,
+    viewer: Option<&str>,
+) -> ServiceResult<(Gist, GistComment)> {
+    let gist = get_readable_gist(db, &path.gist, viewer).await?;
+    let comment = db.get_comment_by_id(path.comment_id).await?;
+    if comment.gist_public_id != gist.public_id {
+        return Err(ServiceError::CommentNotFound);
+    }
+    Ok((gist, comment))
+}
+
+#[my_codegen::get(path = "crate::GITHUB_ROUTES.comment")]
+async fn get_comment(
+    req: HttpRequest,
+    path: web::Path<CommentPath>,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let (_, comment) = get_readable_comment(&db, &path, id.identity().as_deref()).await?;
+    Ok(HttpResponse::Ok().json(GitHubComment::new(&base_url(&req), comment)))
+}
+
+#[my_codegen::delete(path = "crate::GITHUB_ROUTES.comment")]
+async fn delete_comment(
+    path: web::Path<CommentPath>,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = get_username(&id)?;
+    let (_, comment) = get_readable_comment(&db, &path, Some(&username)).await?;
+    if comment.owner != username {
+        return Err(ServiceError::UnauthorizedOperation(
+            "This user is not the owner of the comment to delete it".into(),
+        ));
+    }
+    db.delete_comment(&username, comment.id).await?;
+    Ok(HttpResponse::NoContent())
+}
@@ -0,0 +1,117 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! GitHub Gists REST API compatible routes
+use serde::*;
+
+/// constant [GitHub](GitHub) instance
+pub const ROUTES: GitHub = GitHub::new();
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GistPath {
+    pub gist: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommentPath {
+    pub gist: String,
+    pub comment_id: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserGistsPath {
+    pub username: String,
+}
+
+/// GitHub Gists REST API compatible routes. Routes are mounted under `/api/v3`, where
+/// GitHub Enterprise serves its API, so that clients can be pointed at GitPad by changing
+/// the host.
+pub struct GitHub {
+    /// create gist
+    pub gists: &'static str,
+    /// get, update and delete gist
+    pub gist: &'static str,
+    /// gists owned by a user
+    pub user_gists: &'static str,
+    /// list and create comments on a gist
+    pub comments: &'static str,
+    /// get and delete comment
+    pub comment: &'static str,
+}
+
+impl GitHub {
+    /// create new instance of GitHub routes
+    pub const fn new() -> GitHub {
+        let gists = "/api/v3/gists";
+        let gist = "/api/v3/gists/{gist}";
+        let user_gists = "/api/v3/users/{username}/gists";
+        let comments = "/api/v3/gists/{gist}/comments";
+        let comment = "/api/v3/gists/{gist}/comments/{comment_id}";
+        GitHub {
+            gists,
+            gist,
+            user_gists,
+            comments,
+            comment,
+        }
+    }
+
+    /// get gist route with placeholders replaced with values provided.
+    pub fn get_gist_route(&self, gist: &str) -> String {
+        self.gist.replace("{gist}", gist)
+    }
+
+    /// get user_gists route with placeholders replaced with values provided.
+    pub fn get_user_gists_route(&self, username: &str) -> String {
+        self.user_gists.replace("{username}", username)
+    }
+
+    /// get comments route with placeholders replaced with values provided.
+    pub fn get_comments_route(&self, gist: &str) -> String {
+        self.comments.replace("{gist}", gist)
+    }
+
+    /// get comment route with placeholders replaced with values provided.
+    pub fn get_comment_route(&self, components: &CommentPath) -> String {
+        self.comment
+            .replace("{gist}", &components.gist)
+            .replace("{comment_id}", &components.comment_id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn github_route_substitution_works() {
+        const NAME: &str = "bob";
+        const GIST: &str = "foo";
+        const COMMENT_ID: i64 = 5;
+        let gist = format!("/api/v3/gists/{GIST}");
+        let user_gists = format!("/api/v3/users/{NAME}/gists");
+        let comments = format!("/api/v3/gists/{GIST}/comments");
+        let comment = format!("/api/v3/gists/{GIST}/comments/{COMMENT_ID}");
+
+        assert_eq!(gist, ROUTES.get_gist_route(GIST));
+        assert_eq!(user_gists, ROUTES.get_user_gists_route(NAME));
+        assert_eq!(comments, ROUTES.get_comments_route(GIST));
+        let path = CommentPath {
+            gist: GIST.into(),
+            comment_id: COMMENT_ID,
+        };
+        assert_eq!(comment, ROUTES.get_comment_route(&path));
+    }
+}
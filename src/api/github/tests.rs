@@ -0,0 +1,401 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::BTreeMap;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+
+use db_core::prelude::*;
+
+use super::*;
+use crate::data::Data;
+use crate::tests::*;
+
+#[actix_rt::test]
+async fn postgres_github_api_works() {
+    let (db, data) = sqlx_postgres::get_data().await;
+    github_api_works(data, db).await;
+}
+
+#[actix_rt::test]
+async fn sqlite_github_api_works() {
+    let (db, data) = sqlx_sqlite::get_data().await;
+    github_api_works(data, db).await;
+}
+
+#[test]
+fn format_timestamp_works() {
+    assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_timestamp(1646904922), "2022-03-10T09:35:22Z");
+}
+
+#[test]
+fn apply_file_updates_works() {
+    let file = |filename: &str, content: &str, mode: FileMode| FileInfo {
+        filename: filename.into(),
+        content: FileType::File(ContentType::Text(content.into())),
+        mode,
+    };
+    let mut files = BTreeMap::new();
+    files.insert("a.sh".into(), file("a.sh", "a", FileMode::Executable));
+    files.insert("b.md".into(), file("b.md", "b", FileMode::Regular));
+    files.insert("c.md".into(), file("c.md", "c", FileMode::Regular));
+
+    let mut update = BTreeMap::new();
+    update.insert(
+        "a.sh".into(),
+        Some(UpdateFile {
+            content: Some("new a".into()),
+            filename: None,
+        }),
+    );
+    update.insert(
+        "b.md".into(),
+        Some(UpdateFile {
+            content: None,
+            filename: Some("renamed.md".into()),
+        }),
+    );
+    update.insert("c.md".into(), None);
+    update.insert(
+        "d.md".into(),
+        Some(UpdateFile {
+            content: Some("d".into()),
+            filename: None,
+        }),
+    );
+    apply_file_updates(&mut files, &update).unwrap();
+    let files: Vec<FileInfo> = files.into_values().collect();
+    assert_eq!(
+        files,
+        [
+            file("a.sh", "new a", FileMode::Executable),
+            file("d.md", "d", FileMode::Regular),
+            file("renamed.md", "b", FileMode::Regular),
+        ]
+    );
+
+    // files that don't exist can't be deleted or renamed
+    let mut files = BTreeMap::new();
+    let mut update = BTreeMap::new();
+    update.insert("foo".into(), None);
+    assert_eq!(
+        apply_file_updates(&mut files, &update).err(),
+        Some(ServiceError::FileNotFound)
+    );
+    update.insert("foo".into(), Some(UpdateFile::default()));
+    assert_eq!(
+        apply_file_updates(&mut files, &update).err(),
+        Some(ServiceError::FileNotFound)
+    );
+
+    // files can't be renamed to the name of an existing file
+    let rename = |filename: &str| {
+        Some(UpdateFile {
+            content: None,
+            filename: Some(filename.into()),
+        })
+    };
+    let existing = || {
+        let mut files = BTreeMap::new();
+        files.insert("a.md".to_owned(), file("a.md", "a", FileMode::Regular));
+        files.insert("b.md".to_owned(), file("b.md", "b", FileMode::Regular));
+        files
+    };
+    let mut update = BTreeMap::new();
+    update.insert("a.md".into(), rename("b.md"));
+    assert!(matches!(
+        apply_file_updates(&mut existing(), &update).err(),
+        Some(ServiceError::BadRequest(_))
+    ));
+    // unless the existing file is renamed or deleted too
+    update.insert("b.md".into(), rename("a.md"));
+    let mut files = existing();
+    apply_file_updates(&mut files, &update).unwrap();
+    let files: Vec<FileInfo> = files.into_values().collect();
+    assert_eq!(
+        files,
+        [
+            file("a.md", "b", FileMode::Regular),
+            file("b.md", "a", FileMode::Regular),
+        ]
+    );
+    update.insert("b.md".into(), None);
+    let mut files = existing();
+    apply_file_updates(&mut files, &update).unwrap();
+    let files: Vec<FileInfo> = files.into_values().collect();
+    assert_eq!(files, [file("b.md", "a", FileMode::Regular)]);
+}
+
+async fn github_api_works(data: Arc<Data>, db: BoxDB) {
+    const NAME: &str = "githubapiuser";
+    const NAME2: &str = "githubapiuser2";
+    const EMAIL: &str = "githubapiuser@example.com";
+    const EMAIL2: &str = "githubapiuser2@example.com";
+    const PASSWORD: &str = "longpassword2";
+    let db = &db;
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
+    data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+    data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+    let scopes = [AccessTokenScope::ReadGists, AccessTokenScope::WriteGists];
+    let token = data
+        .new_access_token(db, NAME, "github", &scopes)
+        .await
+        .unwrap()
+        .token;
+    let token2 = data
+        .new_access_token(db, NAME2, "github", &scopes)
+        .await
+        .unwrap()
+        .token;
+    let app = get_app!(data, db).await;
+    // GitHub clients use the token scheme
+    let auth = |token: &str| (header::AUTHORIZATION, format!("token {token}"));
+
+    let mut files = BTreeMap::new();
+    files.insert(
+        "hello world.md".to_owned(),
+        CreateFile {
+            content: "# hello".into(),
+        },
+    );
+    files.insert(
+        "main.rs".to_owned(),
+        CreateFile {
+            content: "fn main() {}".into(),
+        },
+    );
+    let msg = CreateGistRequest {
+        description: Some("github api".into()),
+        public: false,
+        files,
+    };
+
+    // authentication is required to create gists
+    let resp = test::call_service(&app, post_request!(&msg, ROUTES.gists).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // gists aren't left behind when their files can't be written
+    let mut invalid = msg.clone();
+    invalid.files.insert(
+        "../escape".into(),
+        CreateFile {
+            content: "escape".into(),
+        },
+    );
+    let resp = test::call_service(
+        &app,
+        post_request!(&invalid, ROUTES.gists)
+            .insert_header(auth(&token))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(db.get_user_gists(NAME).await.unwrap().is_empty());
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&msg, ROUTES.gists)
+            .insert_header(auth(&token))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let gist: GitHubGist = test::read_body_json(resp).await;
+    assert!(!gist.public);
+    assert_eq!(gist.owner.login, NAME);
+    assert_eq!(gist.description.as_deref(), Some("github api"));
+    assert_eq!(gist.files.len(), 2);
+    let file = gist.files.get("hello world.md").unwrap();
+    assert_eq!(file.content.as_deref(), Some("# hello"));
+    assert_eq!(file.size, "# hello".len());
+    assert!(file
+        .raw_url
+        .ends_with(&V1_API_ROUTES.gist.get_raw_route(&GetFilePath {
+            username: NAME.into(),
+            gist: gist.id.clone(),
+            file: "hello world.md".into(),
+        })));
+    // secret gists on GitHub are unlisted gists on GitPad
+    assert_eq!(
+        db.get_gist(&gist.id).await.unwrap().visibility,
+        GistVisibility::Unlisted
+    );
+
+    // secret gists can be read by anyone with the link
+    let gist_route = ROUTES.get_gist_route(&gist.id);
+    let resp = get_request!(&app, &gist_route);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp: GitHubGist = test::read_body_json(resp).await;
+    assert_eq!(resp.id, gist.id);
+    assert_eq!(resp.files.len(), 2);
+
+    // only owners can update gists
+    let mut update = UpdateGistRequest {
+        description: Some("updated".into()),
+        ..Default::default()
+    };
+    let resp = test::call_service(
+        &app,
+        patch_request!(&update, &gist_route)
+            .insert_header(auth(&token2))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    update.files.insert("main.rs".into(), None);
+    update.files.insert(
+        "hello world.md".into(),
+        Some(UpdateFile {
+            content: Some("# updated".into()),
+            filename: None,
+        }),
+    );
+    update.files.insert(
+        "src/lib.rs".into(),
+        Some(UpdateFile {
+            content: Some("pub fn lib() {}".into()),
+            filename: None,
+        }),
+    );
+    let resp = test::call_service(
+        &app,
+        patch_request!(&update, &gist_route)
+            .insert_header(auth(&token))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let updated: GitHubGist = test::read_body_json(resp).await;
+    assert_eq!(updated.description.as_deref(), Some("updated"));
+    assert_eq!(
+        updated.files.keys().collect::<Vec<_>>(),
+        ["hello world.md", "src/lib.rs"]
+    );
+    assert_eq!(
+        updated.files["hello world.md"].content.as_deref(),
+        Some("# updated")
+    );
+
+    // comments
+    let comments_route = ROUTES.get_comments_route(&gist.id);
+    let comment_msg = CreateCommentRequest {
+        body: "nice gist".into(),
+    };
+    let resp = test::call_service(
+        &app,
+        post_request!(&comment_msg, &comments_route).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(
+        &app,
+        post_request!(&comment_msg, &comments_route)
+            .insert_header(auth(&token2))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let comment: GitHubComment = test::read_body_json(resp).await;
+    assert_eq!(comment.body, comment_msg.body);
+    assert_eq!(comment.user.login, NAME2);
+
+    let resp = get_request!(&app, &comments_route);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let comments: Vec<GitHubComment> = test::read_body_json(resp).await;
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0].id, comment.id);
+
+    let comment_route = ROUTES.get_comment_route(&CommentPath {
+        gist: gist.id.clone(),
+        comment_id: comment.id,
+    });
+    let resp = get_request!(&app, &comment_route);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::delete()
+            .uri(&comment_route)
+            .insert_header(auth(&token))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::delete()
+            .uri(&comment_route)
+            .insert_header(auth(&token2))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = get_request!(&app, &comment_route);
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // user gists: secret gists are only listed for their owners
+    let user_gists_route = ROUTES.get_user_gists_route(NAME);
+    let resp = get_request!(&app, &user_gists_route);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let gists: Vec<GitHubGist> = test::read_body_json(resp).await;
+    assert!(gists.is_empty());
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&user_gists_route)
+            .insert_header(auth(&token))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let gists: Vec<GitHubGist> = test::read_body_json(resp).await;
+    assert_eq!(gists.len(), 1);
+    assert_eq!(gists[0].id, gist.id);
+    // contents aren't included in lists
+    assert!(gists[0].files.values().all(|f| f.content.is_none()));
+    let resp = get_request!(&app, &ROUTES.get_user_gists_route("nonexistentgithubuser"));
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // delete
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::delete()
+            .uri(&gist_route)
+            .insert_header(auth(&token2))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::delete()
+            .uri(&gist_route)
+            .insert_header(auth(&token))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(!db.gist_exists(&gist.id).await.unwrap());
+    let resp = get_request!(&app, &gist_route);
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
+}
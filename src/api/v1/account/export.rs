@@ -0,0 +1,48 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Account data export
+use actix_identity::Identity;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, Responder};
+
+use crate::errors::*;
+use crate::AppData;
+
+pub fn services(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(export_account);
+}
+
+/// Download gists and comments of the user as a gzip compressed tarball
+#[my_codegen::get(
+    path = "crate::V1_API_ROUTES.account.export",
+    wrap = "crate::api::v1::get_auth_middleware()"
+)]
+async fn export_account(
+    id: Identity,
+    data: AppData,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let export = data.export_account(&(**db), &username).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(export.filename)],
+        })
+        .body(export.content))
+}
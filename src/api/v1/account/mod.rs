@@ -24,8 +24,10 @@ use crate::data::api::v1::auth::Password;
 use crate::errors::*;
 use crate::AppData;
 
+pub mod export;
 #[cfg(test)]
 pub mod test;
+pub mod tokens;
 
 pub use super::auth;
 
@@ -43,6 +45,8 @@ pub fn services(cfg: &mut actix_web::web::ServiceConfig) {
     cfg.service(update_user_password);
     cfg.service(get_secret);
     cfg.service(update_user_secret);
+    tokens::services(cfg);
+    export::services(cfg);
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
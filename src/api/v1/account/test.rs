@@ -34,6 +34,9 @@ async fn postgrest_account_works() {
     email_udpate_password_validation_del_userworks(data.clone(), db.clone()).await;
     username_update_works(data.clone(), db.clone()).await;
     update_password_works(data.clone(), db.clone()).await;
+    access_tokens_work(data.clone(), db.clone()).await;
+    account_export_works(data.clone(), db.clone()).await;
+    admin_account_management_works(data.clone(), db.clone()).await;
 }
 
 #[actix_rt::test]
@@ -43,6 +46,9 @@ async fn sqlite_account_works() {
     email_udpate_password_validation_del_userworks(data.clone(), db.clone()).await;
     username_update_works(data.clone(), db.clone()).await;
     update_password_works(data.clone(), db.clone()).await;
+    access_tokens_work(data.clone(), db.clone()).await;
+    account_export_works(data.clone(), db.clone()).await;
+    admin_account_management_works(data.clone(), db.clone()).await;
 }
 
 async fn uname_email_exists_works(data: Arc<Data>, db: BoxDB) {
@@ -340,3 +346,252 @@ async fn update_password_works(data: Arc<Data>, db: BoxDB) {
     .await;
     assert_eq!(update_password_resp.status(), StatusCode::OK);
 }
+
+async fn access_tokens_work(data: Arc<Data>, db: BoxDB) {
+    use crate::api::v1::account::tokens::CreateAccessTokenRequest;
+    use crate::api::v1::gists::{CreateGistRequest, CreateGistResp};
+    use crate::data::api::v1::gists::{ContentType, FileInfo, FileMode, FileType};
+    use crate::data::api::v1::tokens::NewAccessToken;
+    use actix_web::http::header;
+    use db_core::prelude::*;
+
+    const NAME: &str = "accesstokenapiuser";
+    const PASSWORD: &str = "longpassword2";
+    const EMAIL: &str = "accesstokenapiuser@a.com";
+    let db = &db;
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let (_, signin_resp) = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+    let cookies = get_cookie!(signin_resp);
+    let app = get_app!(data, db).await;
+
+    let bearer = |token: &str| (header::AUTHORIZATION, format!("Bearer {token}"));
+
+    // create tokens
+    let mut tokens = Vec::default();
+    for (name, scopes) in [
+        ("read", vec![AccessTokenScope::ReadGists]),
+        (
+            "write",
+            vec![AccessTokenScope::ReadGists, AccessTokenScope::WriteGists],
+        ),
+        ("account", vec![AccessTokenScope::Account]),
+    ] {
+        let msg = CreateAccessTokenRequest {
+            name: name.into(),
+            scopes,
+        };
+        let resp = test::call_service(
+            &app,
+            post_request!(&msg, ROUTES.account.tokens)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let token: NewAccessToken = test::read_body_json(resp).await;
+        assert_eq!(token.info.scopes, msg.scopes);
+        tokens.push(token);
+    }
+    let (read, write, account) = (&tokens[0].token, &tokens[1].token, &tokens[2].token);
+
+    let resp = get_request!(&app, ROUTES.account.tokens, cookies.clone());
+    assert_eq!(resp.status(), StatusCode::OK);
+    let list: Vec<AccessToken> = test::read_body_json(resp).await;
+    assert_eq!(list.len(), 3);
+
+    // tokens can't be used to manage tokens
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(ROUTES.account.tokens)
+            .insert_header(bearer(account))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+
+    // write scope is required to create gists
+    let create_gist_msg = CreateGistRequest {
+        description: None,
+        visibility: GistVisibility::Private,
+        files: vec![FileInfo {
+            filename: "foo".into(),
+            content: FileType::File(ContentType::Text("foobar".into())),
+            mode: FileMode::Regular,
+        }],
+    };
+    let resp = test::call_service(
+        &app,
+        post_request!(&create_gist_msg, ROUTES.gist.new)
+            .insert_header(bearer(read))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(
+        &app,
+        post_request!(&create_gist_msg, ROUTES.gist.new)
+            .insert_header(bearer(write))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    // sessions aren't created for token authenticated requests
+    assert!(resp.response().cookies().next().is_none());
+    let gist: CreateGistResp = test::read_body_json(resp).await;
+    assert_eq!(db.get_gist(&gist.id).await.unwrap().owner, NAME);
+
+    // private gists can be read with read scope
+    let gist_index = ROUTES
+        .gist
+        .get_gist_index(&crate::api::v1::routes::PostCommentPath {
+            username: NAME.into(),
+            gist: gist.id.clone(),
+        });
+    let resp = get_request!(&app, &gist_index);
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&gist_index)
+            .insert_header(bearer(read))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&gist_index)
+            .insert_header(bearer(account))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // account scope is required for account routes
+    for (token, status) in [(read, StatusCode::FORBIDDEN), (account, StatusCode::OK)] {
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(ROUTES.account.get_secret)
+                .insert_header(bearer(token))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), status);
+    }
+
+    // invalid tokens are rejected
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&gist_index)
+            .insert_header(bearer("gp_invalid"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // revoke token
+    let delete_path = ROUTES.account.get_delete_token_route(tokens[0].info.id);
+    let resp = delete_request!(&app, &delete_path, cookies.clone());
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = delete_request!(&app, &delete_path, cookies.clone());
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&gist_index)
+            .insert_header(bearer(read))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+}
+
+async fn account_export_works(data: Arc<Data>, db: BoxDB) {
+    use actix_web::http::header;
+
+    const NAME: &str = "accountexportapiuser";
+    const PASSWORD: &str = "longpassword2";
+    const EMAIL: &str = "accountexportapiuser@a.com";
+    let db = &db;
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let (_, signin_resp) = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+    let cookies = get_cookie!(signin_resp);
+    let app = get_app!(data, db).await;
+
+    // unauthenticated
+    let resp = get_request!(&app, ROUTES.account.export);
+    assert_eq!(resp.status(), StatusCode::FOUND);
+
+    let resp = get_request!(&app, ROUTES.account.export, cookies.clone());
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/gzip"
+    );
+    let disposition = resp
+        .headers()
+        .get(header::CONTENT_DISPOSITION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(disposition.contains(&format!("{NAME}-export-")));
+    let body = test::read_body(resp).await;
+    assert!(body.starts_with(&[0x1f, 0x8b]));
+}
+
+async fn admin_account_management_works(data: Arc<Data>, db: BoxDB) {
+    use crate::data::api::v1::auth::{Login, Register};
+
+    const NAME: &str = "adminmanageduser";
+    const PASSWORD: &str = "longpassword2";
+    const NEW_PASSWORD: &str = "newlongpassword2";
+    let db = &db;
+
+    let _ = data.admin_delete_user(db, NAME).await;
+
+    let msg = Register {
+        username: NAME.into(),
+        password: PASSWORD.into(),
+        confirm_password: PASSWORD.into(),
+        email: None,
+    };
+    data.create_user(db, &msg).await.unwrap();
+    assert!(db.username_exists(NAME).await.unwrap());
+
+    // password is reset without the current password
+    data.admin_reset_password(db, NAME, NEW_PASSWORD)
+        .await
+        .unwrap();
+    let mut login = Login {
+        login: NAME.into(),
+        password: PASSWORD.into(),
+    };
+    assert_eq!(
+        data.login(db, &login).await.err(),
+        Some(ServiceError::WrongPassword)
+    );
+    login.password = NEW_PASSWORD.into();
+    assert_eq!(data.login(db, &login).await.unwrap(), NAME);
+
+    data.admin_delete_user(db, NAME).await.unwrap();
+    assert!(!db.username_exists(NAME).await.unwrap());
+    assert_eq!(
+        data.admin_delete_user(db, NAME).await.err(),
+        Some(ServiceError::AccountNotFound)
+    );
+    assert_eq!(
+        data.admin_reset_password(db, NAME, NEW_PASSWORD)
+            .await
+            .err(),
+        Some(ServiceError::AccountNotFound)
+    );
+}
@@ -0,0 +1,88 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Personal access token management
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse, Responder};
+use db_core::AccessTokenScope;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::AppData;
+
+pub fn services(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(get_access_tokens);
+    cfg.service(new_access_token);
+    cfg.service(delete_access_token);
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreateAccessTokenRequest {
+    pub name: String,
+    pub scopes: Vec<AccessTokenScope>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccessTokenPath {
+    pub id: i64,
+}
+
+#[my_codegen::get(
+    path = "crate::V1_API_ROUTES.account.tokens",
+    wrap = "crate::api::v1::get_auth_middleware()"
+)]
+async fn get_access_tokens(
+    id: Identity,
+    data: AppData,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let tokens = data.get_access_tokens(&(**db), &username).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[my_codegen::post(
+    path = "crate::V1_API_ROUTES.account.tokens",
+    wrap = "crate::api::v1::get_auth_middleware()"
+)]
+async fn new_access_token(
+    id: Identity,
+    payload: web::Json<CreateAccessTokenRequest>,
+    data: AppData,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let token = data
+        .new_access_token(&(**db), &username, &payload.name, &payload.scopes)
+        .await?;
+    Ok(HttpResponse::Ok().json(token))
+}
+
+#[my_codegen::delete(
+    path = "crate::V1_API_ROUTES.account.delete_token",
+    wrap = "crate::api::v1::get_auth_middleware()"
+)]
+async fn delete_access_token(
+    id: Identity,
+    path: web::Path<AccessTokenPath>,
+    data: AppData,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    data.delete_access_token(&(**db), &username, path.id)
+        .await?;
+    Ok(HttpResponse::Ok())
+}
@@ -0,0 +1,221 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Authentication with personal access tokens
+//!
+//! API requests can be authenticated with `Authorization: Bearer <token>` instead of the
+//! session cookie. [BearerIdentityPolicy] resolves tokens to the identity of their owners,
+//! so handlers and [get_auth_middleware](super::get_auth_middleware) work the same
+//! irrespective of how a request was authenticated.
+use actix_identity::{CookieIdentityPolicy, IdentityPolicy};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, header::HeaderMap, Method};
+use actix_web::{Error, HttpMessage};
+use db_core::AccessTokenScope;
+use futures::future::{ready, FutureExt, LocalBoxFuture};
+
+use crate::errors::*;
+use crate::AppData;
+
+/// Marks requests that were authenticated with a personal access token
+struct BearerIdentity;
+
+/// Get scope that a personal access token must have to be used on a route. Tokens can
+/// only be used on API routes, and they can't be used to manage other tokens.
+pub fn required_scope(method: &Method, path: &str) -> Option<AccessTokenScope> {
+    let tokens = crate::V1_API_ROUTES.account.tokens;
+    if path == tokens || path.starts_with(&format!("{tokens}/")) {
+        return None;
+    }
+    if path.starts_with("/api/v3/") {
+        // GitHub Gists API compatibility layer, see [crate::api::github]
+        return if method == Method::GET || method == Method::HEAD {
+            Some(AccessTokenScope::ReadGists)
+        } else {
+            Some(AccessTokenScope::WriteGists)
+        };
+    }
+    let path = path.strip_prefix("/api/v1/")?;
+    if path.starts_with("account/") {
+        Some(AccessTokenScope::Account)
+    } else if path.starts_with("gist/") || path.starts_with("gists/") {
+        if method == Method::GET || method == Method::HEAD {
+            Some(AccessTokenScope::ReadGists)
+        } else {
+            Some(AccessTokenScope::WriteGists)
+        }
+    } else {
+        None
+    }
+}
+
+/// Get token from `Authorization: Bearer <token>` header. GitHub clients send
+/// `Authorization: token <token>`, which is accepted too.
+fn get_bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("Bearer") || scheme.eq_ignore_ascii_case("token") {
+        Some(token.trim().to_owned())
+    } else {
+        None
+    }
+}
+
+/// Identity policy that accepts personal access tokens on API routes and falls back to
+/// the session cookie
+pub struct BearerIdentityPolicy {
+    cookie: CookieIdentityPolicy,
+}
+
+impl BearerIdentityPolicy {
+    pub fn new(cookie: CookieIdentityPolicy) -> Self {
+        Self { cookie }
+    }
+}
+
+impl IdentityPolicy for BearerIdentityPolicy {
+    type Future = LocalBoxFuture<'static, Result<Option<String>, Error>>;
+    type ResponseFuture = LocalBoxFuture<'static, Result<(), Error>>;
+
+    fn from_request(&self, req: &mut ServiceRequest) -> Self::Future {
+        let token = get_bearer_token(req.headers());
+        let scope = required_scope(req.method(), req.path());
+        let (token, scope) = match (token, scope) {
+            (Some(token), Some(scope)) => (token, scope),
+            _ => return self.cookie.from_request(req).boxed_local(),
+        };
+
+        req.extensions_mut().insert(BearerIdentity);
+        let data = req.app_data::<AppData>().cloned();
+        let db = req.app_data::<crate::DB>().cloned();
+        async move {
+            let (data, db) = data.zip(db).ok_or(ServiceError::InternalServerError)?;
+            let username = data
+                .authenticate_access_token(db.as_ref(), &token, scope)
+                .await?;
+            Ok(Some(username))
+        }
+        .boxed_local()
+    }
+
+    fn to_response<B>(
+        &self,
+        identity: Option<String>,
+        changed: bool,
+        response: &mut ServiceResponse<B>,
+    ) -> Self::ResponseFuture {
+        // token authenticated requests don't have sessions
+        if response.request().extensions().contains::<BearerIdentity>() {
+            return ready(Ok(())).boxed_local();
+        }
+        self.cookie
+            .to_response(identity, changed, response)
+            .boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::V1_API_ROUTES;
+
+    #[test]
+    fn required_scope_works() {
+        let file = V1_API_ROUTES
+            .gist
+            .get_file_route(&crate::api::v1::routes::GetFilePath {
+                username: "bob".into(),
+                gist: "foo".into(),
+                file: "bar".into(),
+            });
+        assert_eq!(
+            required_scope(&Method::GET, &file),
+            Some(AccessTokenScope::ReadGists)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, V1_API_ROUTES.gist.explore),
+            Some(AccessTokenScope::ReadGists)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, V1_API_ROUTES.gist.new),
+            Some(AccessTokenScope::WriteGists)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, &file),
+            Some(AccessTokenScope::WriteGists)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, V1_API_ROUTES.account.get_secret),
+            Some(AccessTokenScope::Account)
+        );
+
+        // tokens can't manage tokens
+        assert_eq!(
+            required_scope(&Method::GET, V1_API_ROUTES.account.tokens),
+            None
+        );
+        assert_eq!(
+            required_scope(
+                &Method::DELETE,
+                &V1_API_ROUTES.account.get_delete_token_route(1)
+            ),
+            None
+        );
+
+        // tokens are only accepted on API routes
+        assert_eq!(
+            required_scope(&Method::POST, V1_API_ROUTES.auth.login),
+            None
+        );
+        assert_eq!(required_scope(&Method::GET, "/~bob/foo"), None);
+
+        // GitHub Gists API compatibility layer
+        assert_eq!(
+            required_scope(&Method::GET, &crate::GITHUB_ROUTES.get_gist_route("foo")),
+            Some(AccessTokenScope::ReadGists)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, crate::GITHUB_ROUTES.gists),
+            Some(AccessTokenScope::WriteGists)
+        );
+    }
+
+    #[test]
+    fn get_bearer_token_works() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(get_bearer_token(&headers), None);
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_static("Bearer gp_foo"),
+        );
+        assert_eq!(get_bearer_token(&headers).as_deref(), Some("gp_foo"));
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_static("bearer gp_foo"),
+        );
+        assert_eq!(get_bearer_token(&headers).as_deref(), Some("gp_foo"));
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_static("token gp_foo"),
+        );
+        assert_eq!(get_bearer_token(&headers).as_deref(), Some("gp_foo"));
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_static("Basic Zm9vOmJhcg=="),
+        );
+        assert_eq!(get_bearer_token(&headers), None);
+    }
+}
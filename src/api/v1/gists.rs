@@ -15,13 +15,20 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 use actix_identity::Identity;
+use actix_web::http::header::{
+    self, CacheControl, CacheDirective, ContentDisposition, DispositionParam, DispositionType,
+    ETag, EntityTag, IfNoneMatch,
+};
 use actix_web::*;
 
 use db_core::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use super::routes::{GetCommentPath, GetFilePath, PostCommentPath};
+use super::routes::{ArchivePath, GetCommentPath, GetFilePath, PostCommentPath};
+use crate::data::api::v1::explore::{DEFAULT_EXPLORE_LIMIT, MAX_EXPLORE_LIMIT};
 use crate::data::api::v1::gists::{CreateGist, FileInfo, GistID};
+use crate::data::api::v1::import::{GitHubExport, ImportGist};
+use crate::data::api::v1::search::{DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT};
 use crate::errors::*;
 use crate::utils::escape_spaces;
 use crate::*;
@@ -47,11 +54,26 @@ impl CreateGistRequest {
 pub fn services(cfg: &mut web::ServiceConfig) {
     cfg.service(new);
     cfg.service(get_file);
+    cfg.service(get_raw);
     cfg.service(post_comment);
     cfg.service(get_comment);
     cfg.service(get_gist_comments);
     cfg.service(delete_comment);
     cfg.service(index);
+    cfg.service(update);
+    cfg.service(delete_gist);
+    cfg.service(get_revisions);
+    cfg.service(get_diff);
+    cfg.service(fork_gist);
+    cfg.service(get_forks);
+    cfg.service(star_gist);
+    cfg.service(unstar_gist);
+    cfg.service(star_status);
+    cfg.service(get_starred_gists);
+    cfg.service(explore);
+    cfg.service(search);
+    cfg.service(get_archive);
+    cfg.service(import_gist);
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,9 +110,16 @@ async fn new(
     Ok(HttpResponse::Ok().json(&resp))
 }
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RevisionQuery {
+    /// full or abbreviated commit ID. HEAD is used when unset
+    pub rev: Option<String>,
+}
+
 #[my_codegen::get(path = "crate::V1_API_ROUTES.gist.get_file")]
 async fn get_file(
     path: web::Path<GetFilePath>,
+    query: web::Query<RevisionQuery>,
     data: AppData,
     id: Identity,
     db: crate::DB,
@@ -103,6 +132,7 @@ async fn get_file(
                     db.as_ref(),
                     &GistID::ID(&gist.public_id),
                     &escape_spaces(&path.file),
+                    query.rev.as_deref(),
                 )
                 .await?;
             Ok(HttpResponse::Ok().json(contents))
@@ -115,6 +145,7 @@ async fn get_file(
                             db.as_ref(),
                             &GistID::ID(&gist.public_id),
                             &escape_spaces(&path.file),
+                            query.rev.as_deref(),
                         )
                         .await?;
                     return Ok(HttpResponse::Ok().json(contents));
@@ -125,6 +156,67 @@ async fn get_file(
     }
 }
 
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.raw")]
+async fn get_raw(
+    req: HttpRequest,
+    path: web::Path<GetFilePath>,
+    query: web::Query<RevisionQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.owner != path.username
+        || (gist.visibility == GistVisibility::Private
+            && id.identity().as_ref() != Some(&gist.owner))
+    {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let file = escape_spaces(&path.file);
+    let rev = query.into_inner().rev;
+    let public_id = gist.public_id;
+    let raw = web::block(move || data.read_raw_file(&public_id, &file, rev.as_deref()))
+        .await
+        .map_err(|_| ServiceError::InternalServerError)??;
+
+    let etag = EntityTag::new_strong(raw.oid.clone());
+    let mut cache_control = vec![CacheDirective::NoCache];
+    if gist.visibility == GistVisibility::Private {
+        cache_control.push(CacheDirective::Private);
+    }
+
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(CacheControl(cache_control))
+            .finish());
+    }
+
+    let mut resp = HttpResponse::Ok();
+    resp.content_type(raw.content_type())
+        .insert_header(ETag(etag))
+        .insert_header(CacheControl(cache_control))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"));
+    if raw.is_binary {
+        let disposition = if raw.is_inline() {
+            DispositionType::Inline
+        } else {
+            DispositionType::Attachment
+        };
+        resp.insert_header(ContentDisposition {
+            disposition,
+            parameters: vec![DispositionParam::Filename(raw.filename.clone())],
+        });
+    }
+    Ok(resp.body(raw.content))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostCommentRequest {
     pub comment: String,
@@ -250,28 +342,473 @@ async fn delete_comment(
     wrap = "super::get_auth_middleware()"
 )]
 async fn index(
+    path: web::Path<PostCommentPath>,
+    query: web::Query<RevisionQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.visibility == GistVisibility::Private && username != gist.owner {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let resp = data
+        .gist_preview(
+            db.as_ref(),
+            &mut GistID::ID(&path.gist),
+            query.rev.as_deref(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UpdateGistRequest {
+    /// new description. Empty description clears it, unset leaves it unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<GistVisibility>,
+    /// complete list of files in the gist. Files that aren't in the list are removed
+    /// from the gist. Unset leaves files unchanged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<FileInfo>>,
+}
+
+#[my_codegen::patch(
+    path = "crate::V1_API_ROUTES.gist.update",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn update(
+    payload: web::Json<UpdateGistRequest>,
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let gist = db.get_gist(&path.gist).await?;
+    if username != gist.owner {
+        return match gist.visibility {
+            GistVisibility::Public | GistVisibility::Unlisted => {
+                Err(ServiceError::UnauthorizedOperation(
+                    "This user is not the owner of the gist to update it".into(),
+                ))
+            }
+            GistVisibility::Private => Err(ServiceError::GistNotFound),
+        };
+    }
+
+    if let Some(files) = &payload.files {
+        data.replace_files(db.as_ref(), &mut GistID::ID(&gist.public_id), files)
+            .await?;
+    }
+
+    if payload.description.is_some() || payload.visibility.is_some() {
+        let description = match payload.description.as_deref().map(|d| d.trim()) {
+            Some("") => None,
+            Some(description) => Some(description),
+            None => gist.description.as_deref(),
+        };
+        let msg = UpdateGist {
+            owner: &username,
+            public_id: &gist.public_id,
+            description,
+            visibility: payload.visibility.as_ref().unwrap_or(&gist.visibility),
+        };
+        db.update_gist(&msg).await?;
+    }
+
+    let resp = data
+        .gist_preview(db.as_ref(), &mut GistID::ID(&gist.public_id), None)
+        .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::delete(
+    path = "crate::V1_API_ROUTES.gist.delete",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn delete_gist(
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let gist = db.get_gist(&path.gist).await?;
+    if username != gist.owner {
+        return match gist.visibility {
+            GistVisibility::Public | GistVisibility::Unlisted => {
+                Err(ServiceError::UnauthorizedOperation(
+                    "This user is not the owner of the gist to delete it".into(),
+                ))
+            }
+            GistVisibility::Private => Err(ServiceError::GistNotFound),
+        };
+    }
+
+    data.delete_gist(db.as_ref(), &username, &gist.public_id)
+        .await?;
+    Ok(HttpResponse::Ok())
+}
+
+/// Default number of revisions returned per page
+pub const DEFAULT_REVISIONS_LIMIT: usize = 20;
+/// Maximum number of revisions that can be requested per page
+pub const MAX_REVISIONS_LIMIT: usize = 100;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RevisionsQuery {
+    /// page number, starts at 0
+    pub page: Option<usize>,
+    /// number of revisions per page
+    pub limit: Option<usize>,
+}
+
+impl RevisionsQuery {
+    /// get page number
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or_default()
+    }
+
+    /// get number of revisions per page, capped at [MAX_REVISIONS_LIMIT]
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_REVISIONS_LIMIT)
+            .clamp(1, MAX_REVISIONS_LIMIT)
+    }
+}
+
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.revisions")]
+async fn get_revisions(
+    path: web::Path<PostCommentPath>,
+    query: web::Query<RevisionsQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.visibility == GistVisibility::Private && id.identity() != Some(gist.owner) {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let resp = data
+        .gist_revisions(
+            db.as_ref(),
+            &GistID::ID(&path.gist),
+            query.page(),
+            query.limit(),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DiffQuery {
+    /// old revision. Defaults to the parent of `to`
+    pub from: Option<String>,
+    /// new revision. Defaults to HEAD
+    pub to: Option<String>,
+}
+
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.diff")]
+async fn get_diff(
+    path: web::Path<PostCommentPath>,
+    query: web::Query<DiffQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.visibility == GistVisibility::Private && id.identity() != Some(gist.owner) {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let resp = data
+        .gist_diff(
+            db.as_ref(),
+            &GistID::ID(&path.gist),
+            query.from.as_deref(),
+            query.to.as_deref(),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::post(
+    path = "crate::V1_API_ROUTES.gist.fork",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn fork_gist(
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let gist = data.fork_gist(db.as_ref(), &username, &path.gist).await?;
+    let resp = CreateGistResp { id: gist.id };
+    Ok(HttpResponse::Ok().json(&resp))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportGistRequest {
+    /// path of the repository, relative to `repository.import_root`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// gist metadata, as returned by the GitHub Gists API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export: Option<GitHubExport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<GistVisibility>,
+}
+
+#[my_codegen::post(
+    path = "crate::V1_API_ROUTES.gist.import",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn import_gist(
+    payload: web::Json<ImportGistRequest>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    let repository = match &payload.repository {
+        Some(path) => Some(data.get_import_path(path)?),
+        None => None,
+    };
+    let msg = ImportGist {
+        owner: &username,
+        repository: repository.as_deref(),
+        export: payload.export.as_ref(),
+        description: payload.description.as_deref(),
+        visibility: payload.visibility.as_ref(),
+    };
+    let gist = data.import_gist(db.as_ref(), &msg).await?;
+    let resp = CreateGistResp { id: gist.id };
+    Ok(HttpResponse::Ok().json(&resp))
+}
+
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.get_forks")]
+async fn get_forks(
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity();
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.visibility == GistVisibility::Private && username.as_ref() != Some(&gist.owner) {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let resp = data
+        .get_forks(db.as_ref(), &path.gist, username.as_deref())
+        .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::post(
+    path = "crate::V1_API_ROUTES.gist.star",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn star_gist(
     path: web::Path<PostCommentPath>,
     id: Identity,
     db: crate::DB,
     data: AppData,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity().unwrap();
+    data.star_gist(db.as_ref(), &username, &path.gist).await?;
+    Ok(HttpResponse::Ok())
+}
+
+#[my_codegen::delete(
+    path = "crate::V1_API_ROUTES.gist.unstar",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn unstar_gist(
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
 ) -> ServiceResult<impl Responder> {
     let username = id.identity().unwrap();
     let gist = db.get_gist(&path.gist).await?;
     if gist.visibility == GistVisibility::Private && username != gist.owner {
         return Err(ServiceError::GistNotFound);
     }
+    db.unstar_gist(&username, &path.gist).await?;
+    Ok(HttpResponse::Ok())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StarStatus {
+    /// number of users that have starred the gist
+    pub stars: i64,
+    /// set when the requesting user has starred the gist
+    pub starred: bool,
+}
+
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.star_status")]
+async fn star_status(
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
+) -> ServiceResult<impl Responder> {
+    let username = id.identity();
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.visibility == GistVisibility::Private && username.as_ref() != Some(&gist.owner) {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let starred = match &username {
+        Some(username) => db.is_starred(username, &path.gist).await?,
+        None => false,
+    };
+    let resp = StarStatus {
+        stars: db.get_star_count(&path.gist).await?,
+        starred,
+    };
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.starred")]
+async fn get_starred_gists(
+    path: web::Path<String>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    if !db.username_exists(&path).await? {
+        return Err(ServiceError::AccountNotFound);
+    }
+    let resp = data
+        .get_starred_gists(db.as_ref(), &path, id.identity().as_deref())
+        .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExploreQuery {
+    /// timestamp by which gists are sorted
+    pub sort: Option<GistSort>,
+    /// cursor returned with the previous page
+    pub after: Option<String>,
+    /// number of gists per page
+    pub limit: Option<usize>,
+}
+
+impl ExploreQuery {
+    /// get number of gists per page, capped at [MAX_EXPLORE_LIMIT]
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_EXPLORE_LIMIT)
+            .clamp(1, MAX_EXPLORE_LIMIT)
+    }
+}
 
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.explore")]
+async fn explore(
+    query: web::Query<ExploreQuery>,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
     let resp = data
-        .gist_preview(db.as_ref(), &mut GistID::ID(&path.gist))
+        .explore_gists(
+            db.as_ref(),
+            query.sort.unwrap_or_default(),
+            query.after.as_deref(),
+            query.limit(),
+        )
         .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.archive")]
+async fn get_archive(
+    path: web::Path<ArchivePath>,
+    query: web::Query<RevisionQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.owner != path.username
+        || (gist.visibility == GistVisibility::Private
+            && id.identity().as_ref() != Some(&gist.owner))
+    {
+        return Err(ServiceError::GistNotFound);
+    }
+
+    let format = path.format;
+    let rev = query.into_inner().rev;
+    let archive = web::block(move || data.gist_archive(&gist.public_id, rev.as_deref(), format))
+        .await
+        .map_err(|_| ServiceError::InternalServerError)??;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(archive.filename)],
+        })
+        .body(archive.content))
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    /// search terms
+    #[serde(default)]
+    pub q: String,
+    /// page number, starting from 0
+    pub page: Option<usize>,
+    /// number of gists per page
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    /// get number of gists per page, capped at [MAX_SEARCH_LIMIT]
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT)
+    }
+}
 
+#[my_codegen::get(path = "crate::V1_API_ROUTES.gist.search")]
+async fn search(
+    query: web::Query<SearchQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<impl Responder> {
+    let resp = data
+        .search_gists(
+            db.as_ref(),
+            &query.q,
+            id.identity().as_deref(),
+            query.page.unwrap_or_default(),
+            query.limit(),
+        )
+        .await?;
     Ok(HttpResponse::Ok().json(resp))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data::api::v1::gists::{ContentType, FileType, GistInfo};
+    use crate::data::api::v1::archive::ArchiveFormat;
+    use crate::data::api::v1::diff::GistDiff;
+    use crate::data::api::v1::explore::ExploreGists;
+    use crate::data::api::v1::gists::{ContentType, FileMode, FileType, GistInfo};
+    use crate::data::api::v1::revisions::{ChangeType, Revision};
     use crate::tests::*;
     use actix_web::ResponseError;
 
@@ -309,14 +846,17 @@ mod tests {
             FileInfo {
                 filename: "foo".into(),
                 content: FileType::File(ContentType::Text("foobar".into())),
+                mode: FileMode::Regular,
             },
             FileInfo {
                 filename: "bar".into(),
                 content: FileType::File(ContentType::Text("foobar".into())),
+                mode: FileMode::Regular,
             },
             FileInfo {
                 filename: "foo bar".into(),
                 content: FileType::File(ContentType::Text("foobar".into())),
+                mode: FileMode::Regular,
             },
         ];
 
@@ -340,7 +880,38 @@ mod tests {
         data.gist_created_test_helper(db, &gist_id, NAME).await;
         data.gist_files_written_helper(db, &gist_id, &files).await;
 
-        // get gists
+        // path-like filenames are written to subdirectories
+        let nested_gist_msg = CreateGistRequest {
+            description: None,
+            visibility: GistVisibility::Public,
+            files: vec![FileInfo {
+                filename: "src/main.rs".into(),
+                content: FileType::File(ContentType::Text("fn main() {}".into())),
+                mode: FileMode::Executable,
+            }],
+        };
+        let create_gist_resp = test::call_service(
+            &app,
+            post_request!(&nested_gist_msg, V1_API_ROUTES.gist.new)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(create_gist_resp.status(), StatusCode::OK);
+        let nested_gist: CreateGistResp = test::read_body_json(create_gist_resp).await;
+        let nested_file_path = GetFilePath {
+            username: NAME.into(),
+            gist: nested_gist.id,
+            file: "src/main.rs".into(),
+        };
+        let resp = get_request!(&app, &V1_API_ROUTES.gist.get_file_route(&nested_file_path));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let content: FileInfo = test::read_body_json(resp).await;
+        assert_eq!(content.filename, "main.rs");
+        assert_eq!(content.content, nested_gist_msg.files[0].content);
+        assert_eq!(content.mode, FileMode::Executable);
+
+        // get gists
         // 1. Public gists
         let mut get_file_path = GetFilePath {
             username: NAME.into(),
@@ -359,6 +930,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
 
@@ -369,6 +941,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
 
@@ -379,6 +952,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
         }
@@ -412,6 +986,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
 
@@ -422,6 +997,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
 
@@ -432,6 +1008,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
         }
@@ -463,6 +1040,7 @@ mod tests {
             let req_escaped_file = FileInfo {
                 filename: escape_spaces(&file.filename),
                 content: file.content.clone(),
+                mode: file.mode,
             };
             assert_eq!(&content, &req_escaped_file);
 
@@ -853,6 +1431,7 @@ mod tests {
                 .map(|f| FileInfo {
                     filename: escape_spaces(&f.filename),
                     content: f.content.clone(),
+                    mode: f.mode,
                 })
                 .collect();
 
@@ -860,5 +1439,807 @@ mod tests {
                 .iter()
                 .any(|f| f.filename == file.filename && f.content == file.content));
         }
+
+        /*
+         *
+         * ============================================
+         *                  Gist revisions
+         * ============================================
+         *
+         */
+        let mut revisions_component = PostCommentPath {
+            username: NAME.into(),
+            gist: "non-existant".into(),
+        };
+
+        // non-existant gist
+        let path = V1_API_ROUTES.gist.get_revisions_route(&revisions_component);
+        let resp = get_request!(&app, &path);
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // public gist, unauthenticated
+        revisions_component.gist = gist_id.clone();
+        let path = V1_API_ROUTES.gist.get_revisions_route(&revisions_component);
+        let resp = get_request!(&app, &path);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let revisions: Vec<Revision> = test::read_body_json(resp).await;
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].files.len(), files.len());
+
+        let resp = get_request!(&app, &format!("{path}?page=1&limit=1"));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let revisions: Vec<Revision> = test::read_body_json(resp).await;
+        assert!(revisions.is_empty());
+
+        // private gist, user doesn't have access
+        revisions_component.gist = private.clone();
+        let path = V1_API_ROUTES.gist.get_revisions_route(&revisions_component);
+        let resp = get_request!(&app, &path);
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = get_request!(&app, &path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // private gist, user=owner
+        let resp = get_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let revisions: Vec<Revision> = test::read_body_json(resp).await;
+        assert_eq!(revisions.len(), 1);
+
+        /*
+         *
+         * ============================================
+         *          Gist at a specific revision
+         * ============================================
+         *
+         */
+        let rev = &revisions[0].id;
+        let path = format!(
+            "{}?rev={}",
+            V1_API_ROUTES.gist.get_gist_index(&gist_index),
+            &rev[..7]
+        );
+        let resp = get_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let preview: GistInfo = test::read_body_json(resp).await;
+        assert_eq!(&preview.revision, rev);
+        assert_eq!(preview.files.len(), files.len());
+
+        get_file_path.file = files[0].filename.clone();
+        let path = format!(
+            "{}?rev={rev}",
+            V1_API_ROUTES.gist.get_file_route(&get_file_path)
+        );
+        let resp = get_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for bad_rev in ["notarevision", "0000000"] {
+            let path = format!(
+                "{}?rev={bad_rev}",
+                V1_API_ROUTES.gist.get_gist_index(&gist_index)
+            );
+            let resp = get_request!(&app, &path, cookies.clone());
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            let err: ErrorToResponse = test::read_body_json(resp).await;
+            assert_eq!(err.error, format!("{}", ServiceError::RevisionNotFound));
+        }
+
+        get_file_path.file = "filedoesntexist".into();
+        let path = V1_API_ROUTES.gist.get_file_route(&get_file_path);
+        let resp = get_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::FileNotFound));
+
+        /*
+         *
+         * ============================================
+         *                   Gist diff
+         * ============================================
+         *
+         */
+        let mut diff_component = PostCommentPath {
+            username: NAME.into(),
+            gist: private.clone(),
+        };
+
+        // private gist, user doesn't have access
+        let path = V1_API_ROUTES.gist.get_diff_route(&diff_component);
+        let resp = get_request!(&app, &path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::GistNotFound));
+
+        // private gist, user=owner; first commit is diffed against an empty tree
+        let resp = get_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let diff: GistDiff = test::read_body_json(resp).await;
+        assert!(diff.from.is_none());
+        assert_eq!(&diff.to, rev);
+        assert_eq!(diff.files.len(), files.len());
+        assert!(diff.files.iter().all(|f| f.change == ChangeType::Added));
+
+        // public gist, unauthenticated
+        diff_component.gist = gist_id.clone();
+        let path = V1_API_ROUTES.gist.get_diff_route(&diff_component);
+        let resp = get_request!(&app, &path);
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let path = format!("{path}?from=notarevision");
+        let resp = get_request!(&app, &path);
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::RevisionNotFound));
+
+        /*
+         *
+         * ============================================
+         *                  Gist update
+         * ============================================
+         *
+         */
+        let mut update_component = PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        };
+        let mut update_msg = UpdateGistRequest {
+            description: Some("updated description".into()),
+            visibility: Some(GistVisibility::Unlisted),
+            files: None,
+        };
+
+        // unauthenticated
+        let path = V1_API_ROUTES.gist.get_update_route(&update_component);
+        let resp = test::call_service(&app, patch_request!(&update_msg, &path).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        // non-owner, public gist
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &path)
+                .cookie(cookies2.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(
+            resp.status(),
+            ServiceError::UnauthorizedOperation("".into()).status_code()
+        );
+
+        // non-owner, private gist
+        update_component.gist = private.clone();
+        let private_path = V1_API_ROUTES.gist.get_update_route(&update_component);
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &private_path)
+                .cookie(cookies2.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::GistNotFound));
+
+        // owner updates metadata; files are left untouched
+        let before = db.get_gist(&gist_id).await.unwrap();
+        let head = data
+            .gist_revisions(db, &GistID::ID(&gist_id), 0, 1)
+            .await
+            .unwrap();
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &path)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let updated: GistInfo = test::read_body_json(resp).await;
+        assert_eq!(updated.description.as_deref(), Some("updated description"));
+        assert_eq!(updated.visibility, GistVisibility::Unlisted);
+        assert_eq!(updated.files.len(), files.len());
+        assert_eq!(updated.revision, head[0].id);
+        assert!(updated.updated >= before.updated);
+
+        // empty files list
+        update_msg.description = None;
+        update_msg.visibility = None;
+        update_msg.files = Some(Vec::default());
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &path)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), ServiceError::GistEmpty.status_code());
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::GistEmpty));
+
+        // owner renames one file, modifies another and removes the rest
+        let new_files = vec![
+            FileInfo {
+                filename: "foo.md".into(),
+                content: FileType::File(ContentType::Text("foobar".into())),
+                mode: FileMode::Regular,
+            },
+            FileInfo {
+                filename: "bar".into(),
+                content: FileType::File(ContentType::Text("updated".into())),
+                mode: FileMode::Regular,
+            },
+        ];
+        update_msg.files = Some(new_files.clone());
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &path)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let updated: GistInfo = test::read_body_json(resp).await;
+        assert_eq!(updated.description.as_deref(), Some("updated description"));
+        assert_eq!(updated.files.len(), new_files.len());
+        for file in new_files.iter() {
+            assert!(updated.files.iter().any(|f| f == file));
+        }
+        let revisions = data
+            .gist_revisions(db, &GistID::ID(&gist_id), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].id, updated.revision);
+
+        // directory entries are written as sub-trees
+        update_msg.files = Some(vec![FileInfo {
+            filename: "src".into(),
+            content: FileType::Dir(vec![FileInfo {
+                filename: "main.rs".into(),
+                content: FileType::File(ContentType::Text("fn main() {}".into())),
+                mode: FileMode::Regular,
+            }]),
+            mode: FileMode::Regular,
+        }]);
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &path)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let updated: GistInfo = test::read_body_json(resp).await;
+        assert_eq!(updated.files.len(), 1);
+        assert_eq!(updated.files[0].filename, "src");
+        assert!(matches!(&updated.files[0].content, FileType::Dir(files) if files.len() == 1));
+
+        // clearing description
+        update_msg.description = Some("".into());
+        update_msg.files = None;
+        let resp = test::call_service(
+            &app,
+            patch_request!(&update_msg, &path)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let updated: GistInfo = test::read_body_json(resp).await;
+        assert!(updated.description.is_none());
+
+        /*
+         *
+         * ============================================
+         *                  Gist star
+         * ============================================
+         *
+         */
+        let mut star_component = PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        };
+        let star_path = V1_API_ROUTES.gist.get_star_route(&star_component);
+
+        // unauthenticated
+        let resp = test::call_service(&app, post_request!(&star_path).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let resp = get_request!(&app, &star_path);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let status: StarStatus = test::read_body_json(resp).await;
+        assert_eq!(status.stars, 0);
+        assert!(!status.starred);
+
+        // private gist
+        star_component.gist = private.clone();
+        let private_star_path = V1_API_ROUTES.gist.get_star_route(&star_component);
+        let resp = test::call_service(
+            &app,
+            post_request!(&private_star_path)
+                .cookie(cookies2.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = get_request!(&app, &private_star_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        for cookies in [cookies.clone(), cookies2.clone()] {
+            let resp =
+                test::call_service(&app, post_request!(&star_path).cookie(cookies).to_request())
+                    .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = get_request!(&app, &star_path, cookies2.clone());
+        let status: StarStatus = test::read_body_json(resp).await;
+        assert_eq!(status.stars, 2);
+        assert!(status.starred);
+        let resp = get_request!(
+            &app,
+            &V1_API_ROUTES.gist.get_gist_index(&PostCommentPath {
+                username: NAME.into(),
+                gist: gist_id.clone(),
+            }),
+            cookies.clone()
+        );
+        let info: GistInfo = test::read_body_json(resp).await;
+        assert_eq!(info.stars, 2);
+
+        // unlisted gists are only listed for the user that starred them
+        let starred_path = V1_API_ROUTES.gist.get_starred_route(NAME2);
+        let resp = get_request!(&app, &starred_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let starred: Vec<db_core::Gist> = test::read_body_json(resp).await;
+        assert_eq!(starred.len(), 1);
+        assert_eq!(starred[0].public_id, gist_id);
+        let resp = get_request!(&app, &starred_path);
+        let starred: Vec<db_core::Gist> = test::read_body_json(resp).await;
+        assert!(starred.is_empty());
+        let resp = get_request!(
+            &app,
+            &V1_API_ROUTES.gist.get_starred_route("nonexistentuser")
+        );
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // unstar
+        let unstar_path = V1_API_ROUTES.gist.get_unstar_route(&PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        });
+        let resp = delete_request!(&app, &unstar_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = get_request!(&app, &star_path, cookies2.clone());
+        let status: StarStatus = test::read_body_json(resp).await;
+        assert_eq!(status.stars, 1);
+        assert!(!status.starred);
+
+        /*
+         *
+         * ============================================
+         *                  Gist archive
+         * ============================================
+         *
+         */
+        let mut archive_component = PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        };
+        let archive_path = V1_API_ROUTES
+            .gist
+            .get_archive_route(&archive_component, ArchiveFormat::TarGz);
+        let resp = get_request!(&app, &archive_path);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/gzip"
+        );
+        let disposition = resp
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(disposition.starts_with("attachment"));
+        assert!(disposition.contains(&format!("{gist_id}-")));
+        let archive_path = V1_API_ROUTES
+            .gist
+            .get_archive_route(&archive_component, ArchiveFormat::Zip);
+        let resp = get_request!(&app, &format!("{archive_path}?rev=zzzzzzzz"));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = get_request!(&app, &archive_path.replace("/archive/zip", "/archive/rar"));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // gist must belong to user in path
+        archive_component.username = NAME2.into();
+        let resp = get_request!(
+            &app,
+            &V1_API_ROUTES
+                .gist
+                .get_archive_route(&archive_component, ArchiveFormat::Zip)
+        );
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // private gists can only be downloaded by their owners
+        archive_component.username = NAME.into();
+        archive_component.gist = private.clone();
+        let archive_path = V1_API_ROUTES
+            .gist
+            .get_archive_route(&archive_component, ArchiveFormat::Zip);
+        let resp = get_request!(&app, &archive_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = get_request!(&app, &archive_path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/zip"
+        );
+
+        /*
+         *
+         * ============================================
+         *                  Raw files
+         * ============================================
+         *
+         */
+        let png = vec![0x89, b'P', b'N', b'G', 0, 0, 0, 0];
+        let raw_files = [
+            FileInfo {
+                filename: "src/main.rs".into(),
+                content: FileType::File(ContentType::Text("fn main() {}".into())),
+                mode: FileMode::Regular,
+            },
+            FileInfo {
+                filename: "logo.png".into(),
+                content: FileType::File(ContentType::Binary(png.clone())),
+                mode: FileMode::Regular,
+            },
+        ];
+        data.write_file(db, &mut GistID::ID(&gist_id), &raw_files)
+            .await
+            .unwrap();
+
+        let mut raw_component = GetFilePath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+            file: "src/main.rs".into(),
+        };
+        let raw_path = V1_API_ROUTES.gist.get_raw_route(&raw_component);
+        let resp = get_request!(&app, &raw_path);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/x-rust; charset=utf-8"
+        );
+        assert!(resp.headers().get(header::CONTENT_DISPOSITION).is_none());
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+        let body = test::read_body(resp).await;
+        assert_eq!(body.as_ref(), b"fn main() {}");
+
+        // conditional GET
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&raw_path)
+                .insert_header((header::IF_NONE_MATCH, etag.clone()))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), etag);
+        assert!(test::read_body(resp).await.is_empty());
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&raw_path)
+                .insert_header((header::IF_NONE_MATCH, "\"stale\""))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        raw_component.file = "logo.png".into();
+        let resp = get_request!(&app, &V1_API_ROUTES.gist.get_raw_route(&raw_component));
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+        let disposition = resp
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(disposition.starts_with("inline"));
+        assert!(disposition.contains("logo.png"));
+        let body = test::read_body(resp).await;
+        assert_eq!(body.as_ref(), png.as_slice());
+
+        // directories can't be read raw
+        raw_component.file = "src".into();
+        let resp = get_request!(&app, &V1_API_ROUTES.gist.get_raw_route(&raw_component));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // gist must belong to user in path
+        raw_component.file = "logo.png".into();
+        raw_component.username = NAME2.into();
+        let resp = get_request!(&app, &V1_API_ROUTES.gist.get_raw_route(&raw_component));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // private gists can only be read by their owners
+        let private_file = data
+            .gist_preview(db, &mut GistID::ID(&private), None)
+            .await
+            .unwrap()
+            .files
+            .into_iter()
+            .find(|f| matches!(f.content, FileType::File(_)))
+            .unwrap();
+        raw_component.username = NAME.into();
+        raw_component.gist = private.clone();
+        raw_component.file = private_file.filename;
+        let raw_path = V1_API_ROUTES.gist.get_raw_route(&raw_component);
+        let resp = get_request!(&app, &raw_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = get_request!(&app, &raw_path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp
+            .headers()
+            .get(header::CACHE_CONTROL)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("private"));
+
+        /*
+         *
+         * ============================================
+         *                  Gist explore
+         * ============================================
+         *
+         */
+        let explore_path = V1_API_ROUTES.gist.explore;
+        let resp = get_request!(&app, explore_path);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: ExploreGists = test::read_body_json(resp).await;
+        assert!(page
+            .gists
+            .iter()
+            .all(|g| g.visibility == GistVisibility::Public));
+        assert!(!page
+            .gists
+            .iter()
+            .any(|g| g.public_id == gist_id || g.public_id == private));
+        let resp = get_request!(&app, &format!("{explore_path}?sort=updated&limit=1"));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: ExploreGists = test::read_body_json(resp).await;
+        assert!(page.gists.len() <= 1);
+        let resp = get_request!(&app, &format!("{explore_path}?after=foo"));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        /*
+         *
+         * ============================================
+         *                  Gist search
+         * ============================================
+         *
+         */
+        // gist was updated with a file containing "updated" in the update section
+        let search_path = format!("{}?q=updated&limit=100", V1_API_ROUTES.gist.search);
+        let resp = get_request!(&app, &search_path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let results: Vec<Gist> = test::read_body_json(resp).await;
+        assert!(results.iter().any(|g| g.public_id == gist_id));
+        // unlisted gist is only visible to its owner
+        let resp = get_request!(&app, &search_path, cookies2.clone());
+        let results: Vec<Gist> = test::read_body_json(resp).await;
+        assert!(!results.iter().any(|g| g.public_id == gist_id));
+        let resp = get_request!(&app, &search_path);
+        let results: Vec<Gist> = test::read_body_json(resp).await;
+        assert!(results
+            .iter()
+            .all(|g| g.visibility == GistVisibility::Public));
+
+        /*
+         *
+         * ============================================
+         *                  Gist fork
+         * ============================================
+         *
+         */
+        let mut fork_component = PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        };
+        let fork_path = V1_API_ROUTES.gist.get_fork_route(&fork_component);
+
+        // unauthenticated
+        let resp = test::call_service(&app, post_request!(&fork_path).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        // own gist
+        let resp = test::call_service(
+            &app,
+            post_request!(&fork_path)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // private gist
+        fork_component.gist = private.clone();
+        let private_fork_path = V1_API_ROUTES.gist.get_fork_route(&fork_component);
+        let resp = test::call_service(
+            &app,
+            post_request!(&private_fork_path)
+                .cookie(cookies2.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = get_request!(&app, &private_fork_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = test::call_service(
+            &app,
+            post_request!(&fork_path)
+                .cookie(cookies2.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let fork: CreateGistResp = test::read_body_json(resp).await;
+        let forked = db.get_gist(&fork.id).await.unwrap();
+        assert_eq!(forked.owner, NAME2);
+        assert_eq!(
+            db.get_fork_parent(&fork.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .public_id,
+            gist_id
+        );
+
+        // unlisted forks are only listed for their owner
+        let get_forks_path = V1_API_ROUTES.gist.get_get_forks_route(&PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        });
+        let resp = get_request!(&app, &get_forks_path);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let forks: Vec<db_core::Gist> = test::read_body_json(resp).await;
+        assert!(forks.is_empty());
+        let resp = get_request!(&app, &get_forks_path, cookies2.clone());
+        let forks: Vec<db_core::Gist> = test::read_body_json(resp).await;
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].public_id, fork.id);
+
+        /*
+         *
+         * ============================================
+         *                  Gist delete
+         * ============================================
+         *
+         */
+        let mut delete_component = PostCommentPath {
+            username: NAME.into(),
+            gist: gist_id.clone(),
+        };
+
+        // unauthenticated
+        let path = V1_API_ROUTES.gist.get_delete_route(&delete_component);
+        let resp = delete_request!(&app, &path);
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        // non-owner, unlisted gist
+        let resp = delete_request!(&app, &path, cookies2.clone());
+        assert_eq!(
+            resp.status(),
+            ServiceError::UnauthorizedOperation("".into()).status_code()
+        );
+
+        // non-owner, private gist
+        delete_component.gist = private.clone();
+        let private_path = V1_API_ROUTES.gist.get_delete_route(&delete_component);
+        let resp = delete_request!(&app, &private_path, cookies2.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::GistNotFound));
+        assert!(db.gist_exists(&private).await.unwrap());
+
+        // owner; gist, its comments and repository are deleted
+        let comments = db.get_comments_on_gist(&gist_id).await.unwrap();
+        let resp = delete_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!db.gist_exists(&gist_id).await.unwrap());
+        assert!(!data.get_repository_path(&gist_id).exists());
+        for comment in comments.iter() {
+            assert!(db.get_comment_by_id(comment.id).await.is_err());
+        }
+        let resp = get_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // gist is already deleted
+        let resp = delete_request!(&app, &path, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::GistNotFound));
+
+        // forks outlive the gist they were forked from
+        assert!(db.gist_exists(&fork.id).await.unwrap());
+        assert!(db.get_fork_parent(&fork.id).await.unwrap().is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_import_gist_works_postgres() {
+        let (db, data) = sqlx_postgres::get_data().await;
+        import_gist_test_runner(&data, &db).await;
+    }
+    #[actix_rt::test]
+    async fn test_import_gist_works_sqlite() {
+        let (db, data) = sqlx_sqlite::get_data().await;
+        import_gist_test_runner(&data, &db).await;
+    }
+
+    async fn import_gist_test_runner(data: &Arc<Data>, db: &BoxDB) {
+        const NAME: &str = "httpimporttestuser";
+        const EMAIL: &str = "httpimporttestuser@sss.com";
+        const PASSWORD: &str = "longpassword2";
+
+        let _ = data.delete_user(db, NAME, PASSWORD).await;
+        let (_creds, signin_resp) = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+        let cookies = get_cookie!(signin_resp);
+        let app = get_app!(data, db).await;
+
+        let export: GitHubExport = serde_json::from_value(serde_json::json!({
+            "description": "imported from github",
+            "public": true,
+            "created_at": "2022-03-10T09:35:22Z",
+            "updated_at": "2022-03-11T09:35:22Z",
+            "files": { "foo.md": { "content": "foo" } }
+        }))
+        .unwrap();
+        let mut msg = ImportGistRequest {
+            repository: None,
+            export: Some(export),
+            description: None,
+            visibility: None,
+        };
+
+        // unauthenticated
+        let resp = test::call_service(
+            &app,
+            post_request!(&msg, V1_API_ROUTES.gist.import).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let resp = test::call_service(
+            &app,
+            post_request!(&msg, V1_API_ROUTES.gist.import)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let gist: CreateGistResp = test::read_body_json(resp).await;
+        let info = db.get_gist(&gist.id).await.unwrap();
+        assert_eq!(info.owner, NAME);
+        assert_eq!(info.visibility, GistVisibility::Public);
+        assert_eq!(info.description.as_deref(), Some("imported from github"));
+        assert_eq!(info.created, 1646904922);
+
+        // repositories can't be imported when `repository.import_root` isn't set
+        msg.repository = Some("foo".into());
+        let resp = test::call_service(
+            &app,
+            post_request!(&msg, V1_API_ROUTES.gist.import)
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), ServiceError::ImportDisabled.status_code());
+        let err: ErrorToResponse = test::read_body_json(resp).await;
+        assert_eq!(err.error, format!("{}", ServiceError::ImportDisabled));
     }
 }
@@ -20,6 +20,7 @@ use serde::Deserialize;
 
 pub mod account;
 pub mod auth;
+pub mod bearer;
 pub mod gists;
 pub mod meta;
 pub mod routes;
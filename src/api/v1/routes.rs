@@ -19,6 +19,7 @@ use actix_auth_middleware::GetLoginRoute;
 use serde::*;
 
 use super::meta::routes::Meta;
+use crate::data::api::v1::archive::ArchiveFormat;
 
 /// constant [Routes](Routes) instance
 pub const ROUTES: Routes = Routes::new();
@@ -58,6 +59,13 @@ pub struct PostCommentPath {
     pub gist: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArchivePath {
+    pub username: String,
+    pub gist: String,
+    pub format: ArchiveFormat,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GetCommentPath {
     pub username: String,
@@ -71,6 +79,8 @@ pub struct Gist {
     pub new: &'static str,
     /// get flie route
     pub get_file: &'static str,
+    /// get contents of a file, as it is stored in the repository
+    pub raw: &'static str,
     /// post comment on gist
     pub post_comment: &'static str,
     /// get comment
@@ -81,6 +91,34 @@ pub struct Gist {
     pub delete_comment: &'static str,
     /// gist index page
     pub gist_index: &'static str,
+    /// update gist
+    pub update: &'static str,
+    /// delete gist
+    pub delete: &'static str,
+    /// gist revision history
+    pub revisions: &'static str,
+    /// diff between two revisions of a gist
+    pub diff: &'static str,
+    /// fork gist
+    pub fork: &'static str,
+    /// get forks of a gist
+    pub get_forks: &'static str,
+    /// star gist
+    pub star: &'static str,
+    /// remove star from gist
+    pub unstar: &'static str,
+    /// get star count of a gist and if the user has starred it
+    pub star_status: &'static str,
+    /// gists starred by a user
+    pub starred: &'static str,
+    /// public gists, across all users
+    pub explore: &'static str,
+    /// full-text search over gists
+    pub search: &'static str,
+    /// download gist as an archive
+    pub archive: &'static str,
+    /// import gist from a repository on the server or a GitHub gist export
+    pub import: &'static str,
 }
 
 impl Gist {
@@ -88,29 +126,78 @@ impl Gist {
     pub const fn new() -> Gist {
         let new = "/api/v1/gist/new";
         let gist_index = "/api/v1/gist/profile/{username}/{gist}";
-        let get_file = "/api/v1/gist/profile/{username}/{gist}/contents/{file}";
+        let get_file = "/api/v1/gist/profile/{username}/{gist}/contents/{file:.*}";
+        let raw = "/api/v1/gist/profile/{username}/{gist}/raw/{file:.*}";
         let post_comment = "/api/v1/gist/profile/{username}/{gist}/comments";
         let get_comment = "/api/v1/gist/profile/{username}/{gist}/comment/{comment_id}";
         let delete_comment = get_comment;
         let get_gist_comments = post_comment;
+        let revisions = "/api/v1/gist/profile/{username}/{gist}/revisions";
+        let diff = "/api/v1/gist/profile/{username}/{gist}/diff";
+        let fork = "/api/v1/gist/profile/{username}/{gist}/forks";
+        let get_forks = fork;
+        let star = "/api/v1/gist/profile/{username}/{gist}/star";
+        let unstar = star;
+        let star_status = star;
+        let starred = "/api/v1/gist/starred/{username}";
+        let explore = "/api/v1/gists/explore";
+        let search = "/api/v1/gists/search";
+        let archive = "/api/v1/gist/profile/{username}/{gist}/archive/{format}";
+        let import = "/api/v1/gist/import";
+        let update = gist_index;
+        let delete = gist_index;
         Gist {
             new,
             get_file,
+            raw,
             post_comment,
             get_comment,
             get_gist_comments,
             delete_comment,
             gist_index,
+            update,
+            delete,
+            revisions,
+            diff,
+            fork,
+            get_forks,
+            star,
+            unstar,
+            star_status,
+            starred,
+            explore,
+            search,
+            archive,
+            import,
         }
     }
 
     /// get file routes with placeholders replaced with values provided.
-    /// filename is auto-escaped using [urlencoding::encode]
+    /// each component of the file's path is auto-escaped using [urlencoding::encode]
     pub fn get_file_route(&self, components: &GetFilePath) -> String {
+        let file: Vec<_> = components
+            .file
+            .split('/')
+            .map(urlencoding::encode)
+            .collect();
         self.get_file
             .replace("{username}", &components.username)
             .replace("{gist}", &components.gist)
-            .replace("{file}", &urlencoding::encode(&components.file))
+            .replace("{file:.*}", &file.join("/"))
+    }
+
+    /// get raw file route with placeholders replaced with values provided.
+    /// each component of the file's path is auto-escaped using [urlencoding::encode]
+    pub fn get_raw_route(&self, components: &GetFilePath) -> String {
+        let file: Vec<_> = components
+            .file
+            .split('/')
+            .map(urlencoding::encode)
+            .collect();
+        self.raw
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+            .replace("{file:.*}", &file.join("/"))
     }
 
     /// get post_comment route with placeholders replaced with values provided.
@@ -127,6 +214,16 @@ impl Gist {
             .replace("{gist}", &components.gist)
     }
 
+    /// get update route with placeholders replaced with values provided.
+    pub fn get_update_route(&self, components: &PostCommentPath) -> String {
+        self.get_gist_index(components)
+    }
+
+    /// get delete route with placeholders replaced with values provided.
+    pub fn get_delete_route(&self, components: &PostCommentPath) -> String {
+        self.get_gist_index(components)
+    }
+
     /// get post_comment route with placeholders replaced with values provided.
     pub fn get_gist_comments(&self, components: &PostCommentPath) -> String {
         self.get_post_comment_route(components)
@@ -144,6 +241,62 @@ impl Gist {
     pub fn get_delete_comment_route(&self, components: &GetCommentPath) -> String {
         self.get_get_comment_route(components)
     }
+
+    /// get revisions route with placeholders replaced with values provided.
+    pub fn get_revisions_route(&self, components: &PostCommentPath) -> String {
+        self.revisions
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get diff route with placeholders replaced with values provided.
+    pub fn get_diff_route(&self, components: &PostCommentPath) -> String {
+        self.diff
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get archive route with placeholders replaced with values provided.
+    pub fn get_archive_route(&self, components: &PostCommentPath, format: ArchiveFormat) -> String {
+        self.archive
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+            .replace("{format}", format.extension())
+    }
+
+    /// get fork route with placeholders replaced with values provided.
+    pub fn get_fork_route(&self, components: &PostCommentPath) -> String {
+        self.fork
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get get_forks route with placeholders replaced with values provided.
+    pub fn get_get_forks_route(&self, components: &PostCommentPath) -> String {
+        self.get_fork_route(components)
+    }
+
+    /// get star route with placeholders replaced with values provided.
+    pub fn get_star_route(&self, components: &PostCommentPath) -> String {
+        self.star
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get unstar route with placeholders replaced with values provided.
+    pub fn get_unstar_route(&self, components: &PostCommentPath) -> String {
+        self.get_star_route(components)
+    }
+
+    /// get star_status route with placeholders replaced with values provided.
+    pub fn get_star_status_route(&self, components: &PostCommentPath) -> String {
+        self.get_star_route(components)
+    }
+
+    /// get starred route with placeholders replaced with values provided.
+    pub fn get_starred_route(&self, username: &str) -> String {
+        self.starred.replace("{username}", username)
+    }
 }
 
 /// Account management routes
@@ -164,6 +317,12 @@ pub struct Account {
     pub username_exists: &'static str,
     ///    route to change username
     pub update_username: &'static str,
+    /// route to list and create personal access tokens
+    pub tokens: &'static str,
+    /// route to revoke a personal access token
+    pub delete_token: &'static str,
+    /// route to download an archive of the account's gists and comments
+    pub export: &'static str,
 }
 
 impl Account {
@@ -177,6 +336,9 @@ impl Account {
         let update_username = "/api/v1/account/username/update";
         let update_email = "/api/v1/account/email/update";
         let update_password = "/api/v1/account/password/update";
+        let tokens = "/api/v1/account/tokens";
+        let delete_token = "/api/v1/account/tokens/{id}";
+        let export = "/api/v1/account/export";
         Account {
             delete,
             email_exists,
@@ -186,8 +348,16 @@ impl Account {
             update_secret,
             username_exists,
             update_username,
+            tokens,
+            delete_token,
+            export,
         }
     }
+
+    /// get delete_token route with placeholders replaced with values provided.
+    pub fn get_delete_token_route(&self, id: i64) -> String {
+        self.delete_token.replace("{id}", &id.to_string())
+    }
 }
 
 /// Top-level routes data structure for V1 AP1
@@ -243,6 +413,12 @@ mod tests {
         let get_gist_comments = format!("/api/v1/gist/profile/{NAME}/{GIST}/comments");
         let get_comment = format!("/api/v1/gist/profile/{NAME}/{GIST}/comment/{COMMENT_ID}");
         let delete_comment = format!("/api/v1/gist/profile/{NAME}/{GIST}/comment/{COMMENT_ID}");
+        let revisions = format!("/api/v1/gist/profile/{NAME}/{GIST}/revisions");
+        let diff = format!("/api/v1/gist/profile/{NAME}/{GIST}/diff");
+        let forks = format!("/api/v1/gist/profile/{NAME}/{GIST}/forks");
+        let star = format!("/api/v1/gist/profile/{NAME}/{GIST}/star");
+        let starred = format!("/api/v1/gist/starred/{NAME}");
+        let archive = format!("/api/v1/gist/profile/{NAME}/{GIST}/archive/tar.gz");
 
         let get_file_component = GetFilePath {
             file: FILE.into(),
@@ -250,6 +426,23 @@ mod tests {
             username: NAME.into(),
         };
         assert_eq!(get_file, ROUTES.gist.get_file_route(&get_file_component));
+        let nested_file_component = GetFilePath {
+            file: "src/foo bar.rs".into(),
+            gist: GIST.into(),
+            username: NAME.into(),
+        };
+        assert_eq!(
+            format!("/api/v1/gist/profile/{NAME}/{GIST}/contents/src/foo%20bar.rs"),
+            ROUTES.gist.get_file_route(&nested_file_component)
+        );
+        assert_eq!(
+            format!("/api/v1/gist/profile/{NAME}/{GIST}/raw/{FILE}"),
+            ROUTES.gist.get_raw_route(&get_file_component)
+        );
+        assert_eq!(
+            format!("/api/v1/gist/profile/{NAME}/{GIST}/raw/src/foo%20bar.rs"),
+            ROUTES.gist.get_raw_route(&nested_file_component)
+        );
 
         let post_comment_path = PostCommentPath {
             gist: GIST.into(),
@@ -290,5 +483,32 @@ mod tests {
         );
 
         assert_eq!(gist_index, ROUTES.gist.get_gist_index(&post_comment_path));
+        assert_eq!(gist_index, ROUTES.gist.get_update_route(&post_comment_path));
+        assert_eq!(gist_index, ROUTES.gist.get_delete_route(&post_comment_path));
+        assert_eq!(
+            revisions,
+            ROUTES.gist.get_revisions_route(&post_comment_path)
+        );
+        assert_eq!(diff, ROUTES.gist.get_diff_route(&post_comment_path));
+        assert_eq!(forks, ROUTES.gist.get_fork_route(&post_comment_path));
+        assert_eq!(forks, ROUTES.gist.get_get_forks_route(&post_comment_path));
+        assert_eq!(star, ROUTES.gist.get_star_route(&post_comment_path));
+        assert_eq!(star, ROUTES.gist.get_unstar_route(&post_comment_path));
+        assert_eq!(star, ROUTES.gist.get_star_status_route(&post_comment_path));
+        assert_eq!(starred, ROUTES.gist.get_starred_route(NAME));
+        assert_eq!(
+            archive,
+            ROUTES
+                .gist
+                .get_archive_route(&post_comment_path, ArchiveFormat::TarGz)
+        );
+    }
+
+    #[test]
+    fn account_route_substitution_works() {
+        assert_eq!(
+            "/api/v1/account/tokens/5",
+            ROUTES.account.get_delete_token_route(5)
+        );
     }
 }
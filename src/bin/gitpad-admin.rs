@@ -0,0 +1,40 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Instance management CLI, see [gitpad::cli]
+use std::env;
+
+use gitpad::errors::ErrorToResponse;
+use gitpad::*;
+
+#[cfg(not(tarpaulin_include))]
+#[actix_web::main]
+async fn main() {
+    let settings = Settings::new().unwrap();
+    pretty_env_logger::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match cli::run(&args, &settings).await {
+        Ok(output) => println!("{}", serde_json::to_string_pretty(&output).unwrap()),
+        Err(e) => {
+            let err = ErrorToResponse {
+                error: e.to_string(),
+            };
+            eprintln!("{}", serde_json::to_string_pretty(&err).unwrap());
+            std::process::exit(1);
+        }
+    }
+}
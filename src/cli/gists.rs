@@ -0,0 +1,115 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Gist management commands
+use std::path::Path;
+use std::str::FromStr;
+
+use db_core::prelude::*;
+use serde_json::{json, Value};
+
+use super::{get_data, to_json, Args};
+use crate::data::api::v1::import::{GitHubExport, ImportGist};
+use crate::errors::*;
+use crate::*;
+
+/// Run `gitpad-admin gist <subcommand>`
+pub async fn run(subcommand: &str, args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    match subcommand {
+        "list" => list(args, settings).await,
+        "delete" => delete(args, settings).await,
+        "visibility" => visibility(args, settings).await,
+        _ => Err(ServiceError::BadRequest(format!(
+            "unknown command gist {subcommand}"
+        ))),
+    }
+}
+
+/// List gists owned by a user, including private gists, most recently updated first
+async fn list(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["owner"])?;
+    args.positional(0)?;
+    let owner = args.required("owner")?;
+    let (data, db) = get_data(settings).await?;
+    if !db.username_exists(owner).await? {
+        return Err(ServiceError::AccountNotFound);
+    }
+    let gists = data.get_user_gists(&db, owner, Some(owner)).await?;
+    to_json(&gists)
+}
+
+/// Delete gist, its comments and its repository
+async fn delete(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["id"])?;
+    args.positional(0)?;
+    let id = args.required("id")?;
+    let (data, db) = get_data(settings).await?;
+    let gist = db.get_gist(id).await?;
+    data.delete_gist(&db, &gist.owner, &gist.public_id).await?;
+    Ok(json!({ "id": gist.public_id, "deleted": true }))
+}
+
+/// Change visibility of a gist, prints the updated gist
+async fn visibility(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["id", "visibility"])?;
+    args.positional(0)?;
+    let id = args.required("id")?;
+    let visibility = GistVisibility::from_str(args.required("visibility")?)?;
+    let (_data, db) = get_data(settings).await?;
+    let gist = db.get_gist(id).await?;
+    let msg = UpdateGist {
+        owner: &gist.owner,
+        public_id: &gist.public_id,
+        description: gist.description.as_deref(),
+        visibility: &visibility,
+    };
+    db.update_gist(&msg).await?;
+    to_json(&db.get_gist(id).await?)
+}
+
+/// Import a gist from a repository and/or a GitHub gist export
+pub async fn import(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["owner", "export", "description", "visibility"])?;
+    let owner = args.required("owner")?;
+    let repository = args.positional(1)?.first().map(Path::new);
+    let export = match args.get("export") {
+        Some(path) => Some(GitHubExport::from_file(Path::new(path))?),
+        None => None,
+    };
+    if repository.is_none() && export.is_none() {
+        return Err(ServiceError::BadRequest(
+            "a repository or --export is required".into(),
+        ));
+    }
+    let visibility = args
+        .get("visibility")
+        .map(GistVisibility::from_str)
+        .transpose()?;
+
+    let (data, db) = get_data(settings).await?;
+    if !db.username_exists(owner).await? {
+        return Err(ServiceError::AccountNotFound);
+    }
+    let msg = ImportGist {
+        owner,
+        repository,
+        export: export.as_ref(),
+        description: args.get("description"),
+        visibility: visibility.as_ref(),
+    };
+    let gist = data.import_gist(&db, &msg).await?;
+    Ok(json!({ "id": gist.id }))
+}
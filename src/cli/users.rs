@@ -0,0 +1,101 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! User management commands
+use serde_json::{json, Value};
+
+use super::{get_data, to_json, Args};
+use crate::data::api::v1::auth::Register;
+use crate::errors::*;
+use crate::*;
+
+/// Run `gitpad-admin user <subcommand>`
+pub async fn run(subcommand: &str, args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    match subcommand {
+        "create" => create(args, settings).await,
+        "delete" => delete(args, settings).await,
+        "reset-password" => reset_password(args, settings).await,
+        "rotate-secret" => rotate_secret(args, settings).await,
+        _ => Err(ServiceError::BadRequest(format!(
+            "unknown command user {subcommand}"
+        ))),
+    }
+}
+
+/// Create user, even when the instance is closed for registration
+async fn create(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["username", "email", "password"])?;
+    args.positional(0)?;
+    let password = args.password()?;
+    let msg = Register {
+        username: args.required("username")?.to_owned(),
+        confirm_password: password.clone(),
+        password,
+        email: args.get("email").map(|e| e.to_owned()),
+    };
+    let (data, db) = get_data(settings).await?;
+    data.create_user(&db, &msg).await?;
+    Ok(json!({ "username": msg.username, "email": msg.email }))
+}
+
+/// Delete user, along with their gists and comments
+async fn delete(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["username"])?;
+    args.positional(0)?;
+    let username = args.required("username")?;
+    let (data, db) = get_data(settings).await?;
+    data.admin_delete_user(&db, username).await?;
+    Ok(json!({ "username": username, "deleted": true }))
+}
+
+async fn reset_password(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["username", "password"])?;
+    args.positional(0)?;
+    let username = args.required("username")?;
+    let password = args.password()?;
+    let (data, db) = get_data(settings).await?;
+    data.admin_reset_password(&db, username, &password).await?;
+    Ok(json!({ "username": username }))
+}
+
+/// Rotate account secret, prints the new secret
+async fn rotate_secret(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["username"])?;
+    args.positional(0)?;
+    let username = args.required("username")?;
+    let (data, db) = get_data(settings).await?;
+    if !db.username_exists(username).await? {
+        return Err(ServiceError::AccountNotFound);
+    }
+    let secret = data.update_user_secret(&db, username).await?;
+    Ok(json!({ "username": username, "secret": secret }))
+}
+
+/// Export gists and comments of a user, see `docs/account-export.md`. The archive is
+/// written to the current directory unless an output file is specified.
+pub async fn export(args: &[String], settings: &Settings) -> ServiceResult<Value> {
+    let args = Args::parse(args, &["user", "output"])?;
+    args.positional(0)?;
+    let user = args.required("user")?;
+    let (data, db) = get_data(settings).await?;
+    let export = data.export_account(&db, user).await?;
+    let output = args
+        .get("output")
+        .unwrap_or(export.filename.as_str())
+        .to_owned();
+    tokio::fs::write(&output, &export.content).await?;
+    Ok(json!({ "path": output, "manifest": to_json(&export.manifest)? }))
+}
@@ -21,7 +21,6 @@ use tokio::fs;
 
 pub use super::auth;
 use super::get_random;
-use super::gists::GistID;
 use crate::db::BoxDB;
 use crate::errors::*;
 use crate::Data;
@@ -144,10 +143,21 @@ impl Data {
         password: &str,
     ) -> ServiceResult<()> {
         self.authenticate(db, username, password).await?;
+        self.admin_delete_user(db, username).await
+    }
+
+    /// Delete user, their gists and their repositories without verifying their password.
+    /// Used by instance administrators.
+    pub async fn admin_delete_user(&self, db: &BoxDB, username: &str) -> ServiceResult<()> {
+        if !db.username_exists(username).await? {
+            return Err(ServiceError::AccountNotFound);
+        }
         let gists = db.get_user_gists(username).await?;
         for gist in gists.iter() {
-            let path = self.get_gist_id_from_repo_path(&GistID::ID(&gist.public_id));
-            fs::remove_dir_all(&path).await?;
+            let path = self.get_repository_path(&gist.public_id);
+            if path.exists() {
+                fs::remove_dir_all(&path).await?;
+            }
         }
         db.delete_account(username).await?;
         Ok(())
@@ -177,4 +187,23 @@ impl Data {
 
         Ok(())
     }
+
+    /// Set password of a user without verifying their current password. Used by instance
+    /// administrators.
+    pub async fn admin_reset_password(
+        &self,
+        db: &BoxDB,
+        username: &str,
+        new_password: &str,
+    ) -> ServiceResult<()> {
+        if !db.username_exists(username).await? {
+            return Err(ServiceError::AccountNotFound);
+        }
+        let db_payload = Creds {
+            username: username.into(),
+            password: self.creds.password(new_password)?,
+        };
+        db.update_password(&db_payload).await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,336 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Gist archives
+use std::io::{Cursor, Write};
+
+use flate2::{write::GzEncoder, Compression};
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use super::gists::{resolve_revision, FileMode};
+use crate::errors::*;
+use crate::*;
+
+/// Archive formats that gists can be downloaded as
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    /// gzip compressed tarball
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    /// zip archive
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// file extension of archives of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+
+    /// media type of archives of this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::TarGz => "application/gzip",
+            Self::Zip => "application/zip",
+        }
+    }
+}
+
+/// Archive of the files in a gist, at a revision
+pub struct Archive {
+    /// name of the archive file, like `<gist>-<revision>.tar.gz`
+    pub filename: String,
+    /// ID of the commit that was archived
+    pub revision: String,
+    pub content: Vec<u8>,
+}
+
+/// File read from the tree that is being archived
+struct ArchiveEntry {
+    /// path relative to root of the archive
+    path: String,
+    mode: FileMode,
+    content: Vec<u8>,
+}
+
+impl ArchiveEntry {
+    /// unix permissions of the file
+    fn permissions(&self) -> u32 {
+        match self.mode {
+            FileMode::Regular => 0o644,
+            FileMode::Executable => 0o755,
+            FileMode::Symlink => 0o777,
+        }
+    }
+
+    /// target of a symlink, symlinks store their targets as contents
+    fn link_target(&self) -> String {
+        String::from_utf8_lossy(&self.content).to_string()
+    }
+}
+
+/// Read all files in `tree`. Paths of files are prefixed with `prefix`.
+fn read_entries(
+    repo: &Repository,
+    tree: &git2::Tree,
+    prefix: &str,
+) -> ServiceResult<Vec<ArchiveEntry>> {
+    let mut entries = Vec::default();
+    let mut res = Ok(());
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(ObjectType::Tree) {
+            return TreeWalkResult::Ok;
+        }
+        let read = || -> ServiceResult<ArchiveEntry> {
+            let mode = FileMode::try_from(entry)?;
+            let blob = repo.find_blob(entry.id())?;
+            let name = entry.name().ok_or(ServiceError::FileNotFound)?;
+            Ok(ArchiveEntry {
+                path: format!("{prefix}/{dir}{name}"),
+                mode,
+                content: blob.content().to_vec(),
+            })
+        };
+        match read() {
+            Ok(archive_entry) => {
+                entries.push(archive_entry);
+                TreeWalkResult::Ok
+            }
+            Err(e) => {
+                res = Err(e);
+                TreeWalkResult::Abort
+            }
+        }
+    })?;
+    res?;
+    Ok(entries)
+}
+
+fn write_tar_gz(entries: &[ArchiveEntry], mtime: i64) -> ServiceResult<Vec<u8>> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for entry in entries.iter() {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(entry.permissions());
+        header.set_mtime(mtime.max(0) as u64);
+        if entry.mode == FileMode::Symlink {
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, &entry.path, entry.link_target())?;
+        } else {
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(entry.content.len() as u64);
+            builder.append_data(&mut header, &entry.path, entry.content.as_slice())?;
+        }
+    }
+    Ok(builder.into_inner()?.finish()?)
+}
+
+fn write_zip(entries: &[ArchiveEntry], mtime: i64) -> ServiceResult<Vec<u8>> {
+    let mut options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    if let Some(mtime) = OffsetDateTime::from_unix_timestamp(mtime)
+        .ok()
+        .and_then(|t| zip::DateTime::try_from(t).ok())
+    {
+        options = options.last_modified_time(mtime);
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for entry in entries.iter() {
+        let options = options.unix_permissions(entry.permissions());
+        if entry.mode == FileMode::Symlink {
+            zip.add_symlink(&entry.path, entry.link_target(), options)?;
+        } else {
+            zip.start_file(&entry.path, options)?;
+            zip.write_all(&entry.content)?;
+        }
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+impl Data {
+    /// Archive files in a gist at revision `rev`(HEAD when `rev` is `None`). Files are
+    /// placed in a directory named after the gist and the revision.
+    pub fn gist_archive(
+        &self,
+        public_id: &str,
+        rev: Option<&str>,
+        format: ArchiveFormat,
+    ) -> ServiceResult<Archive> {
+        let repo = Repository::open(self.get_repository_path(public_id))?;
+        let commit = resolve_revision(&repo, rev)?;
+        let revision = commit.id().to_string();
+        let name = format!("{public_id}-{}", &revision[..8]);
+
+        let entries = read_entries(&repo, &commit.tree()?, &name)?;
+        let mtime = commit.time().seconds();
+        let content = match format {
+            ArchiveFormat::TarGz => write_tar_gz(&entries, mtime)?,
+            ArchiveFormat::Zip => write_zip(&entries, mtime)?,
+        };
+        Ok(Archive {
+            filename: format!("{name}.{}", format.extension()),
+            revision,
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use db_core::prelude::*;
+
+    use super::*;
+    use crate::data::api::v1::gists::{ContentType, CreateGist, FileInfo, FileType, GistID};
+    use crate::tests::*;
+
+    #[actix_rt::test]
+    async fn test_gist_archive_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "archivetestuser";
+            const EMAIL: &str = "archivetestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let msg = CreateGist {
+                owner: NAME,
+                description: None,
+                visibility: &GistVisibility::Public,
+            };
+            let mut gist = data.new_gist(db, &msg).await.unwrap();
+            assert_eq!(
+                data.gist_archive(&gist.id, None, ArchiveFormat::Zip).err(),
+                Some(ServiceError::RevisionNotFound)
+            );
+
+            let files = [
+                FileInfo {
+                    filename: "README.md".into(),
+                    content: FileType::File(ContentType::Text("# foo".into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "bin/run.sh".into(),
+                    content: FileType::File(ContentType::Text("echo foo".into())),
+                    mode: FileMode::Executable,
+                },
+                FileInfo {
+                    filename: "link.md".into(),
+                    content: FileType::File(ContentType::Text("README.md".into())),
+                    mode: FileMode::Symlink,
+                },
+            ];
+            data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                .await
+                .unwrap();
+            let second = [FileInfo {
+                filename: "second.md".into(),
+                content: FileType::File(ContentType::Text("second".into())),
+                mode: FileMode::Regular,
+            }];
+            data.write_file(db, &mut GistID::ID(&gist.id), &second)
+                .await
+                .unwrap();
+
+            // tar.gz
+            let archive = data
+                .gist_archive(&gist.id, None, ArchiveFormat::TarGz)
+                .unwrap();
+            let prefix = format!("{}-{}", gist.id, &archive.revision[..8]);
+            assert_eq!(archive.filename, format!("{prefix}.tar.gz"));
+            let mut tarball =
+                tar::Archive::new(flate2::read::GzDecoder::new(archive.content.as_slice()));
+            let mut paths = Vec::default();
+            for entry in tarball.entries().unwrap() {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().to_string();
+                let mode = entry.header().mode().unwrap();
+                if path.ends_with("run.sh") {
+                    assert_eq!(mode, 0o755);
+                    let mut content = String::default();
+                    entry.read_to_string(&mut content).unwrap();
+                    assert_eq!(content, "echo foo");
+                } else if path.ends_with("link.md") {
+                    assert_eq!(entry.header().entry_type(), tar::EntryType::Symlink);
+                    assert_eq!(
+                        entry.link_name().unwrap().unwrap().to_str(),
+                        Some("README.md")
+                    );
+                } else {
+                    assert_eq!(mode, 0o644);
+                }
+                paths.push(path);
+            }
+            paths.sort();
+            let expected: Vec<String> = ["README.md", "bin/run.sh", "link.md", "second.md"]
+                .iter()
+                .map(|p| format!("{prefix}/{p}"))
+                .collect();
+            assert_eq!(paths, expected);
+
+            // zip
+            let archive = data
+                .gist_archive(&gist.id, None, ArchiveFormat::Zip)
+                .unwrap();
+            assert_eq!(archive.filename, format!("{prefix}.zip"));
+            let mut zip = zip::ZipArchive::new(Cursor::new(archive.content)).unwrap();
+            assert_eq!(zip.len(), 4);
+            let mut script = zip.by_name(&format!("{prefix}/bin/run.sh")).unwrap();
+            assert_eq!(script.unix_mode().unwrap() & 0o777, 0o755);
+            let mut content = String::default();
+            script.read_to_string(&mut content).unwrap();
+            assert_eq!(content, "echo foo");
+            drop(script);
+            let link = zip.by_name(&format!("{prefix}/link.md")).unwrap();
+            assert_eq!(link.unix_mode().unwrap() & 0o170000, 0o120000);
+            drop(link);
+
+            // older revision
+            let revisions = data
+                .gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                .await
+                .unwrap();
+            let first = &revisions.last().unwrap().id;
+            let archive = data
+                .gist_archive(&gist.id, Some(first), ArchiveFormat::Zip)
+                .unwrap();
+            assert_eq!(&archive.revision, first);
+            let zip = zip::ZipArchive::new(Cursor::new(archive.content)).unwrap();
+            assert_eq!(zip.len(), 3);
+            assert!(!zip.file_names().any(|f| f.ends_with("second.md")));
+            assert_eq!(
+                data.gist_archive(&gist.id, Some("zzzzzzzz"), ArchiveFormat::Zip)
+                    .err(),
+                Some(ServiceError::RevisionNotFound)
+            );
+        }
+    }
+}
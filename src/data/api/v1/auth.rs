@@ -84,7 +84,12 @@ impl Data {
         if !self.settings.allow_registration {
             return Err(ServiceError::ClosedForRegistration);
         }
+        self.create_user(db, payload).await
+    }
 
+    /// Create new user, even when the instance is closed for registration. Used by
+    /// instance administrators.
+    pub async fn create_user<T: GPDatabse>(&self, db: &T, payload: &Register) -> ServiceResult<()> {
         if payload.password != payload.confirm_password {
             return Err(ServiceError::PasswordsDontMatch);
         }
@@ -0,0 +1,360 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Instance backup and restore
+//!
+//! See `docs/backup.md` for the layout of the archive.
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use db_core::prelude::*;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use git2::{BranchType, Repository};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::process::Command;
+
+use super::export::bundle_repository;
+use crate::errors::*;
+use crate::*;
+
+/// Version of the backup format, bumped on incompatible changes
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Name of the manifest file in the archive, always the first entry
+pub const BACKUP_MANIFEST: &str = "backup.json";
+
+/// Directory in the archive that repository bundles are stored in
+pub const BACKUP_REPOSITORIES: &str = "repositories";
+
+/// Directory under `repository.root` that bundles are unpacked to while restoring
+const RESTORE_DIR: &str = ".restore";
+
+/// Describes the contents of a backup
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// see [BACKUP_VERSION]
+    pub version: u32,
+    /// time at which the backup was created, UNIX timestamp
+    pub created: i64,
+    pub snapshot: Snapshot,
+}
+
+/// Outcome of a backup or a restore
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupSummary {
+    /// time at which the backup was created, UNIX timestamp
+    pub created: i64,
+    /// number of rows in the database snapshot
+    pub rows: SnapshotCounts,
+    /// number of repository bundles in the archive
+    pub repositories: usize,
+    /// gists whose repositories were missing or couldn't be read during backup. They are
+    /// restored with empty repositories. Always empty for restores.
+    pub skipped: Vec<String>,
+}
+
+/// Fetch all refs from the bundle at `bundle` into the bare repository at `path`
+async fn unbundle_repository(path: &Path, bundle: &Path) -> ServiceResult<()> {
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(path)
+        .arg("fetch")
+        .arg("--quiet")
+        .arg(bundle)
+        .arg("+refs/*:refs/*")
+        .output()
+        .await?;
+    if !output.status.success() {
+        log::error!(
+            "unable to unbundle {}: {}",
+            bundle.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(ServiceError::InternalServerError);
+    }
+
+    // bundles don't record which branch HEAD points to
+    let repo = Repository::open(path)?;
+    if repo.head().is_err() {
+        if let Some(branch) = repo.branches(Some(BranchType::Local))?.next() {
+            let (branch, _) = branch?;
+            if let Some(name) = branch.get().name() {
+                repo.set_head(name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Data {
+    /// Write a backup of the instance to `writer`, as a gzip compressed tarball. The
+    /// database snapshot is consistent, repositories are bundled after the snapshot is
+    /// taken and may contain commits made while the backup was running.
+    pub async fn backup<T: GPDatabse, W: Write>(
+        &self,
+        db: &T,
+        writer: W,
+    ) -> ServiceResult<BackupSummary> {
+        let created = OffsetDateTime::now_utc().unix_timestamp();
+        let manifest = BackupManifest {
+            version: BACKUP_VERSION,
+            created,
+            snapshot: db.snapshot().await?,
+        };
+
+        let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+        let mut append = |path: &str, content: &[u8]| -> ServiceResult<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_mtime(created.max(0) as u64);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(content.len() as u64);
+            builder.append_data(&mut header, path, content)?;
+            Ok(())
+        };
+        append(BACKUP_MANIFEST, &serde_json::to_vec(&manifest).unwrap())?;
+
+        let mut repositories = 0;
+        let mut skipped = Vec::default();
+        for gist in manifest.snapshot.gists.iter() {
+            let path = self.get_repository_path(&gist.public_id);
+            if !path.exists() {
+                log::warn!("repository of gist {} is missing", gist.public_id);
+                skipped.push(gist.public_id.clone());
+                continue;
+            }
+            match bundle_repository(&path).await {
+                Ok(Some(bundle)) => {
+                    append(
+                        &format!("{BACKUP_REPOSITORIES}/{}.bundle", gist.public_id),
+                        &bundle,
+                    )?;
+                    repositories += 1;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    log::warn!("unable to back up gist {}: {e}", gist.public_id);
+                    skipped.push(gist.public_id.clone());
+                }
+            }
+        }
+        builder.into_inner()?.finish()?.flush()?;
+
+        Ok(BackupSummary {
+            created,
+            rows: manifest.snapshot.counts(),
+            repositories,
+            skipped,
+        })
+    }
+
+    /// Restore backup written by [Data::backup] from `reader`. The database and
+    /// `repository.root` must not contain any gists.
+    pub async fn restore<T: GPDatabse, R: Read>(
+        &self,
+        db: &T,
+        reader: R,
+    ) -> ServiceResult<BackupSummary> {
+        fn invalid(msg: String) -> ServiceError {
+            ServiceError::BadRequest(format!("invalid backup: {msg}"))
+        }
+
+        let mut archive = tar::Archive::new(GzDecoder::new(reader));
+        let mut entries = archive.entries()?;
+        let manifest: BackupManifest = match entries.next() {
+            Some(entry) => {
+                let entry = entry?;
+                if &*entry.path()? != Path::new(BACKUP_MANIFEST) {
+                    return Err(invalid(format!(
+                        "{BACKUP_MANIFEST} must be the first entry"
+                    )));
+                }
+                serde_json::from_reader(entry).map_err(|e| invalid(e.to_string()))?
+            }
+            None => return Err(invalid("archive is empty".into())),
+        };
+        if manifest.version != BACKUP_VERSION {
+            return Err(invalid(format!("unsupported version {}", manifest.version)));
+        }
+        let gists = &manifest.snapshot.gists;
+        if gists
+            .iter()
+            .any(|g| self.get_repository_path(&g.public_id).exists())
+        {
+            return Err(ServiceError::InstanceNotEmpty);
+        }
+
+        db.restore(&manifest.snapshot).await?;
+
+        let staging = self.get_repository_path(RESTORE_DIR);
+        fs::create_dir_all(&staging)?;
+        let mut repositories = 0;
+        for entry in entries {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let public_id = path
+                .strip_prefix(BACKUP_REPOSITORIES)
+                .ok()
+                .and_then(|p| p.to_str())
+                .and_then(|p| p.strip_suffix(".bundle"))
+                .filter(|id| gists.iter().any(|g| g.public_id == *id))
+                .ok_or_else(|| invalid(format!("unexpected entry {}", path.display())))?;
+
+            let bundle = staging.join(path.file_name().unwrap());
+            entry.unpack(&bundle)?;
+            let repo_path = self.get_repository_path(public_id);
+            Repository::init_bare(&repo_path)?;
+            let res = unbundle_repository(&repo_path, &bundle).await;
+            fs::remove_file(&bundle)?;
+            res?;
+            repositories += 1;
+        }
+        fs::remove_dir_all(&staging)?;
+
+        // empty repositories aren't bundled
+        for gist in gists.iter() {
+            let path = self.get_repository_path(&gist.public_id);
+            if !path.exists() {
+                Repository::init_bare(&path)?;
+            }
+        }
+        self.reindex_gists(db).await?;
+
+        Ok(BackupSummary {
+            created: manifest.created,
+            rows: manifest.snapshot.counts(),
+            repositories,
+            skipped: Vec::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::data::api::v1::gists::{
+        ContentType, CreateGist, FileInfo, FileMode, FileType, GistID,
+    };
+    use crate::db::sqlite;
+    use crate::tests::*;
+    use crate::utils::get_random;
+
+    /// Get empty instance, backed by an in-memory database
+    async fn get_empty_instance() -> (BoxDB, Arc<Data>) {
+        let mut settings = Settings::new().unwrap();
+        settings.database.url = "sqlite::memory:".into();
+        settings.database.pool = 1;
+        settings.repository.root = env::temp_dir()
+            .join(format!("gitpad-restore-{}", get_random(10)))
+            .to_string_lossy()
+            .to_string();
+        let db = sqlite::get_data(Some(settings.clone())).await;
+        (db, Data::new(Some(settings)))
+    }
+
+    #[actix_rt::test]
+    async fn test_backup_and_restore_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "backuptestuser";
+            const EMAIL: &str = "backuptestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let msg = CreateGist {
+                owner: NAME,
+                description: Some("back me up"),
+                visibility: &GistVisibility::Private,
+            };
+            let mut gist = data.new_gist(db, &msg).await.unwrap();
+            let content = format!("backup {}", get_random(10));
+            let files = [FileInfo {
+                filename: "foo.md".into(),
+                content: FileType::File(ContentType::Text(content.clone())),
+                mode: FileMode::Regular,
+            }];
+            data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                .await
+                .unwrap();
+            let empty = data.new_gist(db, &msg).await.unwrap();
+
+            let mut archive = Vec::default();
+            let backup = data.backup(db, &mut archive).await.unwrap();
+            assert!(backup.rows.users >= 1);
+            assert!(backup.rows.gists >= 2);
+            assert!(!backup.skipped.contains(&gist.id));
+
+            let (restored_db, restored) = get_empty_instance().await;
+            let summary = restored
+                .restore(&restored_db, archive.as_slice())
+                .await
+                .unwrap();
+            assert_eq!(summary.created, backup.created);
+            assert_eq!(summary.rows, backup.rows);
+            assert_eq!(summary.repositories, backup.repositories);
+
+            let original = db.get_gist(&gist.id).await.unwrap();
+            let restored_gist = restored_db.get_gist(&gist.id).await.unwrap();
+            assert_eq!(restored_gist.owner, original.owner);
+            assert_eq!(restored_gist.description, original.description);
+            assert_eq!(restored_gist.visibility, original.visibility);
+            assert_eq!(restored_gist.created, original.created);
+            assert_eq!(restored_gist.updated, original.updated);
+            let file = restored
+                .read_file(&restored_db, &GistID::ID(&gist.id), "foo.md", None)
+                .await
+                .unwrap();
+            assert_eq!(file.content, files[0].content);
+            assert!(Repository::open(restored.get_repository_path(&empty.id))
+                .unwrap()
+                .is_empty()
+                .unwrap());
+            let found = restored
+                .search_gists(&restored_db, &content, Some(NAME), 0, 10)
+                .await
+                .unwrap();
+            assert!(found.iter().any(|g| g.public_id == gist.id));
+
+            // backups can only be restored on empty instances
+            assert_eq!(
+                restored
+                    .restore(&restored_db, archive.as_slice())
+                    .await
+                    .err(),
+                Some(ServiceError::InstanceNotEmpty)
+            );
+            fs::remove_dir_all(&restored.settings.repository.root).unwrap();
+
+            assert!(matches!(
+                restored
+                    .restore(&restored_db, b"foo".as_slice())
+                    .await
+                    .err(),
+                Some(ServiceError::FSError(_))
+            ));
+        }
+    }
+}
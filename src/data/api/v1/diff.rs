@@ -0,0 +1,380 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Unified diffs between gist revisions
+use db_core::prelude::*;
+use git2::{DiffFindOptions, DiffOptions, Patch, Repository};
+use serde::{Deserialize, Serialize};
+
+use super::gists::{resolve_revision, GistID};
+use super::render_html::{GenerateHTML, SourcegraphQuery};
+use super::revisions::{delta_filenames, ChangeType};
+use crate::errors::*;
+use crate::*;
+
+/// Kind of line in a diff hunk
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineType {
+    /// line is present in both revisions
+    Context,
+    /// line was added
+    Addition,
+    /// line was deleted
+    Deletion,
+}
+
+/// A single line in a diff hunk
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub kind: LineType,
+    /// line number in the old revision, unset for additions
+    pub old_lineno: Option<u32>,
+    /// line number in the new revision, unset for deletions
+    pub new_lineno: Option<u32>,
+    pub content: String,
+}
+
+/// A contiguous block of changes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiffHunk {
+    /// hunk header: `@@ -old_start,old_lines +new_start,new_lines @@`
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Changes made to a file between two revisions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    pub filename: String,
+    /// previous name of the file, set only when the file was renamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_filename: Option<String>,
+    pub change: ChangeType,
+    /// hunks aren't computed for binary files
+    pub binary: bool,
+    pub additions: usize,
+    pub deletions: usize,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Diff between two revisions of a gist
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GistDiff {
+    /// commit ID of the old revision. Unset when the new revision is the first
+    /// commit of the gist
+    pub from: Option<String>,
+    /// commit ID of the new revision
+    pub to: String,
+    pub files: Vec<FileDiff>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl GenerateHTML for FileDiff {
+    /// Syntax highlight content of all lines. Both sides of a hunk are
+    /// highlighted separately so that multi-line constructs are highlighted
+    /// correctly.
+    fn generate(&mut self) {
+        if self.binary {
+            return;
+        }
+
+        let filepath = self.filename.clone();
+        for hunk in self.hunks.iter_mut() {
+            let side = |skip: LineType| -> String {
+                hunk.lines
+                    .iter()
+                    .filter(|l| l.kind != skip)
+                    .map(|l| l.content.as_str())
+                    .collect()
+            };
+            let old = side(LineType::Addition);
+            let new = side(LineType::Deletion);
+
+            let mut old = SourcegraphQuery {
+                filepath: &filepath,
+                code: &old,
+            }
+            .highlight_lines()
+            .into_iter();
+            let mut new = SourcegraphQuery {
+                filepath: &filepath,
+                code: &new,
+            }
+            .highlight_lines()
+            .into_iter();
+
+            for line in hunk.lines.iter_mut() {
+                let html = match line.kind {
+                    LineType::Context => {
+                        old.next();
+                        new.next()
+                    }
+                    LineType::Addition => new.next(),
+                    LineType::Deletion => old.next(),
+                };
+                line.content = html.unwrap_or_default();
+            }
+        }
+    }
+}
+
+impl FileDiff {
+    fn from_patch(patch: &Patch) -> ServiceResult<Self> {
+        let delta = patch.delta();
+        let (filename, old_filename) = delta_filenames(&delta);
+        let binary = delta.flags().is_binary();
+
+        let mut hunks = Vec::with_capacity(patch.num_hunks());
+        let mut additions = 0;
+        let mut deletions = 0;
+        if !binary {
+            for hunk_idx in 0..patch.num_hunks() {
+                let (hunk, num_lines) = patch.hunk(hunk_idx)?;
+                let mut lines = Vec::with_capacity(num_lines);
+                for line_idx in 0..num_lines {
+                    let line = patch.line_in_hunk(hunk_idx, line_idx)?;
+                    let kind = match line.origin() {
+                        ' ' => LineType::Context,
+                        '+' => {
+                            additions += 1;
+                            LineType::Addition
+                        }
+                        '-' => {
+                            deletions += 1;
+                            LineType::Deletion
+                        }
+                        // "\ No newline at end of file" markers
+                        _ => continue,
+                    };
+                    lines.push(DiffLine {
+                        kind,
+                        old_lineno: line.old_lineno(),
+                        new_lineno: line.new_lineno(),
+                        content: String::from_utf8_lossy(line.content()).to_string(),
+                    });
+                }
+
+                hunks.push(DiffHunk {
+                    header: String::from_utf8_lossy(hunk.header()).trim().to_string(),
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    lines,
+                });
+            }
+        }
+
+        Ok(Self {
+            filename,
+            old_filename,
+            change: delta.status().into(),
+            binary,
+            additions,
+            deletions,
+            hunks,
+        })
+    }
+}
+
+impl Data {
+    /// Compute diff between revisions `from` and `to` of a gist. `to` defaults
+    /// to HEAD and `from` defaults to the parent of `to`. See [resolve_revision].
+    pub async fn gist_diff<T: GPDatabse>(
+        &self,
+        _db: &T,
+        gist_id: &GistID<'_>,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> ServiceResult<GistDiff> {
+        let inner = |repo: &Repository| -> ServiceResult<GistDiff> {
+            let to = resolve_revision(repo, to)?;
+            let from = match from {
+                Some(from) => Some(resolve_revision(repo, Some(from))?),
+                None => to.parents().next(),
+            };
+
+            let from_tree = match &from {
+                Some(from) => Some(from.tree()?),
+                None => None,
+            };
+            let mut opts = DiffOptions::new();
+            opts.context_lines(3);
+            let mut diff =
+                repo.diff_tree_to_tree(from_tree.as_ref(), Some(&to.tree()?), Some(&mut opts))?;
+            diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+            let mut files = Vec::with_capacity(diff.deltas().len());
+            for idx in 0..diff.deltas().len() {
+                if let Some(patch) = Patch::from_diff(&diff, idx)? {
+                    files.push(FileDiff::from_patch(&patch)?);
+                }
+            }
+
+            Ok(GistDiff {
+                from: from.map(|c| c.id().to_string()),
+                to: to.id().to_string(),
+                additions: files.iter().map(|f| f.additions).sum(),
+                deletions: files.iter().map(|f| f.deletions).sum(),
+                files,
+            })
+        };
+
+        match gist_id {
+            GistID::ID(path) => {
+                let repo = git2::Repository::open(self.get_repository_path(path))?;
+                inner(&repo)
+            }
+            GistID::Repository(repository) => inner(repository),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::api::v1::gists::{ContentType, CreateGist, FileInfo, FileMode, FileType};
+    use crate::tests::*;
+
+    #[actix_rt::test]
+    async fn test_gist_diff_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "difftestuser";
+            const EMAIL: &str = "difftestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let create_gist_msg = CreateGist {
+                owner: NAME,
+                description: None,
+                visibility: &GistVisibility::Public,
+            };
+            let gist = data.new_gist(db, &create_gist_msg).await.unwrap();
+
+            let files = [FileInfo {
+                filename: "foo.rs".into(),
+                content: FileType::File(ContentType::Text("fn a() {}\nfn b() {}\n".into())),
+                mode: FileMode::Regular,
+            }];
+            data.write_file(db, &mut GistID::ID(&gist.id), &files)
+                .await
+                .unwrap();
+            let files = [
+                FileInfo {
+                    filename: "foo.rs".into(),
+                    content: FileType::File(ContentType::Text("fn a() {}\nfn c() {}\n".into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "bar".into(),
+                    content: FileType::File(ContentType::Binary(vec![0, 1, 2, 0])),
+                    mode: FileMode::Regular,
+                },
+            ];
+            data.write_file(db, &mut GistID::ID(&gist.id), &files)
+                .await
+                .unwrap();
+
+            let revisions = data
+                .gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                .await
+                .unwrap();
+
+            // first commit is diffed against an empty tree
+            let diff = data
+                .gist_diff(db, &GistID::ID(&gist.id), None, Some(&revisions[1].id))
+                .await
+                .unwrap();
+            assert!(diff.from.is_none());
+            assert_eq!(diff.files.len(), 1);
+            assert_eq!(diff.files[0].change, ChangeType::Added);
+            assert_eq!(diff.additions, 2);
+
+            // HEAD is diffed against its parent
+            let diff = data
+                .gist_diff(db, &GistID::ID(&gist.id), None, None)
+                .await
+                .unwrap();
+            assert_eq!(diff.from.as_ref(), Some(&revisions[1].id));
+            assert_eq!(diff.to, revisions[0].id);
+            assert_eq!(diff.files.len(), 2);
+            let bin = diff.files.iter().find(|f| f.filename == "bar").unwrap();
+            assert!(bin.binary);
+            assert!(bin.hunks.is_empty());
+
+            let mut foo = diff
+                .files
+                .iter()
+                .find(|f| f.filename == "foo.rs")
+                .unwrap()
+                .clone();
+            assert_eq!(foo.change, ChangeType::Modified);
+            assert_eq!((foo.additions, foo.deletions), (1, 1));
+            assert_eq!(foo.hunks.len(), 1);
+            let lines = &foo.hunks[0].lines;
+            assert_eq!(lines.len(), 3);
+            assert_eq!(lines[0].kind, LineType::Context);
+            assert_eq!(
+                (lines[0].old_lineno, lines[0].new_lineno),
+                (Some(1), Some(1))
+            );
+            assert_eq!(lines[1].kind, LineType::Deletion);
+            assert_eq!(lines[1].content, "fn b() {}\n");
+            assert_eq!((lines[1].old_lineno, lines[1].new_lineno), (Some(2), None));
+            assert_eq!(lines[2].kind, LineType::Addition);
+            assert_eq!((lines[2].old_lineno, lines[2].new_lineno), (None, Some(2)));
+
+            foo.generate();
+            assert_eq!(foo.hunks[0].lines.len(), 3);
+            assert!(foo.hunks[0].lines[1].content.contains("<span"));
+
+            // reverse diff
+            let diff = data
+                .gist_diff(
+                    db,
+                    &GistID::ID(&gist.id),
+                    Some(&revisions[0].id),
+                    Some(&revisions[1].id[..8]),
+                )
+                .await
+                .unwrap();
+            assert!(diff
+                .files
+                .iter()
+                .any(|f| f.filename == "bar" && f.change == ChangeType::Deleted));
+
+            assert_eq!(
+                data.gist_diff(db, &GistID::ID(&gist.id), Some("zzzz"), None)
+                    .await
+                    .err(),
+                Some(ServiceError::RevisionNotFound)
+            );
+        }
+    }
+}
@@ -0,0 +1,176 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Explore public gists
+use db_core::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::*;
+
+/// Default number of gists returned per page
+pub const DEFAULT_EXPLORE_LIMIT: usize = 20;
+/// Maximum number of gists that can be requested per page
+pub const MAX_EXPLORE_LIMIT: usize = 100;
+
+/// A page of public gists
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExploreGists {
+    /// gists on this page
+    pub gists: Vec<Gist>,
+    /// cursor to fetch the next page with, not set on the last page
+    pub next: Option<String>,
+}
+
+/// Encode position of `gist` in a list sorted by `sort` as a cursor
+pub fn encode_cursor(gist: &Gist, sort: GistSort) -> String {
+    let timestamp = match sort {
+        GistSort::Created => gist.created,
+        GistSort::Updated => gist.updated,
+    };
+    format!("{timestamp}.{}", gist.public_id)
+}
+
+/// Decode cursor encoded with [encode_cursor]
+pub fn decode_cursor(cursor: &str) -> ServiceResult<GistCursor<'_>> {
+    let err = || ServiceError::BadRequest("Invalid cursor".into());
+    let (timestamp, public_id) = cursor.split_once('.').ok_or_else(err)?;
+    if public_id.is_empty() {
+        return Err(err());
+    }
+    let timestamp = timestamp.parse().map_err(|_| err())?;
+    Ok(GistCursor {
+        timestamp,
+        public_id,
+    })
+}
+
+impl Data {
+    /// Get a page of public gists, most recent first. `after` is the cursor returned along
+    /// with the previous page.
+    pub async fn explore_gists<T: GPDatabse>(
+        &self,
+        db: &T,
+        sort: GistSort,
+        after: Option<&str>,
+        limit: usize,
+    ) -> ServiceResult<ExploreGists> {
+        let after = match after {
+            Some(cursor) => Some(decode_cursor(cursor)?),
+            None => None,
+        };
+        // fetch an extra gist to find out if there's a next page
+        let mut gists = db
+            .get_public_gists(sort, after.as_ref(), limit as u32 + 1)
+            .await?;
+        let next = if gists.len() > limit {
+            gists.truncate(limit);
+            gists.last().map(|g| encode_cursor(g, sort))
+        } else {
+            None
+        };
+        Ok(ExploreGists { gists, next })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::api::v1::gists::CreateGist;
+    use crate::tests::*;
+
+    #[test]
+    fn cursor_works() {
+        let gist = Gist {
+            owner: "foo".into(),
+            description: None,
+            public_id: "abc".into(),
+            created: 10,
+            updated: 20,
+            visibility: GistVisibility::Public,
+        };
+        let cursor = encode_cursor(&gist, GistSort::Updated);
+        assert_eq!(cursor, "20.abc");
+        assert_eq!(
+            decode_cursor(&cursor).unwrap(),
+            GistCursor {
+                timestamp: 20,
+                public_id: "abc"
+            }
+        );
+        assert_eq!(encode_cursor(&gist, GistSort::Created), "10.abc");
+        for invalid in ["", "10", "10.", "foo.abc"] {
+            assert!(decode_cursor(invalid).is_err());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_explore_gists_work() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "exploretestuser";
+            const EMAIL: &str = "exploretestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let mut gists = Vec::default();
+            for visibility in [
+                GistVisibility::Public,
+                GistVisibility::Unlisted,
+                GistVisibility::Private,
+                GistVisibility::Public,
+            ] {
+                let msg = CreateGist {
+                    owner: NAME,
+                    description: None,
+                    visibility: &visibility,
+                };
+                gists.push(data.new_gist(db, &msg).await.unwrap().id);
+            }
+
+            let mut explored = Vec::default();
+            let mut after = None;
+            loop {
+                let page = data
+                    .explore_gists(db, GistSort::Created, after.as_deref(), 1)
+                    .await
+                    .unwrap();
+                assert!(page.gists.len() <= 1);
+                explored.extend(page.gists);
+                after = page.next;
+                if after.is_none() {
+                    break;
+                }
+            }
+            for (gist, listed) in gists.iter().zip([true, false, false, true]) {
+                assert_eq!(explored.iter().any(|g| &g.public_id == gist), listed);
+            }
+
+            assert!(matches!(
+                data.explore_gists(db, GistSort::Updated, Some("foo"), 1)
+                    .await
+                    .err(),
+                Some(ServiceError::BadRequest(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,259 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Account data export
+//!
+//! See `docs/account-export.md` for the layout of the archive and the manifest.
+use std::path::Path;
+
+use db_core::prelude::*;
+use flate2::{write::GzEncoder, Compression};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::process::Command;
+
+use crate::errors::*;
+use crate::*;
+
+/// Version of the manifest format, bumped on incompatible changes
+pub const EXPORT_MANIFEST_VERSION: u32 = 1;
+
+/// Name of the manifest file in the archive
+pub const EXPORT_MANIFEST: &str = "manifest.json";
+
+/// Gist in an account export
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedGist {
+    #[serde(flatten)]
+    pub gist: db_core::Gist,
+    /// public ID of the gist that this gist was forked from
+    pub forked_from: Option<String>,
+    /// path of the git bundle of the gist's repository, relative to the root of the
+    /// archive. Unset when the repository is empty.
+    pub bundle: Option<String>,
+}
+
+/// Describes the contents of an account export
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// see [EXPORT_MANIFEST_VERSION]
+    pub version: u32,
+    pub username: String,
+    /// time at which the export was created, UNIX timestamp
+    pub exported: i64,
+    /// gists owned by the user, including private gists
+    pub gists: Vec<ExportedGist>,
+    /// comments authored by the user, on all gists
+    pub comments: Vec<GistComment>,
+}
+
+/// Account export, a gzip compressed tarball
+pub struct AccountExport {
+    /// name of the archive file, like `<username>-export-<timestamp>.tar.gz`
+    pub filename: String,
+    pub manifest: ExportManifest,
+    pub content: Vec<u8>,
+}
+
+/// Bundle all branches of the repository at `path`. Returns `None` when the repository is
+/// empty, git refuses to create empty bundles.
+pub(super) async fn bundle_repository(path: &Path) -> ServiceResult<Option<Vec<u8>>> {
+    if Repository::open(path)?.is_empty()? {
+        return Ok(None);
+    }
+    let output = Command::new("git")
+        .arg("bundle")
+        .arg("create")
+        .arg("-")
+        .arg("--all")
+        .current_dir(path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        log::error!(
+            "unable to bundle {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(ServiceError::InternalServerError);
+    }
+    Ok(Some(output.stdout))
+}
+
+impl Data {
+    /// Export gists, as git bundles, and comments of a user, along with a manifest that
+    /// describes them
+    pub async fn export_account<T: GPDatabse>(
+        &self,
+        db: &T,
+        username: &str,
+    ) -> ServiceResult<AccountExport> {
+        if !db.username_exists(username).await? {
+            return Err(ServiceError::AccountNotFound);
+        }
+        let exported = OffsetDateTime::now_utc().unix_timestamp();
+        let dir = format!("{username}-export-{exported}");
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut append = |path: &str, content: &[u8]| -> ServiceResult<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_mtime(exported.max(0) as u64);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(content.len() as u64);
+            builder.append_data(&mut header, format!("{dir}/{path}"), content)?;
+            Ok(())
+        };
+
+        let mut gists = Vec::default();
+        for gist in db.get_user_gists(username).await?.drain(..) {
+            let forked_from = db
+                .get_fork_parent(&gist.public_id)
+                .await?
+                .map(|p| p.public_id);
+            let path = self.get_repository_path(&gist.public_id);
+            let bundle = match bundle_repository(&path).await? {
+                Some(bundle) => {
+                    let bundle_path = format!("gists/{}.bundle", gist.public_id);
+                    append(&bundle_path, &bundle)?;
+                    Some(bundle_path)
+                }
+                None => None,
+            };
+            gists.push(ExportedGist {
+                gist,
+                forked_from,
+                bundle,
+            });
+        }
+
+        let manifest = ExportManifest {
+            version: EXPORT_MANIFEST_VERSION,
+            username: username.to_owned(),
+            exported,
+            gists,
+            comments: db.get_user_comments(username).await?,
+        };
+        let manifest_json = serde_json::to_vec_pretty(&manifest).unwrap();
+        append(EXPORT_MANIFEST, &manifest_json)?;
+
+        Ok(AccountExport {
+            filename: format!("{dir}.tar.gz"),
+            manifest,
+            content: builder.into_inner()?.finish()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::data::api::v1::gists::{
+        ContentType, CreateGist, FileInfo, FileMode, FileType, GistID,
+    };
+    use crate::tests::*;
+
+    /// Read files in an account export
+    fn read_export(content: &[u8]) -> HashMap<String, Vec<u8>> {
+        let mut archive = tar::Archive::new(GzDecoder::new(content));
+        let mut files = HashMap::default();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            let mut content = Vec::default();
+            entry.read_to_end(&mut content).unwrap();
+            files.insert(path, content);
+        }
+        files
+    }
+
+    #[actix_rt::test]
+    async fn test_export_account_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "exporttestuser";
+            const EMAIL: &str = "exporttestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let msg = CreateGist {
+                owner: NAME,
+                description: Some("export me"),
+                visibility: &GistVisibility::Private,
+            };
+            let mut gist = data.new_gist(db, &msg).await.unwrap();
+            let files = [FileInfo {
+                filename: "foo.md".into(),
+                content: FileType::File(ContentType::Text("foo".into())),
+                mode: FileMode::Regular,
+            }];
+            data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                .await
+                .unwrap();
+            let empty = data.new_gist(db, &msg).await.unwrap();
+            let comment = CreateGistComment {
+                owner: NAME,
+                gist_public_id: &gist.id,
+                comment: "foo",
+            };
+            db.new_comment(&comment).await.unwrap();
+
+            let export = data.export_account(db, NAME).await.unwrap();
+            assert!(export.filename.ends_with(".tar.gz"));
+            let manifest = &export.manifest;
+            assert_eq!(manifest.version, EXPORT_MANIFEST_VERSION);
+            assert_eq!(manifest.username, NAME);
+            assert_eq!(manifest.gists.len(), 2);
+            assert_eq!(manifest.comments.len(), 1);
+            assert_eq!(manifest.comments[0].gist_public_id, gist.id);
+
+            let dir = export.filename.strip_suffix(".tar.gz").unwrap();
+            let files = read_export(&export.content);
+            let archived: ExportManifest =
+                serde_json::from_slice(&files[&format!("{dir}/{EXPORT_MANIFEST}")]).unwrap();
+            assert_eq!(archived.gists.len(), 2);
+            for exported in archived.gists.iter() {
+                assert_eq!(exported.gist.owner, NAME);
+                assert_eq!(exported.gist.visibility, GistVisibility::Private);
+                assert!(exported.forked_from.is_none());
+                if exported.gist.public_id == empty.id {
+                    assert!(exported.bundle.is_none());
+                } else {
+                    let bundle = exported.bundle.as_ref().unwrap();
+                    assert_eq!(bundle, &format!("gists/{}.bundle", gist.id));
+                    assert!(files[&format!("{dir}/{bundle}")].starts_with(b"# v2 git bundle"));
+                }
+            }
+
+            assert_eq!(
+                data.export_account(db, "nonexistentexportuser").await.err(),
+                Some(ServiceError::AccountNotFound)
+            );
+        }
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Gist forks
+use db_core::prelude::*;
+
+use super::gists::{fetch_history, CreateGist, Gist};
+use crate::errors::*;
+use crate::*;
+
+impl Data {
+    /// Fork a [GistVisibility::Public] or [GistVisibility::Unlisted] gist into `owner`'s
+    /// account. The fork starts out with the description, visibility and history of the gist
+    /// it was forked from.
+    pub async fn fork_gist<T: GPDatabse>(
+        &self,
+        db: &T,
+        owner: &str,
+        public_id: &str,
+    ) -> ServiceResult<Gist> {
+        let parent = db.get_gist(public_id).await?;
+        if parent.visibility == GistVisibility::Private {
+            return Err(ServiceError::GistNotFound);
+        }
+        if parent.owner == owner {
+            return Err(ServiceError::BadRequest("Can't fork your own gist".into()));
+        }
+
+        let msg = CreateGist {
+            owner,
+            description: parent.description.as_deref(),
+            visibility: &parent.visibility,
+        };
+        let gist = self.new_gist(db, &msg).await?;
+
+        // the fork is deleted when any of the following steps fail, so that it isn't left
+        // behind without history or a link to its parent
+        let res: ServiceResult<()> = async {
+            let parent_path = self.get_repository_path(public_id);
+            fetch_history(&gist.repository, &parent_path)?;
+            db.new_fork(public_id, &gist.id).await?;
+            self.index_gist(db, &gist.id).await
+        }
+        .await;
+        if let Err(e) = res {
+            self.delete_gist(db, owner, &gist.id).await?;
+            return Err(e);
+        }
+        Ok(gist)
+    }
+
+    /// Get forks of a gist that `viewer` can see: public forks and forks owned by `viewer`
+    pub async fn get_forks<T: GPDatabse>(
+        &self,
+        db: &T,
+        public_id: &str,
+        viewer: Option<&str>,
+    ) -> ServiceResult<Vec<db_core::Gist>> {
+        let mut forks = db.get_forks(public_id).await?;
+        forks
+            .retain(|f| f.visibility == GistVisibility::Public || Some(f.owner.as_str()) == viewer);
+        Ok(forks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::api::v1::gists::{ContentType, FileInfo, FileMode, FileType, GistID};
+    use crate::tests::*;
+
+    #[actix_rt::test]
+    async fn test_gist_forks_work() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "forktestuser";
+            const NAME2: &str = "forktestuser2";
+            const EMAIL: &str = "forktestuser@sss.com";
+            const EMAIL2: &str = "forktestuser2@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.delete_user(db, NAME2, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+
+            let files = [FileInfo {
+                filename: "foo".into(),
+                content: FileType::File(ContentType::Text("foo".into())),
+                mode: FileMode::Regular,
+            }];
+
+            let mut gists = Vec::default();
+            for visibility in [
+                GistVisibility::Public,
+                GistVisibility::Unlisted,
+                GistVisibility::Private,
+            ] {
+                let msg = CreateGist {
+                    owner: NAME,
+                    description: Some("fork me"),
+                    visibility: &visibility,
+                };
+                let mut gist = data.new_gist(db, &msg).await.unwrap();
+                data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                    .await
+                    .unwrap();
+                gists.push(gist.id);
+            }
+
+            // private gists and own gists can't be forked
+            assert_eq!(
+                data.fork_gist(db, NAME2, &gists[2]).await.err(),
+                Some(ServiceError::GistNotFound)
+            );
+            assert!(matches!(
+                data.fork_gist(db, NAME, &gists[0]).await.err(),
+                Some(ServiceError::BadRequest(_))
+            ));
+
+            for parent in gists.iter().take(2) {
+                let fork = data.fork_gist(db, NAME2, parent).await.unwrap();
+                let parent_gist = db.get_gist(parent).await.unwrap();
+                let fork_gist = db.get_gist(&fork.id).await.unwrap();
+                assert_eq!(fork_gist.owner, NAME2);
+                assert_eq!(fork_gist.description, parent_gist.description);
+                assert_eq!(fork_gist.visibility, parent_gist.visibility);
+                assert_eq!(
+                    db.get_fork_parent(&fork.id)
+                        .await
+                        .unwrap()
+                        .unwrap()
+                        .public_id,
+                    *parent
+                );
+
+                // history is preserved
+                let revisions = |id: String| async move {
+                    data.gist_revisions(db, &GistID::ID(&id), 0, 10)
+                        .await
+                        .unwrap()
+                };
+                assert_eq!(
+                    revisions(fork.id.clone()).await,
+                    revisions(parent.clone()).await
+                );
+                let preview = data
+                    .gist_preview(db, &mut GistID::ID(&fork.id), None)
+                    .await
+                    .unwrap();
+                assert_eq!(preview.files.len(), 1);
+                assert_eq!(preview.files[0].filename, "foo");
+
+                // unlisted forks are only visible to their owner
+                let forks = data.get_forks(db, parent, None).await.unwrap();
+                let own_forks = data.get_forks(db, parent, Some(NAME2)).await.unwrap();
+                assert_eq!(own_forks.len(), 1);
+                assert_eq!(own_forks[0].public_id, fork.id);
+                if parent_gist.visibility == GistVisibility::Public {
+                    assert_eq!(forks.len(), 1);
+                } else {
+                    assert!(forks.is_empty());
+                }
+            }
+
+            // fork is deleted when forking fails
+            let forks = db.get_user_gists(NAME2).await.unwrap().len();
+            let parent_path = data.get_repository_path(&gists[0]);
+            let moved_path = data.get_repository_path(&format!(".{}.moved", gists[0]));
+            std::fs::rename(&parent_path, &moved_path).unwrap();
+            assert!(data.fork_gist(db, NAME2, &gists[0]).await.is_err());
+            std::fs::rename(&moved_path, &parent_path).unwrap();
+            assert_eq!(db.get_user_gists(NAME2).await.unwrap().len(), forks);
+        }
+    }
+}
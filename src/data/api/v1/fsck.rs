@@ -0,0 +1,404 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Consistency checks between the database and repository storage
+//!
+//! Gists are stored in the database, while their contents are stored in bare
+//! repositories under `repository.root`. The two can drift apart when GitPad crashes in
+//! the middle of an operation or when storage is tampered with.
+use std::collections::HashSet;
+use std::path::Path;
+
+use db_core::prelude::*;
+use git2::{ErrorCode, Repository};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::fs;
+use tokio::process::Command;
+
+use super::gists::GIST_ID_LEN;
+use crate::errors::*;
+use crate::settings::FsckMode;
+use crate::*;
+
+/// Directory under `repository.root` that repositories are quarantined in
+pub const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Gists created less than this many seconds ago are skipped, they might still be being
+/// written to
+pub const FSCK_GRACE_PERIOD: i64 = 3600;
+
+/// Kind of inconsistency
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckIssueKind {
+    /// gist exists in the database but its repository doesn't
+    MissingRepository,
+    /// repository exists but its gist doesn't exist in the database. Only directories that
+    /// are named like gists and contain a git repository are considered.
+    OrphanedRepository,
+    /// repository doesn't have any commits
+    EmptyRepository,
+    /// repository can't be opened or contains corrupt objects
+    CorruptRepository,
+}
+
+/// Action taken to fix an inconsistency
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckAction {
+    /// gist and its repository, if any, were deleted
+    DeletedGist,
+    /// repository was moved to [QUARANTINE_DIR] and its gist, if any, was deleted
+    Quarantined,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FsckIssue {
+    /// public ID of the gist, name of the repository's directory for orphaned repositories
+    pub gist: String,
+    /// owner of the gist, unset for orphaned repositories
+    pub owner: Option<String>,
+    pub kind: FsckIssueKind,
+    /// details, like the output of `git fsck`
+    pub detail: Option<String>,
+    /// unset when the issue was left alone
+    pub action: Option<FsckAction>,
+    /// set when `action` failed, it might have been applied partially
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FsckReport {
+    pub mode: FsckMode,
+    /// number of gists in the database
+    pub gists: usize,
+    /// number of repositories in storage
+    pub repositories: usize,
+    pub issues: Vec<FsckIssue>,
+}
+
+/// Check if `name` could be the public ID of a gist
+fn is_gist_id(name: &str) -> bool {
+    name.len() == GIST_ID_LEN && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Check the repository at `path`. Returns `None` when it is healthy.
+async fn check_repository(path: &Path) -> ServiceResult<Option<(FsckIssueKind, Option<String>)>> {
+    let head = match Repository::open(path) {
+        Ok(repo) => repo.head().map(|_| ()),
+        Err(e) => {
+            return Ok(Some((
+                FsckIssueKind::CorruptRepository,
+                Some(e.message().to_owned()),
+            )))
+        }
+    };
+    if let Err(e) = head {
+        if e.code() == ErrorCode::UnbornBranch {
+            return Ok(Some((FsckIssueKind::EmptyRepository, None)));
+        }
+    }
+
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(path)
+        .arg("fsck")
+        .arg("--no-progress")
+        .arg("--no-dangling")
+        .output()
+        .await?;
+    if output.status.success() {
+        return Ok(None);
+    }
+    let mut detail = String::from_utf8_lossy(&output.stderr).to_string();
+    detail.push_str(&String::from_utf8_lossy(&output.stdout));
+    Ok(Some((
+        FsckIssueKind::CorruptRepository,
+        Some(detail.trim().to_owned()),
+    )))
+}
+
+impl Data {
+    /// Find inconsistencies between the database and repository storage, and fix them
+    /// according to `mode`. Failing to fix an issue doesn't stop the remaining issues from
+    /// being fixed, the failure is recorded on the issue instead.
+    pub async fn fsck<T: GPDatabse>(&self, db: &T, mode: FsckMode) -> ServiceResult<FsckReport> {
+        let mut report = self.fsck_check(db).await?;
+        report.mode = mode;
+        for issue in report.issues.iter_mut() {
+            if let Err(e) = self.fsck_fix(db, issue, mode).await {
+                log::error!("failed to fix {:?} {}: {}", issue.kind, issue.gist, e);
+                issue.error = Some(e.to_string());
+            }
+        }
+        Ok(report)
+    }
+
+    /// Find inconsistencies without fixing them
+    async fn fsck_check<T: GPDatabse>(&self, db: &T) -> ServiceResult<FsckReport> {
+        let gists = db.get_all_gists().await?;
+        let mut repositories = HashSet::new();
+        let mut dir = fs::read_dir(&self.settings.repository.root).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // hooks, quarantined and deleted repositories
+            if name.starts_with('.') || !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            repositories.insert(name);
+        }
+
+        let recent = OffsetDateTime::now_utc().unix_timestamp() - FSCK_GRACE_PERIOD;
+        let mut issues = Vec::default();
+        for gist in gists.iter() {
+            let issue = |kind, detail| FsckIssue {
+                gist: gist.public_id.clone(),
+                owner: Some(gist.owner.clone()),
+                kind,
+                detail,
+                action: None,
+                error: None,
+            };
+            if !repositories.contains(&gist.public_id) {
+                if gist.created < recent {
+                    issues.push(issue(FsckIssueKind::MissingRepository, None));
+                }
+                continue;
+            }
+            let path = self.get_repository_path(&gist.public_id);
+            match check_repository(&path).await? {
+                Some((FsckIssueKind::EmptyRepository, _)) if gist.created >= recent => (),
+                Some((kind, detail)) => issues.push(issue(kind, detail)),
+                None => (),
+            }
+        }
+
+        // other directories, like lost+found, aren't GitPad's to touch
+        let ids: HashSet<&str> = gists.iter().map(|g| g.public_id.as_str()).collect();
+        let mut orphans: Vec<&String> = repositories
+            .iter()
+            .filter(|r| !ids.contains(r.as_str()) && is_gist_id(r))
+            .filter(|r| Repository::open_bare(self.get_repository_path(r)).is_ok())
+            .collect();
+        orphans.sort();
+        for orphan in orphans.drain(..) {
+            issues.push(FsckIssue {
+                gist: orphan.clone(),
+                owner: None,
+                kind: FsckIssueKind::OrphanedRepository,
+                detail: None,
+                action: None,
+                error: None,
+            });
+        }
+
+        Ok(FsckReport {
+            mode: FsckMode::Check,
+            gists: gists.len(),
+            repositories: repositories.len(),
+            issues,
+        })
+    }
+
+    /// Fix `issue` according to `mode`, and record the action that was taken
+    async fn fsck_fix<T: GPDatabse>(
+        &self,
+        db: &T,
+        issue: &mut FsckIssue,
+        mode: FsckMode,
+    ) -> ServiceResult<()> {
+        use FsckIssueKind::*;
+
+        let action = match (mode, issue.kind) {
+            (FsckMode::Check, _) => None,
+            (_, MissingRepository) | (FsckMode::Repair, EmptyRepository) => {
+                Some(FsckAction::DeletedGist)
+            }
+            // orphans might still be needed, like when gists are restored from a backup of
+            // the database, so they aren't deleted
+            (_, OrphanedRepository) | (FsckMode::Quarantine, _) => Some(FsckAction::Quarantined),
+            // there's no way to repair corrupt repositories without losing data
+            (FsckMode::Repair, CorruptRepository) => None,
+        };
+        issue.action = action;
+        if action == Some(FsckAction::Quarantined) {
+            self.quarantine_repository(&issue.gist).await?;
+        }
+        if action.is_some() {
+            if let Some(owner) = &issue.owner {
+                self.delete_gist(db, owner, &issue.gist).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move repository of gist `public_id` to [QUARANTINE_DIR]
+    async fn quarantine_repository(&self, public_id: &str) -> ServiceResult<()> {
+        let quarantine = self.get_repository_path(QUARANTINE_DIR);
+        fs::create_dir_all(&quarantine).await?;
+        let mut target = quarantine.join(public_id);
+        if target.exists() {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            target = quarantine.join(format!("{public_id}-{now}"));
+        }
+        fs::rename(self.get_repository_path(public_id), target).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use git2::{ObjectType, Oid};
+
+    use super::*;
+    use crate::data::api::v1::gists::{
+        ContentType, CreateGist, FileInfo, FileMode, FileType, GistID,
+    };
+    use crate::tests::*;
+    use crate::utils::get_random;
+
+    #[actix_rt::test]
+    async fn test_fsck_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "fscktestuser";
+            const EMAIL: &str = "fscktestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let msg = CreateGist {
+                owner: NAME,
+                description: None,
+                visibility: &GistVisibility::Public,
+            };
+            let content = format!("fsck {}", get_random(10));
+            let files = [FileInfo {
+                filename: "foo.md".into(),
+                content: FileType::File(ContentType::Text(content.clone())),
+                mode: FileMode::Regular,
+            }];
+            let mut gists = Vec::default();
+            for _ in 0..3 {
+                let mut gist = data.new_gist(db, &msg).await.unwrap();
+                data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                    .await
+                    .unwrap();
+                // gists created recently are skipped
+                db.set_gist_timestamps(&gist.id, 1000, 1000).await.unwrap();
+                gists.push(gist.id);
+            }
+            let (healthy, missing, corrupt) = (&gists[0], &gists[1], &gists[2]);
+
+            fs::remove_dir_all(data.get_repository_path(missing))
+                .await
+                .unwrap();
+
+            let blob = Oid::hash_object(ObjectType::Blob, content.as_bytes()).unwrap();
+            let blob = blob.to_string();
+            let object = data
+                .get_repository_path(corrupt)
+                .join("objects")
+                .join(&blob[..2])
+                .join(&blob[2..]);
+            std::fs::set_permissions(&object, std::fs::Permissions::from_mode(0o644)).unwrap();
+            std::fs::write(&object, "corrupt").unwrap();
+
+            let empty = data.new_gist(db, &msg).await.unwrap().id;
+            let recent = data.new_gist(db, &msg).await.unwrap().id;
+            db.set_gist_timestamps(&empty, 1000, 1000).await.unwrap();
+
+            let orphan = get_random(GIST_ID_LEN);
+            Repository::init_bare(data.get_repository_path(&orphan)).unwrap();
+            // directories that aren't gist repositories are left alone
+            let unrelated = [format!("fsck{}", get_random(10)), get_random(GIST_ID_LEN)];
+            for dir in unrelated.iter() {
+                fs::create_dir(data.get_repository_path(dir)).await.unwrap();
+            }
+
+            let report = data.fsck(db, FsckMode::Check).await.unwrap();
+            assert_eq!(report.mode, FsckMode::Check);
+            assert!(report.issues.iter().all(|i| i.action.is_none()));
+            let find = |gist: &str| report.issues.iter().find(|i| i.gist == gist).cloned();
+            assert!(find(healthy).is_none());
+            assert!(find(&recent).is_none());
+            assert_eq!(
+                find(missing).unwrap().kind,
+                FsckIssueKind::MissingRepository
+            );
+            assert_eq!(
+                find(corrupt).unwrap().kind,
+                FsckIssueKind::CorruptRepository
+            );
+            assert!(find(corrupt).unwrap().detail.is_some());
+            assert_eq!(find(&empty).unwrap().kind, FsckIssueKind::EmptyRepository);
+            let orphan_issue = find(&orphan).unwrap();
+            assert_eq!(orphan_issue.kind, FsckIssueKind::OrphanedRepository);
+            assert!(orphan_issue.owner.is_none());
+            for dir in unrelated.iter() {
+                assert!(find(dir).is_none());
+            }
+
+            // repositories from the other backend show up as orphans, so repairs are
+            // only run on the issues that this test created
+            let mut issues = [
+                find(missing).unwrap(),
+                find(corrupt).unwrap(),
+                find(&empty).unwrap(),
+                orphan_issue,
+            ];
+            for issue in issues.iter_mut() {
+                data.fsck_fix(db, issue, FsckMode::Repair).await.unwrap();
+            }
+            assert_eq!(issues[0].action, Some(FsckAction::DeletedGist));
+            assert!(!db.gist_exists(missing).await.unwrap());
+            assert_eq!(issues[1].action, None);
+            assert!(db.gist_exists(corrupt).await.unwrap());
+            assert_eq!(issues[2].action, Some(FsckAction::DeletedGist));
+            assert!(!db.gist_exists(&empty).await.unwrap());
+            assert!(!data.get_repository_path(&empty).exists());
+            assert_eq!(issues[3].action, Some(FsckAction::Quarantined));
+            assert!(!data.get_repository_path(&orphan).exists());
+            let quarantined = data.get_repository_path(QUARANTINE_DIR).join(&orphan);
+            assert!(quarantined.exists());
+            fs::remove_dir_all(quarantined).await.unwrap();
+            for dir in unrelated.iter() {
+                assert!(data.get_repository_path(dir).exists());
+                fs::remove_dir(data.get_repository_path(dir)).await.unwrap();
+            }
+
+            // corrupt repositories are quarantined
+            let mut issue = issues[1].clone();
+            data.fsck_fix(db, &mut issue, FsckMode::Quarantine)
+                .await
+                .unwrap();
+            assert_eq!(issue.action, Some(FsckAction::Quarantined));
+            assert!(!db.gist_exists(corrupt).await.unwrap());
+            assert!(!data.get_repository_path(corrupt).exists());
+            let quarantined = data.get_repository_path(QUARANTINE_DIR).join(corrupt);
+            assert!(quarantined.exists());
+            fs::remove_dir_all(quarantined).await.unwrap();
+        }
+    }
+}
@@ -14,6 +14,7 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use db_core::prelude::*;
@@ -81,6 +82,9 @@ impl From<TreeEntry<'_>> for GitFileMode {
     }
 }
 
+/// Length of public IDs of gists, which are also the names of their repositories
+pub const GIST_ID_LEN: usize = 32;
+
 pub struct Gist {
     pub id: String,
     pub repository: git2::Repository,
@@ -97,10 +101,53 @@ pub enum GistID<'a> {
     ID(&'a str),
 }
 
+/// Mode of a file in a gist
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMode {
+    /// non-executable file
+    #[default]
+    Regular,
+    /// executable file
+    Executable,
+    /// symbolic link, contents of the file is the path that the link points to
+    Symlink,
+}
+
+impl FileMode {
+    /// get mode of tree entries that represent files of this mode
+    pub fn to_git_mode(self) -> GitFileMode {
+        match self {
+            Self::Regular => GitFileMode::Regular,
+            Self::Executable => GitFileMode::Executable,
+            Self::Symlink => GitFileMode::Symlink,
+        }
+    }
+}
+
+impl TryFrom<&'_ TreeEntry<'_>> for FileMode {
+    type Error = ServiceError;
+
+    /// directories are reported as [FileMode::Regular]
+    fn try_from(t: &TreeEntry) -> ServiceResult<Self> {
+        match GitFileMode::from(t) {
+            GitFileMode::Regular | GitFileMode::Deprecated | GitFileMode::Dir => Ok(Self::Regular),
+            GitFileMode::Executable => Ok(Self::Executable),
+            GitFileMode::Symlink => Ok(Self::Symlink),
+            GitFileMode::Empty | GitFileMode::Submodule | GitFileMode::Unsupported => {
+                Err(ServiceError::UnsupportedFileMode(t.filemode()))
+            }
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FileInfo {
     pub filename: String,
     pub content: FileType,
+    /// mode of the file, ignored for directories
+    #[serde(default)]
+    pub mode: FileMode,
 }
 
 impl GenerateHTML for FileInfo {
@@ -115,6 +162,10 @@ impl GenerateHTML for FileInfo {
         }
 
         fn extract(f: &mut FileInfo) {
+            if f.mode == FileMode::Symlink {
+                // link target is displayed as is
+                return;
+            }
             match f.content {
                 FileType::File(ref mut c) => match &mut *c {
                     ContentType::Binary(_) => (),
@@ -142,6 +193,39 @@ pub struct GistInfo {
     pub updated: i64,
     pub visibility: GistVisibility,
     pub id: String,
+    /// ID of the commit that the files were read from
+    pub revision: String,
+    /// number of users that have starred the gist
+    pub stars: i64,
+}
+
+impl FileInfo {
+    /// Flatten directories into the files that they contain. Filenames of nested files are
+    /// paths relative to `self`, separated by `/`.
+    pub fn into_files(self) -> Vec<FileInfo> {
+        fn flatten(f: FileInfo, prefix: Option<&str>, files: &mut Vec<FileInfo>) {
+            let filename = match prefix {
+                Some(prefix) => format!("{prefix}/{}", f.filename),
+                None => f.filename,
+            };
+            match f.content {
+                FileType::File(content) => files.push(FileInfo {
+                    filename,
+                    content: FileType::File(content),
+                    mode: f.mode,
+                }),
+                FileType::Dir(children) => {
+                    for child in children.into_iter() {
+                        flatten(child, Some(&filename), files);
+                    }
+                }
+            }
+        }
+
+        let mut files = Vec::default();
+        flatten(self, None, &mut files);
+        files
+    }
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug, Deserialize)]
@@ -176,6 +260,158 @@ pub enum FileType {
     Dir(Vec<FileInfo>),
 }
 
+/// Get commit that `rev` points to. `rev` can either be a full commit ID or an
+/// abbreviated one(minimum 4 characters). HEAD is used when `rev` is `None`.
+pub fn resolve_revision<'a>(repo: &'a Repository, rev: Option<&str>) -> ServiceResult<Commit<'a>> {
+    match rev {
+        None => match repo.head() {
+            Ok(head) => Ok(head.peel_to_commit()?),
+            // gist doesn't have any commits yet
+            Err(e) if e.code() == ErrorCode::UnbornBranch => Err(ServiceError::RevisionNotFound),
+            Err(e) => Err(e.into()),
+        },
+        Some(rev) => {
+            if rev.len() < 4 || rev.len() > 40 || !rev.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ServiceError::RevisionNotFound);
+            }
+            repo.revparse_single(rev)
+                .and_then(|obj| obj.peel_to_commit())
+                .map_err(|_| ServiceError::RevisionNotFound)
+        }
+    }
+}
+
+/// File that is to be written to a repository, with its path split into components
+pub(super) struct TreeFile<'a> {
+    path: Vec<String>,
+    content: &'a ContentType,
+    mode: FileMode,
+}
+
+/// Split filenames like `src/main.rs` into path components, descending into directories.
+/// Spaces in each component are escaped with [escape_spaces].
+pub(super) fn flatten_tree_files<'a>(
+    prefix: &[String],
+    files: &'a [FileInfo],
+    tree_files: &mut Vec<TreeFile<'a>>,
+) -> ServiceResult<()> {
+    for file in files.iter() {
+        if file.filename.trim().is_empty() {
+            return Err(ServiceError::BadRequest("filename can't be empty".into()));
+        }
+        let mut path = prefix.to_vec();
+        for component in file.filename.split('/') {
+            if component.trim().is_empty() || component == "." || component == ".." {
+                return Err(ServiceError::BadRequest(format!(
+                    "invalid filename: {}",
+                    file.filename
+                )));
+            }
+            path.push(escape_spaces(component));
+        }
+
+        match &file.content {
+            FileType::File(content) => tree_files.push(TreeFile {
+                path,
+                content,
+                mode: file.mode,
+            }),
+            FileType::Dir(dir_contents) => flatten_tree_files(&path, dir_contents, tree_files)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write `files` on top of `base` and return ID of the resulting tree. Files nested in
+/// directories are written to sub-trees, which are created when they don't exist in `base`.
+pub(super) fn write_tree(
+    repo: &Repository,
+    base: Option<&Tree>,
+    files: &[&TreeFile],
+    depth: usize,
+) -> ServiceResult<Oid> {
+    let mut tree_builder = repo.treebuilder(base)?;
+    let mut blobs = HashSet::new();
+    let mut dirs: BTreeMap<&str, Vec<&TreeFile>> = BTreeMap::new();
+
+    for file in files.iter() {
+        let name = file.path[depth].as_str();
+        if file.path.len() == depth + 1 {
+            let obj = repo.blob(file.content.as_bytes())?;
+            tree_builder.insert(name, obj, file.mode.to_git_mode() as i32)?;
+            blobs.insert(name);
+        } else {
+            dirs.entry(name).or_default().push(file);
+        }
+    }
+
+    for (name, dir_files) in dirs.into_iter() {
+        if blobs.contains(name) {
+            return Err(ServiceError::BadRequest(format!(
+                "{name} can't be both a file and a directory"
+            )));
+        }
+        let sub_tree = match base.and_then(|tree| tree.get_name(name)) {
+            Some(entry) if entry.kind() == Some(ObjectType::Tree) => {
+                Some(repo.find_tree(entry.id())?)
+            }
+            _ => None,
+        };
+        let sub_tree = write_tree(repo, sub_tree.as_ref(), &dir_files, depth + 1)?;
+        tree_builder.insert(name, sub_tree, GitFileMode::Dir as i32)?;
+    }
+    Ok(tree_builder.write()?)
+}
+
+/// Commit tree `tree_hash` on top of HEAD. The commit is dated `time`(UNIX timestamp),
+/// current time is used when `time` is `None`.
+pub(super) fn commit_tree(
+    repo: &Repository,
+    tree_hash: Oid,
+    time: Option<i64>,
+) -> ServiceResult<()> {
+    let signature = || match time {
+        Some(time) => Signature::new("gists", "admin@gists.batsense.net", &Time::new(time, 0)),
+        None => Signature::now("gists", "admin@gists.batsense.net"),
+    };
+    let author = signature()?;
+    let committer = signature()?;
+
+    let commit_tree = repo.find_tree(tree_hash)?;
+    let msg = "";
+    if let Err(e) = repo.head() {
+        if e.code() == ErrorCode::UnbornBranch && e.class() == ErrorClass::Reference {
+            // fisrt commit ever; set parent commit(s) to empty array
+            repo.commit(Some("HEAD"), &author, &committer, msg, &commit_tree, &[])?;
+        } else {
+            return Err(e.into());
+        }
+    } else {
+        let head_ref = repo.head()?;
+        let head_commit = head_ref.peel_to_commit()?;
+        repo.commit(
+            Some("HEAD"),
+            &author,
+            &committer,
+            msg,
+            &commit_tree,
+            &[&head_commit],
+        )?;
+    };
+    Ok(())
+}
+
+/// Copy branches and HEAD of the repository at `source` into `repo`
+pub(super) fn fetch_history(repo: &Repository, source: &Path) -> ServiceResult<()> {
+    let source_repo = Repository::open(source)?;
+    let mut remote = repo.remote_anonymous(&source_repo.path().to_string_lossy())?;
+    remote.fetch(&["+refs/heads/*:refs/heads/*"], None, None)?;
+    if let Some(head) = source_repo.find_reference("HEAD")?.symbolic_target() {
+        repo.set_head(head)?;
+    }
+    Ok(())
+}
+
 impl Data {
     pub async fn new_gist<T: GPDatabse>(
         &self,
@@ -183,7 +419,7 @@ impl Data {
         msg: &CreateGist<'_>,
     ) -> ServiceResult<Gist> {
         loop {
-            let gist_id = get_random(32);
+            let gist_id = get_random(GIST_ID_LEN);
 
             if db.gist_exists(&gist_id).await? {
                 continue;
@@ -205,16 +441,62 @@ impl Data {
                 public_id: &gist_id,
             };
 
-            db.new_gist(&create_gist).await.unwrap();
+            db.new_gist(&create_gist).await?;
 
             fs::create_dir(&gist_path).await?;
             return Ok(Gist {
                 id: gist_id,
-                repository: Repository::init_bare(&gist_path).unwrap(),
+                repository: Repository::init_bare(&gist_path)?,
             });
         }
     }
 
+    /// Delete gist, its comments and its repository.
+    ///
+    /// The repository is moved out of the way before the gist is deleted from the
+    /// database, so that it can be put back if that fails.
+    pub async fn delete_gist<T: GPDatabse>(
+        &self,
+        db: &T,
+        owner: &str,
+        public_id: &str,
+    ) -> ServiceResult<()> {
+        let gist_path = self.get_repository_path(public_id);
+        if !gist_path.exists() {
+            db.delete_gist(owner, public_id).await?;
+            return Ok(());
+        }
+
+        let trash_path = self.get_repository_path(&format!(".{public_id}.deleted"));
+        if trash_path.exists() {
+            fs::remove_dir_all(&trash_path).await?;
+        }
+        fs::rename(&gist_path, &trash_path).await?;
+        if let Err(e) = db.delete_gist(owner, public_id).await {
+            fs::rename(&trash_path, &gist_path).await?;
+            return Err(e.into());
+        }
+        fs::remove_dir_all(&trash_path).await?;
+        Ok(())
+    }
+
+    /// Get gists owned by `owner` that `viewer` can see, most recently updated first. Owners
+    /// see all of their gists, everyone else only sees public ones.
+    pub async fn get_user_gists<T: GPDatabse>(
+        &self,
+        db: &T,
+        owner: &str,
+        viewer: Option<&str>,
+    ) -> ServiceResult<Vec<db_core::Gist>> {
+        let mut gists = if viewer == Some(owner) {
+            db.get_user_gists(owner).await?
+        } else {
+            db.get_user_public_gists(owner).await?
+        };
+        gists.sort_by_key(|g| std::cmp::Reverse(g.updated));
+        Ok(gists)
+    }
+
     pub(crate) fn get_repository_path(&self, gist_id: &str) -> PathBuf {
         Path::new(&self.settings.repository.root).join(gist_id)
     }
@@ -231,7 +513,7 @@ impl Data {
 
     pub async fn write_file<T: GPDatabse>(
         &self,
-        _db: &T,
+        db: &T,
         gist_id: &mut GistID<'_>,
         files: &[FileInfo],
     ) -> ServiceResult<()> {
@@ -239,67 +521,82 @@ impl Data {
             return Err(ServiceError::GistEmpty);
         }
 
-        // TODO change updated in DB
-        let inner = |repo: &mut Repository| -> ServiceResult<()> {
-            let mut tree_builder = match repo.head() {
-                Err(_) => repo.treebuilder(None).unwrap(),
+        let mut tree_files = Vec::with_capacity(files.len());
+        flatten_tree_files(&[], files, &mut tree_files)?;
+        if tree_files.is_empty() {
+            return Err(ServiceError::GistEmpty);
+        }
+        let tree_files: Vec<&TreeFile> = tree_files.iter().collect();
 
-                Ok(h) => repo.treebuilder(Some(&h.peel_to_tree().unwrap())).unwrap(),
+        let inner = |repo: &mut Repository| -> ServiceResult<()> {
+            let base = match repo.head() {
+                Err(_) => None,
+                Ok(h) => Some(h.peel_to_tree()?),
             };
+            let tree_hash = write_tree(repo, base.as_ref(), &tree_files, 0)?;
+            commit_tree(repo, tree_hash, None)?;
+            Ok(())
+        };
 
-            let odb = repo.odb().unwrap();
+        match gist_id {
+            GistID::ID(path) => {
+                let mut repo = git2::Repository::open(self.get_repository_path(path))?;
+                inner(&mut repo)?;
+            }
+            GistID::Repository(repository) => inner(*repository)?,
+        };
+        let public_id = self.get_gist_id_from_repo_path(gist_id);
+        db.touch_gist(&public_id).await?;
+        self.index_gist(db, &public_id).await?;
+        Ok(())
+    }
 
-            for file in files.iter() {
-                let escaped_filename = escape_spaces(&file.filename);
-
-                match &file.content {
-                    FileType::Dir(_dir_contents) => unimplemented!(),
-                    FileType::File(f) => {
-                        let obj = odb.write(ObjectType::Blob, f.as_bytes()).unwrap();
-                        tree_builder
-                            .insert(&escaped_filename, obj, 0o100644)
-                            .unwrap();
-                    }
+    /// Replace all files in a gist with `files`: files that aren't present in `files` are
+    /// removed from the gist. A new commit is created only when contents of the gist change.
+    ///
+    /// Returns `true` when a commit was created.
+    pub async fn replace_files<T: GPDatabse>(
+        &self,
+        db: &T,
+        gist_id: &mut GistID<'_>,
+        files: &[FileInfo],
+    ) -> ServiceResult<bool> {
+        if files.is_empty() {
+            return Err(ServiceError::GistEmpty);
+        }
+
+        let mut tree_files = Vec::with_capacity(files.len());
+        flatten_tree_files(&[], files, &mut tree_files)?;
+        if tree_files.is_empty() {
+            return Err(ServiceError::GistEmpty);
+        }
+        let tree_files: Vec<&TreeFile> = tree_files.iter().collect();
+
+        let inner = |repo: &mut Repository| -> ServiceResult<bool> {
+            let tree_hash = write_tree(repo, None, &tree_files, 0)?;
+            if let Ok(head) = repo.head() {
+                if head.peel_to_tree()?.id() == tree_hash {
+                    // nothing changed
+                    return Ok(false);
                 }
             }
-            let tree_hash = tree_builder.write().unwrap();
-            let author = Signature::now("gists", "admin@gists.batsense.net").unwrap();
-            let committer = Signature::now("gists", "admin@gists.batsense.net").unwrap();
-
-            let commit_tree = repo.find_tree(tree_hash).unwrap();
-            let msg = "";
-            if let Err(e) = repo.head() {
-                if e.code() == ErrorCode::UnbornBranch && e.class() == ErrorClass::Reference {
-                    // fisrt commit ever; set parent commit(s) to empty array
-                    repo.commit(Some("HEAD"), &author, &committer, msg, &commit_tree, &[])
-                        .unwrap();
-                } else {
-                    panic!("{:?}", e);
-                }
-            } else {
-                let head_ref = repo.head().unwrap();
-                let head_commit = head_ref.peel_to_commit().unwrap();
-                repo.commit(
-                    Some("HEAD"),
-                    &author,
-                    &committer,
-                    msg,
-                    &commit_tree,
-                    &[&head_commit],
-                )
-                .unwrap();
-            };
-
-            Ok(())
+            commit_tree(repo, tree_hash, None)?;
+            Ok(true)
         };
 
-        match gist_id {
+        let changed = match gist_id {
             GistID::ID(path) => {
-                let mut repo = git2::Repository::open(self.get_repository_path(path)).unwrap();
-                inner(&mut repo)
+                let mut repo = git2::Repository::open(self.get_repository_path(path))?;
+                inner(&mut repo)?
             }
-            GistID::Repository(repository) => inner(*repository),
+            GistID::Repository(repository) => inner(repository)?,
+        };
+        if changed {
+            let public_id = self.get_gist_id_from_repo_path(gist_id);
+            db.touch_gist(&public_id).await?;
+            self.index_gist(db, &public_id).await?;
         }
+        Ok(changed)
     }
 
     /// Please note that this method expects path to not contain any spaces
@@ -308,70 +605,67 @@ impl Data {
     /// For example, a read request for "foo bar.md" will fail even if that file is present
     /// in the repository. However, it will succeed if the output of [escape_spaces] is
     /// used in the request.
+    ///
+    /// File is read from HEAD when `rev` is `None`. See [resolve_revision].
     pub async fn read_file<T: GPDatabse>(
         &self,
         _db: &T,
         gist_id: &GistID<'_>,
         path: &str,
+        rev: Option<&str>,
     ) -> ServiceResult<FileInfo> {
         let inner = |repo: &git2::Repository| -> ServiceResult<FileInfo> {
-            let head = repo.head().unwrap();
-            let tree = head.peel_to_tree().unwrap();
-            let entry = tree.get_path(Path::new(path)).unwrap();
-            fn read_file(id: Oid, repo: &git2::Repository) -> FileType {
-                let blob = repo.find_blob(id).unwrap();
-                FileType::File(ContentType::from_blob(&blob))
+            let tree = resolve_revision(repo, rev)?.tree()?;
+            let entry = match tree.get_path(Path::new(path)) {
+                Ok(entry) => entry,
+                Err(e) if e.code() == ErrorCode::NotFound => {
+                    return Err(ServiceError::FileNotFound)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            fn read_file(id: Oid, repo: &git2::Repository) -> ServiceResult<FileType> {
+                let blob = repo.find_blob(id)?;
+                Ok(FileType::File(ContentType::from_blob(&blob)))
             }
 
-            fn read_dir(id: Oid, repo: &Repository) -> FileType {
-                let tree = repo.find_tree(id).unwrap();
+            fn read_dir(id: Oid, repo: &Repository) -> ServiceResult<FileType> {
+                let tree = repo.find_tree(id)?;
                 let mut items = Vec::with_capacity(tree.len());
                 for item in tree.iter() {
                     if let Some(name) = item.name() {
-                        #[allow(clippy::needless_borrow)]
-                        let mode: GitFileMode = (&item).into();
-                        let file = match mode {
-                            GitFileMode::Dir => read_dir(item.id(), repo),
-                            GitFileMode::Submodule => unimplemented!(),
-                            GitFileMode::Empty => unimplemented!(),
-                            GitFileMode::Deprecated => unimplemented!(),
-                            GitFileMode::Unsupported => unimplemented!(),
-                            GitFileMode::Symlink => unimplemented!(),
-                            GitFileMode::Executable => read_file(item.id(), repo),
-                            GitFileMode::Regular => read_file(item.id(), repo),
-                        };
-                        items.push(FileInfo {
-                            filename: name.to_owned(),
-                            content: file,
-                        });
+                        items.push(read_entry(name, &item, repo)?);
                     }
                 }
-                FileType::Dir(items)
+                Ok(FileType::Dir(items))
             }
-            let mode: GitFileMode = entry.clone().into();
-            if let Some(name) = entry.name() {
-                let file = match mode {
-                    GitFileMode::Dir => read_dir(entry.id(), repo),
-                    GitFileMode::Submodule => unimplemented!(),
-                    GitFileMode::Empty => unimplemented!(),
-                    GitFileMode::Deprecated => unimplemented!(),
-                    GitFileMode::Unsupported => unimplemented!(),
-                    GitFileMode::Symlink => unimplemented!(),
-                    GitFileMode::Executable => read_file(entry.id(), repo),
-                    GitFileMode::Regular => read_file(entry.id(), repo),
+
+            fn read_entry(
+                name: &str,
+                entry: &TreeEntry,
+                repo: &Repository,
+            ) -> ServiceResult<FileInfo> {
+                let mode = FileMode::try_from(entry)?;
+                let content = if GitFileMode::from(entry) == GitFileMode::Dir {
+                    read_dir(entry.id(), repo)?
+                } else {
+                    read_file(entry.id(), repo)?
                 };
                 Ok(FileInfo {
-                    filename: name.to_string(),
-                    content: file,
+                    filename: name.to_owned(),
+                    content,
+                    mode,
                 })
-            } else {
-                unimplemented!();
+            }
+
+            match entry.name() {
+                Some(name) => read_entry(name, &entry, repo),
+                None => Err(ServiceError::FileNotFound),
             }
         };
 
         match gist_id {
             GistID::ID(path) => {
-                let repo = git2::Repository::open(self.get_repository_path(path)).unwrap();
+                let repo = git2::Repository::open(self.get_repository_path(path))?;
                 inner(&repo)
             }
             GistID::Repository(repository) => inner(repository),
@@ -379,33 +673,33 @@ impl Data {
     }
 
     /// fetches gist metadata from DB and retrieves contents of all the files stored
-    /// in the repository
-    // TODO
-    // Data::gist_preview uses Data::read_file under the hood, which
-    // currently reads subdirectories up to level 1 depth. Decision has
-    // to be made regarding what to do with level 2 and below subdirectories.
+    /// in the repository at revision `rev`(HEAD when `rev` is `None`). Subdirectories
+    /// are read recursively, see [FileType::Dir].
     pub async fn gist_preview<T: GPDatabse>(
         &self,
         db: &T,
         gist_id: &mut GistID<'_>,
+        rev: Option<&str>,
     ) -> ServiceResult<GistInfo> {
         async fn inner<F: GPDatabse>(
             gist_id: &mut GistID<'_>,
             data: &Data,
             db: &F,
-        ) -> ServiceResult<Vec<FileInfo>> {
+            rev: Option<&str>,
+        ) -> ServiceResult<(String, Vec<FileInfo>)> {
             match &gist_id {
                 GistID::Repository(repo) => {
-                    let head = repo.head().unwrap();
-                    let tree = head.peel_to_tree().unwrap();
+                    let commit = resolve_revision(repo, rev)?;
+                    let revision = commit.id().to_string();
+                    let tree = commit.tree()?;
                     let mut files = Vec::with_capacity(5);
                     for item in tree.iter() {
                         if let Some(name) = item.name() {
-                            let file = data.read_file(db, gist_id, name).await?;
+                            let file = data.read_file(db, gist_id, name, Some(&revision)).await?;
                             files.push(file);
                         }
                     }
-                    Ok(files)
+                    Ok((revision, files))
                 }
                 _ => unimplemented!(),
             }
@@ -414,15 +708,16 @@ impl Data {
         let gist_public_id = self.get_gist_id_from_repo_path(gist_id);
         let gist_info = db.get_gist(&gist_public_id).await?;
 
-        let files = match &gist_id {
+        let (revision, files) = match &gist_id {
             GistID::ID(path) => {
-                let mut repo = git2::Repository::open(self.get_repository_path(path)).unwrap();
+                let mut repo = git2::Repository::open(self.get_repository_path(path))?;
                 let mut gist_id = GistID::Repository(&mut repo);
-                inner(&mut gist_id, self, db).await?
+                inner(&mut gist_id, self, db, rev).await?
             }
-            GistID::Repository(_) => inner(gist_id, self, db).await?,
+            GistID::Repository(_) => inner(gist_id, self, db, rev).await?,
         };
 
+        let stars = db.get_star_count(&gist_info.public_id).await?;
         let resp = GistInfo {
             created: gist_info.created,
             updated: gist_info.updated,
@@ -431,6 +726,8 @@ impl Data {
             description: gist_info.description,
             owner: gist_info.owner,
             id: gist_info.public_id,
+            revision,
+            stars,
         };
 
         Ok(resp)
@@ -465,12 +762,18 @@ pub mod tests {
         ) {
             for file in files.iter() {
                 let content = self
-                    .read_file(db, &GistID::ID(gist_id), &escape_spaces(&file.filename))
+                    .read_file(
+                        db,
+                        &GistID::ID(gist_id),
+                        &escape_spaces(&file.filename),
+                        None,
+                    )
                     .await
                     .unwrap();
                 let req_escaped_file = FileInfo {
                     filename: escape_spaces(&file.filename),
                     content: file.content.clone(),
+                    mode: file.mode,
                 };
                 assert_eq!(&content, &req_escaped_file);
             }
@@ -508,14 +811,17 @@ pub mod tests {
                 FileInfo {
                     filename: "foo".into(),
                     content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
                 },
                 FileInfo {
                     filename: "bar".into(),
                     content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
                 },
                 FileInfo {
                     filename: "foo bar".into(),
                     content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
                 },
             ];
 
@@ -526,6 +832,7 @@ pub mod tests {
             let files2 = [FileInfo {
                 filename: "notfirstcommit".into(),
                 content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                mode: FileMode::Regular,
             }];
 
             data.write_file(db, &mut GistID::ID(&gist.id), &files2)
@@ -539,7 +846,7 @@ pub mod tests {
                 gist.id
             );
             let preview = data
-                .gist_preview(db, &mut GistID::ID(&gist.id))
+                .gist_preview(db, &mut GistID::ID(&gist.id), None)
                 .await
                 .unwrap();
             assert_eq!(preview.owner, NAME);
@@ -553,6 +860,7 @@ pub mod tests {
                         .map(|f| FileInfo {
                             filename: escape_spaces(&f.filename),
                             content: f.content.clone(),
+                            mode: f.mode,
                         })
                         .collect();
 
@@ -561,6 +869,281 @@ pub mod tests {
                         .any(|f| f.filename == file.filename && f.content == file.content));
                 }
             }
+
+            // owner's gists
+            for viewer in [Some(NAME), None] {
+                let gists = data.get_user_gists(db, NAME, viewer).await.unwrap();
+                assert!(gists.iter().any(|g| g.public_id == gist.id));
+            }
+
+            // read gist at an older revision
+            let revisions = data
+                .gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                .await
+                .unwrap();
+            assert_eq!(preview.revision, revisions[0].id);
+            let first_rev = &revisions.last().unwrap().id;
+            let old_preview = data
+                .gist_preview(db, &mut GistID::ID(&gist.id), Some(&first_rev[..8]))
+                .await
+                .unwrap();
+            assert_eq!(&old_preview.revision, first_rev);
+            assert_eq!(old_preview.files.len(), files.len());
+            assert!(matches!(
+                data.read_file(db, &GistID::ID(&gist.id), "notfirstcommit", Some(first_rev))
+                    .await
+                    .err(),
+                Some(ServiceError::FileNotFound)
+            ));
+            for rev in ["zzzzzzzz", "000000000", "a"] {
+                assert!(matches!(
+                    data.gist_preview(db, &mut GistID::ID(&gist.id), Some(rev))
+                        .await
+                        .err(),
+                    Some(ServiceError::RevisionNotFound)
+                ));
+            }
+
+            // replace files: modify "foo", remove "bar" and "foo bar", rename "notfirstcommit"
+            let replacement = [
+                FileInfo {
+                    filename: "foo".into(),
+                    content: FileType::File(ContentType::Text("updated".into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "renamed".into(),
+                    content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
+                },
+            ];
+            assert!(data
+                .replace_files(db, &mut GistID::ID(&gist.id), &replacement)
+                .await
+                .unwrap());
+            let preview = data
+                .gist_preview(db, &mut GistID::ID(&gist.id), None)
+                .await
+                .unwrap();
+            assert_eq!(preview.files.len(), replacement.len());
+            data.gist_files_written_helper(db, &gist.id, &replacement)
+                .await;
+            assert!(preview.updated >= old_preview.updated);
+
+            // unchanged files don't create a new commit
+            assert!(!data
+                .replace_files(db, &mut GistID::ID(&gist.id), &replacement)
+                .await
+                .unwrap());
+            assert_eq!(
+                data.gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                    .await
+                    .unwrap()
+                    .len(),
+                revisions.len() + 1
+            );
+
+            assert_eq!(
+                data.replace_files(db, &mut GistID::ID(&gist.id), &[])
+                    .await
+                    .err(),
+                Some(ServiceError::GistEmpty)
+            );
+            let empty_name = [FileInfo {
+                filename: " ".into(),
+                content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                mode: FileMode::Regular,
+            }];
+            assert!(matches!(
+                data.replace_files(db, &mut GistID::ID(&gist.id), &empty_name)
+                    .await
+                    .err(),
+                Some(ServiceError::BadRequest(_))
+            ));
+
+            // missing repositories are reported as errors
+            let missing = "nonexistentgistrepository";
+            assert!(data
+                .write_file(db, &mut GistID::ID(missing), &replacement)
+                .await
+                .is_err());
+            assert!(data
+                .read_file(db, &GistID::ID(missing), "foo", None)
+                .await
+                .is_err());
+
+            // nested files are written to subdirectories
+            let nested = [
+                FileInfo {
+                    filename: "src/main.rs".into(),
+                    content: FileType::File(ContentType::Text("fn main() {}".into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "docs".into(),
+                    content: FileType::Dir(vec![FileInfo {
+                        filename: "guide/intro.md".into(),
+                        content: FileType::File(ContentType::Text("# intro".into())),
+                        mode: FileMode::Regular,
+                    }]),
+                    mode: FileMode::Regular,
+                },
+            ];
+            data.write_file(db, &mut GistID::ID(&gist.id), &nested)
+                .await
+                .unwrap();
+            let lib = [FileInfo {
+                filename: "src/lib.rs".into(),
+                content: FileType::File(ContentType::Text("pub fn lib() {}".into())),
+                mode: FileMode::Regular,
+            }];
+            data.write_file(db, &mut GistID::ID(&gist.id), &lib)
+                .await
+                .unwrap();
+            for (path, content) in [
+                ("src/main.rs", "fn main() {}"),
+                ("src/lib.rs", "pub fn lib() {}"),
+                ("docs/guide/intro.md", "# intro"),
+                ("foo", "updated"),
+            ] {
+                let file = data
+                    .read_file(db, &GistID::ID(&gist.id), path, None)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    file.content,
+                    FileType::File(ContentType::Text(content.into()))
+                );
+            }
+            let preview = data
+                .gist_preview(db, &mut GistID::ID(&gist.id), None)
+                .await
+                .unwrap();
+            let src = preview.files.iter().find(|f| f.filename == "src").unwrap();
+            assert!(matches!(&src.content, FileType::Dir(files) if files.len() == 2));
+            let paths: Vec<String> = preview
+                .files
+                .into_iter()
+                .flat_map(FileInfo::into_files)
+                .map(|f| f.filename)
+                .collect();
+            for path in [
+                "src/main.rs",
+                "src/lib.rs",
+                "docs/guide/intro.md",
+                "renamed",
+            ] {
+                assert!(paths.iter().any(|p| p == path));
+            }
+
+            for filename in ["../foo", "src//main.rs", "/foo", "src/./main.rs"] {
+                let invalid = [FileInfo {
+                    filename: filename.into(),
+                    content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
+                }];
+                assert!(matches!(
+                    data.write_file(db, &mut GistID::ID(&gist.id), &invalid)
+                        .await
+                        .err(),
+                    Some(ServiceError::BadRequest(_))
+                ));
+            }
+            let conflict = [
+                FileInfo {
+                    filename: "conflict".into(),
+                    content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "conflict/file".into(),
+                    content: FileType::File(ContentType::Text(FILE_CONTENT.into())),
+                    mode: FileMode::Regular,
+                },
+            ];
+            assert!(matches!(
+                data.replace_files(db, &mut GistID::ID(&gist.id), &conflict)
+                    .await
+                    .err(),
+                Some(ServiceError::BadRequest(_))
+            ));
+            assert_eq!(
+                data.write_file(
+                    db,
+                    &mut GistID::ID(&gist.id),
+                    &[FileInfo {
+                        filename: "empty".into(),
+                        content: FileType::Dir(Vec::default()),
+                        mode: FileMode::Regular,
+                    }]
+                )
+                .await
+                .err(),
+                Some(ServiceError::GistEmpty)
+            );
+
+            // file modes are preserved
+            let modes = [
+                FileInfo {
+                    filename: "run.sh".into(),
+                    content: FileType::File(ContentType::Text("echo foo".into())),
+                    mode: FileMode::Executable,
+                },
+                FileInfo {
+                    filename: "link".into(),
+                    content: FileType::File(ContentType::Text("src/main.rs".into())),
+                    mode: FileMode::Symlink,
+                },
+            ];
+            data.write_file(db, &mut GistID::ID(&gist.id), &modes)
+                .await
+                .unwrap();
+            data.gist_files_written_helper(db, &gist.id, &modes).await;
+            let repo = Repository::open(data.get_repository_path(&gist.id)).unwrap();
+            let tree = repo.head().unwrap().peel_to_tree().unwrap();
+            assert_eq!(tree.get_name("run.sh").unwrap().filemode(), 0o100755);
+            assert_eq!(tree.get_name("link").unwrap().filemode(), 0o120000);
+
+            // unsupported file modes are reported as errors
+            let mut tree_builder = repo.treebuilder(Some(&tree)).unwrap();
+            tree_builder
+                .insert("submodule", tree.get_name("run.sh").unwrap().id(), 0o160000)
+                .unwrap();
+            let tree_hash = tree_builder.write().unwrap();
+            commit_tree(&repo, tree_hash).unwrap();
+            assert_eq!(
+                data.read_file(db, &GistID::ID(&gist.id), "submodule", None)
+                    .await
+                    .err(),
+                Some(ServiceError::UnsupportedFileMode(0o160000))
+            );
+            assert_eq!(
+                data.gist_preview(db, &mut GistID::ID(&gist.id), None)
+                    .await
+                    .err(),
+                Some(ServiceError::UnsupportedFileMode(0o160000))
+            );
+
+            // delete gist: repository is restored when gist can't be deleted from DB
+            let path = data.get_repository_path(&gist.id);
+            assert_eq!(
+                data.delete_gist(db, "notowner", &gist.id).await.err(),
+                Some(ServiceError::GistNotFound)
+            );
+            assert!(path.exists());
+            data.delete_gist(db, NAME, &gist.id).await.unwrap();
+            assert!(!path.exists());
+            assert!(!db.gist_exists(&gist.id).await.unwrap());
+            assert!(!data
+                .get_repository_path(&format!(".{}.deleted", gist.id))
+                .exists());
+
+            // account deletion removes repositories of all gists
+            let gist = data.new_gist(db, &create_gist_msg).await.unwrap();
+            let path = data.get_repository_path(&gist.id);
+            assert!(path.exists());
+            data.delete_user(db, NAME, PASSWORD).await.unwrap();
+            assert!(!path.exists());
         }
     }
 }
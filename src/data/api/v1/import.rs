@@ -0,0 +1,475 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Import gists from git repositories and GitHub gist exports
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use db_core::prelude::*;
+use git2::{Repository, TreeWalkMode, TreeWalkResult};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::gists::{
+    commit_tree, fetch_history, flatten_tree_files, write_tree, ContentType, CreateGist, FileInfo,
+    FileMode, FileType, Gist, GistID, TreeFile,
+};
+use crate::errors::*;
+use crate::*;
+
+/// File in a GitHub gist export
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GitHubExportFile {
+    /// unset when GitHub truncated the file
+    pub content: Option<String>,
+}
+
+/// Gist metadata, as returned by the GitHub Gists API and saved by gist export tools
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GitHubExport {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub public: bool,
+    /// ISO 8601 date
+    pub created_at: String,
+    /// ISO 8601 date
+    pub updated_at: String,
+    #[serde(default)]
+    pub files: BTreeMap<String, GitHubExportFile>,
+}
+
+impl GitHubExport {
+    /// Read export from a JSON file
+    pub fn from_file(path: &Path) -> ServiceResult<Self> {
+        let export = std::fs::read(path)?;
+        serde_json::from_slice(&export)
+            .map_err(|e| ServiceError::BadRequest(format!("invalid GitHub gist export: {e}")))
+    }
+
+    /// Secret gists on GitHub are [GistVisibility::Unlisted]
+    pub fn visibility(&self) -> GistVisibility {
+        if self.public {
+            GistVisibility::Public
+        } else {
+            GistVisibility::Unlisted
+        }
+    }
+
+    /// Creation and last modified time of the gist, as UNIX timestamps
+    pub fn timestamps(&self) -> ServiceResult<(i64, i64)> {
+        let parse = |date: &str| -> ServiceResult<i64> {
+            OffsetDateTime::parse(date, &Rfc3339)
+                .map(|t| t.unix_timestamp())
+                .map_err(|_| ServiceError::BadRequest(format!("invalid date {date}")))
+        };
+        Ok((parse(&self.created_at)?, parse(&self.updated_at)?))
+    }
+
+    /// Files in the export. Exports that contain truncated files can't be imported without
+    /// the gist's repository.
+    fn to_files(&self) -> ServiceResult<Vec<FileInfo>> {
+        let mut files = Vec::with_capacity(self.files.len());
+        for (filename, file) in self.files.iter() {
+            let content = file.content.as_ref().ok_or_else(|| {
+                ServiceError::BadRequest(format!("content of {filename} is missing"))
+            })?;
+            files.push(FileInfo {
+                filename: filename.clone(),
+                content: FileType::File(ContentType::Text(content.clone())),
+                mode: FileMode::Regular,
+            });
+        }
+        Ok(files)
+    }
+}
+
+/// Gist that is to be imported
+pub struct ImportGist<'a> {
+    pub owner: &'a str,
+    /// git repository, bare or otherwise, whose history is imported. Files are read from
+    /// `export` when unset.
+    pub repository: Option<&'a Path>,
+    /// metadata of the gist on GitHub
+    pub export: Option<&'a GitHubExport>,
+    /// overrides description in `export`
+    pub description: Option<&'a str>,
+    /// overrides visibility in `export`. Gists that aren't from GitHub default to
+    /// [GistVisibility::Private].
+    pub visibility: Option<&'a GistVisibility>,
+}
+
+/// Commit the files in `export` to `repo`, dated at the time the gist was last modified
+fn commit_export(repo: &Repository, export: &GitHubExport, updated: i64) -> ServiceResult<()> {
+    let files = export.to_files()?;
+    let mut tree_files = Vec::with_capacity(files.len());
+    flatten_tree_files(&[], &files, &mut tree_files)?;
+    if tree_files.is_empty() {
+        return Err(ServiceError::GistEmpty);
+    }
+    let tree_files: Vec<&TreeFile> = tree_files.iter().collect();
+    let tree_hash = write_tree(repo, None, &tree_files, 0)?;
+    commit_tree(repo, tree_hash, Some(updated))
+}
+
+/// Check that all commits on the branches of `repo`, which are the ones that are imported,
+/// only contain files that GitPad can serve. Submodules, for instance, are rejected.
+fn check_history(repo: &Repository) -> ServiceResult<()> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_glob("refs/heads/*")?;
+    // trees are shared between commits, they are only checked once
+    let mut checked = HashSet::new();
+    for oid in revwalk {
+        let tree = repo.find_commit(oid?)?.tree()?;
+        if !checked.insert(tree.id()) {
+            continue;
+        }
+        let mut res = Ok(());
+        let walk = tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+            if let Err(e) = FileMode::try_from(entry) {
+                res = Err(e);
+                return TreeWalkResult::Abort;
+            }
+            if entry.kind() == Some(git2::ObjectType::Tree) && !checked.insert(entry.id()) {
+                return TreeWalkResult::Skip;
+            }
+            TreeWalkResult::Ok
+        });
+        res?;
+        walk?;
+    }
+    Ok(())
+}
+
+/// Creation and last modified time of the history reachable from HEAD: times of the
+/// oldest commit and of HEAD
+fn history_timestamps(repo: &Repository) -> ServiceResult<(i64, i64)> {
+    let updated = repo.head()?.peel_to_commit()?.time().seconds();
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    let mut created = updated;
+    for oid in revwalk {
+        created = created.min(repo.find_commit(oid?)?.time().seconds());
+    }
+    Ok((created, updated))
+}
+
+impl Data {
+    /// Import a gist into `owner`'s account. The full history of `repository` is
+    /// preserved, along with the timestamps of the gist on GitHub when `export` is set.
+    /// Timestamps are read from the history otherwise.
+    pub async fn import_gist<T: GPDatabse>(
+        &self,
+        db: &T,
+        msg: &ImportGist<'_>,
+    ) -> ServiceResult<Gist> {
+        if msg.repository.is_none() && msg.export.is_none() {
+            return Err(ServiceError::BadRequest(
+                "a repository or a GitHub gist export is required to import a gist".into(),
+            ));
+        }
+        let export_timestamps = msg.export.map(|e| e.timestamps()).transpose()?;
+        if let Some(path) = msg.repository {
+            check_history(&Repository::open(path)?)?;
+        }
+
+        let description = msg
+            .description
+            .or_else(|| msg.export.and_then(|e| e.description.as_deref()))
+            .map(|d| d.trim())
+            .filter(|d| !d.is_empty());
+        let visibility = match (msg.visibility, msg.export) {
+            (Some(visibility), _) => visibility.clone(),
+            (None, Some(export)) => export.visibility(),
+            (None, None) => GistVisibility::Private,
+        };
+        let create_gist = CreateGist {
+            owner: msg.owner,
+            description,
+            visibility: &visibility,
+        };
+        let mut gist = self.new_gist(db, &create_gist).await?;
+
+        let import = |repo: &Repository| -> ServiceResult<(i64, i64)> {
+            match (msg.repository, msg.export) {
+                (Some(path), _) => fetch_history(repo, path)?,
+                (None, Some(export)) => commit_export(repo, export, export_timestamps.unwrap().1)?,
+                (None, None) => unreachable!(),
+            };
+            match repo.head() {
+                Ok(_) => (),
+                Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                    return Err(ServiceError::GistEmpty)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let timestamps = history_timestamps(repo)?;
+            Ok(export_timestamps.unwrap_or(timestamps))
+        };
+
+        let res = match import(&gist.repository) {
+            // files with modes that GitPad can't serve, like submodules, are rejected
+            Ok(timestamps) => self
+                .gist_preview(db, &mut GistID::Repository(&mut gist.repository), None)
+                .await
+                .map(|_| timestamps),
+            Err(e) => Err(e),
+        };
+        let (created, updated) = match res {
+            Ok(timestamps) => timestamps,
+            Err(e) => {
+                self.delete_gist(db, msg.owner, &gist.id).await?;
+                return Err(e);
+            }
+        };
+
+        self.index_gist(db, &gist.id).await?;
+        db.set_gist_timestamps(&gist.id, created, updated).await?;
+        Ok(gist)
+    }
+
+    /// Resolve `path`, relative to the directory that repositories can be imported from
+    /// through the API. Paths that lead outside of the directory are rejected.
+    pub fn get_import_path(&self, path: &str) -> ServiceResult<PathBuf> {
+        let root = self
+            .settings
+            .repository
+            .import_root
+            .as_ref()
+            .ok_or(ServiceError::ImportDisabled)?;
+        let not_found = || ServiceError::BadRequest(format!("repository {path} not found"));
+        let root = Path::new(root).canonicalize().map_err(|_| not_found())?;
+        let path = root.join(path).canonicalize().map_err(|_| not_found())?;
+        if !path.starts_with(&root) {
+            return Err(not_found());
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::data::api::v1::gists::resolve_revision;
+    use crate::tests::*;
+    use crate::utils::get_random;
+
+    /// Run git in `dir`, with commits dated at `time`
+    fn git(dir: &Path, time: i64, args: &[&str]) {
+        let date = format!("{time} +0000");
+        let output = Command::new("git")
+            .args([
+                "-c",
+                "user.name=gitpad",
+                "-c",
+                "user.email=gitpad@example.com",
+            ])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn github_export_works() {
+        let export: GitHubExport = serde_json::from_str(
+            r#"{
+                "description": "foo",
+                "public": true,
+                "created_at": "2022-03-10T09:35:22Z",
+                "updated_at": "2022-03-11T09:35:22Z",
+                "files": {
+                    "foo.md": { "filename": "foo.md", "content": "foo" },
+                    "bar.md": { "filename": "bar.md", "content": "bar" }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(export.visibility(), GistVisibility::Public);
+        assert_eq!(export.timestamps().unwrap(), (1646904922, 1646991322));
+        let files = export.to_files().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].filename, "bar.md");
+
+        let mut export = export;
+        export.created_at = "yesterday".into();
+        assert!(matches!(
+            export.timestamps().err(),
+            Some(ServiceError::BadRequest(_))
+        ));
+        export
+            .files
+            .insert("truncated.md".into(), GitHubExportFile { content: None });
+        assert!(matches!(
+            export.to_files().err(),
+            Some(ServiceError::BadRequest(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn import_gist_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "importtestuser";
+            const EMAIL: &str = "importtestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            // non-bare repository with two commits
+            let source = std::env::temp_dir().join(format!("gitpad-import-{}", get_random(10)));
+            std::fs::create_dir(&source).unwrap();
+            git(&source, 0, &["init", "-q"]);
+            std::fs::write(source.join("foo.md"), "foo").unwrap();
+            git(&source, 1000, &["add", "."]);
+            git(&source, 1000, &["commit", "-q", "-m", "first"]);
+            std::fs::write(source.join("foo.md"), "bar").unwrap();
+            git(&source, 2000, &["commit", "-q", "-am", "second"]);
+
+            let mut msg = ImportGist {
+                owner: NAME,
+                repository: Some(&source),
+                export: None,
+                description: Some("imported"),
+                visibility: None,
+            };
+            let gist = data.import_gist(db, &msg).await.unwrap();
+            let info = db.get_gist(&gist.id).await.unwrap();
+            assert_eq!(info.owner, NAME);
+            assert_eq!(info.description.as_deref(), Some("imported"));
+            assert_eq!(info.visibility, GistVisibility::Private);
+            assert_eq!((info.created, info.updated), (1000, 2000));
+            let revisions = data
+                .gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                .await
+                .unwrap();
+            assert_eq!(revisions.len(), 2);
+            let file = data
+                .read_file(db, &GistID::ID(&gist.id), "foo.md", None)
+                .await
+                .unwrap();
+            assert_eq!(
+                file.content,
+                FileType::File(ContentType::Text("bar".into()))
+            );
+
+            // timestamps and metadata of GitHub gists are preserved
+            let export = GitHubExport {
+                description: Some("from github".into()),
+                public: false,
+                created_at: "2022-03-10T09:35:22Z".into(),
+                updated_at: "2022-03-11T09:35:22Z".into(),
+                files: BTreeMap::default(),
+            };
+            msg.description = None;
+            msg.export = Some(&export);
+            let gist = data.import_gist(db, &msg).await.unwrap();
+            let info = db.get_gist(&gist.id).await.unwrap();
+            assert_eq!(info.description.as_deref(), Some("from github"));
+            assert_eq!(info.visibility, GistVisibility::Unlisted);
+            assert_eq!((info.created, info.updated), (1646904922, 1646991322));
+            assert_eq!(
+                data.gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+
+            // files are read from the export when there's no repository
+            let mut export = export.clone();
+            export.files.insert(
+                "hello.md".into(),
+                GitHubExportFile {
+                    content: Some("hello".into()),
+                },
+            );
+            msg.repository = None;
+            msg.export = Some(&export);
+            msg.visibility = Some(&GistVisibility::Public);
+            let gist = data.import_gist(db, &msg).await.unwrap();
+            let info = db.get_gist(&gist.id).await.unwrap();
+            assert_eq!(info.visibility, GistVisibility::Public);
+            let repo = Repository::open(data.get_repository_path(&gist.id)).unwrap();
+            let head = resolve_revision(&repo, None).unwrap();
+            assert_eq!(head.time().seconds(), 1646991322);
+            let file = data
+                .read_file(db, &GistID::ID(&gist.id), "hello.md", None)
+                .await
+                .unwrap();
+            assert_eq!(
+                file.content,
+                FileType::File(ContentType::Text("hello".into()))
+            );
+
+            // empty repositories can't be imported, and failed imports are cleaned up
+            let gists = db.get_user_gists(NAME).await.unwrap().len();
+
+            // unsupported files are rejected even when they were removed in later commits
+            let submodule = std::env::temp_dir().join(format!("gitpad-import-{}", get_random(10)));
+            std::fs::create_dir(&submodule).unwrap();
+            git(&submodule, 0, &["init", "-q"]);
+            let gitlink = format!("160000,{},sub", "1".repeat(40));
+            git(
+                &submodule,
+                1000,
+                &["update-index", "--add", "--cacheinfo", &gitlink],
+            );
+            git(&submodule, 1000, &["commit", "-q", "-m", "first"]);
+            git(&submodule, 2000, &["rm", "-q", "--cached", "sub"]);
+            std::fs::write(submodule.join("foo.md"), "foo").unwrap();
+            git(&submodule, 2000, &["add", "foo.md"]);
+            git(&submodule, 2000, &["commit", "-q", "-m", "second"]);
+            msg.repository = Some(&submodule);
+            msg.export = None;
+            assert!(matches!(
+                data.import_gist(db, &msg).await.err(),
+                Some(ServiceError::UnsupportedFileMode(_))
+            ));
+            assert_eq!(db.get_user_gists(NAME).await.unwrap().len(), gists);
+            std::fs::remove_dir_all(&submodule).unwrap();
+
+            let empty = std::env::temp_dir().join(format!("gitpad-import-{}", get_random(10)));
+            std::fs::create_dir(&empty).unwrap();
+            git(&empty, 0, &["init", "-q", "--bare"]);
+            msg.repository = Some(&empty);
+            msg.export = None;
+            assert_eq!(
+                data.import_gist(db, &msg).await.err(),
+                Some(ServiceError::GistEmpty)
+            );
+            assert_eq!(db.get_user_gists(NAME).await.unwrap().len(), gists);
+
+            msg.repository = None;
+            assert!(matches!(
+                data.import_gist(db, &msg).await.err(),
+                Some(ServiceError::BadRequest(_))
+            ));
+
+            std::fs::remove_dir_all(&source).unwrap();
+            std::fs::remove_dir_all(&empty).unwrap();
+        }
+    }
+}
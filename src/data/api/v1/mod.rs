@@ -15,8 +15,21 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 pub mod account;
+pub mod archive;
 pub mod auth;
+pub mod backup;
+pub mod diff;
+pub mod explore;
+pub mod export;
+pub mod forks;
+pub mod fsck;
 pub mod gists;
+pub mod import;
+pub mod raw;
 pub mod render_html;
+pub mod revisions;
+pub mod search;
+pub mod stars;
+pub mod tokens;
 
 pub(crate) use crate::utils::get_random;
@@ -0,0 +1,233 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Raw gist files
+use std::path::Path;
+
+use git2::{ErrorCode, ObjectType, Repository};
+use mime::Mime;
+
+use super::gists::{resolve_revision, FileMode};
+use crate::errors::*;
+use crate::*;
+
+/// Media types that browsers execute or render as documents. Files of these types are
+/// served as plain text so that gist contents can't run scripts on the gitpad origin.
+const ACTIVE_CONTENT: [&str; 4] = [
+    "text/html",
+    "application/xhtml+xml",
+    "image/svg+xml",
+    "text/xml",
+];
+
+/// Contents of a single file in a gist, as stored in the repository
+pub struct RawFile {
+    /// name of the file, without the directories leading up to it
+    pub filename: String,
+    /// ID of the blob that holds the file. Contents of a blob never change, so this is
+    /// suitable for use as an entity tag.
+    pub oid: String,
+    pub mode: FileMode,
+    /// git considers the file to be binary
+    pub is_binary: bool,
+    pub content: Vec<u8>,
+}
+
+impl RawFile {
+    /// media type of the file, guessed from its extension
+    pub fn content_type(&self) -> Mime {
+        if self.mode == FileMode::Symlink {
+            return mime::TEXT_PLAIN_UTF_8;
+        }
+        let guess = mime_guess::from_path(&self.filename).first();
+        if self.is_binary {
+            return guess.unwrap_or(mime::APPLICATION_OCTET_STREAM);
+        }
+        match guess {
+            Some(m) if ACTIVE_CONTENT.contains(&m.essence_str()) => mime::TEXT_PLAIN_UTF_8,
+            Some(m) if m.type_() == mime::TEXT && m.get_param(mime::CHARSET).is_none() => {
+                format!("{m}; charset=utf-8").parse().unwrap_or(m)
+            }
+            Some(m) => m,
+            None => mime::TEXT_PLAIN_UTF_8,
+        }
+    }
+
+    /// whether browsers should display the file in place rather than download it.
+    /// Text files and media that can be embedded in pages are displayed.
+    pub fn is_inline(&self) -> bool {
+        if !self.is_binary {
+            return true;
+        }
+        let content_type = self.content_type();
+        matches!(
+            content_type.type_(),
+            mime::IMAGE | mime::AUDIO | mime::VIDEO
+        ) && !ACTIVE_CONTENT.contains(&content_type.essence_str())
+    }
+}
+
+impl Data {
+    /// Read file at `path` in a gist at revision `rev`(HEAD when `rev` is `None`).
+    /// Directories can't be read raw, [ServiceError::FileNotFound] is returned for them.
+    ///
+    /// Like [Data::read_file], `path` is expected to not contain any spaces.
+    pub fn read_raw_file(
+        &self,
+        public_id: &str,
+        path: &str,
+        rev: Option<&str>,
+    ) -> ServiceResult<RawFile> {
+        let repo = Repository::open(self.get_repository_path(public_id))?;
+        let tree = resolve_revision(&repo, rev)?.tree()?;
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(e) if e.code() == ErrorCode::NotFound => return Err(ServiceError::FileNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        if entry.kind() != Some(ObjectType::Blob) {
+            return Err(ServiceError::FileNotFound);
+        }
+        let mode = FileMode::try_from(&entry)?;
+        let blob = repo.find_blob(entry.id())?;
+        Ok(RawFile {
+            filename: entry.name().unwrap_or_default().to_owned(),
+            oid: blob.id().to_string(),
+            mode,
+            is_binary: blob.is_binary(),
+            content: blob.content().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use db_core::prelude::*;
+
+    use super::*;
+    use crate::data::api::v1::gists::{ContentType, CreateGist, FileInfo, FileType, GistID};
+    use crate::tests::*;
+
+    fn raw_file(filename: &str, is_binary: bool) -> RawFile {
+        RawFile {
+            filename: filename.into(),
+            oid: String::default(),
+            mode: FileMode::Regular,
+            is_binary,
+            content: Vec::default(),
+        }
+    }
+
+    #[test]
+    fn raw_content_type_works() {
+        let file = raw_file("main.rs", false);
+        assert_eq!(file.content_type().essence_str(), "text/x-rust");
+        assert_eq!(
+            file.content_type().get_param(mime::CHARSET),
+            Some(mime::UTF_8)
+        );
+        assert!(file.is_inline());
+
+        let file = raw_file("no-extension", false);
+        assert_eq!(file.content_type(), mime::TEXT_PLAIN_UTF_8);
+
+        let file = raw_file("index.html", false);
+        assert_eq!(file.content_type(), mime::TEXT_PLAIN_UTF_8);
+        let file = raw_file("logo.svg", false);
+        assert_eq!(file.content_type(), mime::TEXT_PLAIN_UTF_8);
+
+        let file = raw_file("logo.png", true);
+        assert_eq!(file.content_type(), mime::IMAGE_PNG);
+        assert!(file.is_inline());
+
+        let file = raw_file("release.tar", true);
+        assert_eq!(file.content_type().essence_str(), "application/x-tar");
+        assert!(!file.is_inline());
+
+        let file = raw_file("blob", true);
+        assert_eq!(file.content_type(), mime::APPLICATION_OCTET_STREAM);
+        assert!(!file.is_inline());
+    }
+
+    #[actix_rt::test]
+    async fn test_read_raw_file_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "rawfiletestuser";
+            const EMAIL: &str = "rawfiletestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let msg = CreateGist {
+                owner: NAME,
+                description: None,
+                visibility: &GistVisibility::Public,
+            };
+            let mut gist = data.new_gist(db, &msg).await.unwrap();
+            let png = vec![0x89, b'P', b'N', b'G', 0, 0, 0, 0];
+            let files = [
+                FileInfo {
+                    filename: "src/main.rs".into(),
+                    content: FileType::File(ContentType::Text("fn main() {}".into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "logo.png".into(),
+                    content: FileType::File(ContentType::Binary(png.clone())),
+                    mode: FileMode::Regular,
+                },
+            ];
+            data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                .await
+                .unwrap();
+
+            let main = data.read_raw_file(&gist.id, "src/main.rs", None).unwrap();
+            assert_eq!(main.filename, "main.rs");
+            assert_eq!(main.content, b"fn main() {}");
+            assert!(!main.is_binary);
+            assert_eq!(
+                main.oid,
+                git2::Oid::hash_object(ObjectType::Blob, b"fn main() {}")
+                    .unwrap()
+                    .to_string()
+            );
+
+            let logo = data.read_raw_file(&gist.id, "logo.png", None).unwrap();
+            assert!(logo.is_binary);
+            assert_eq!(logo.content, png);
+            assert_eq!(logo.content_type(), mime::IMAGE_PNG);
+
+            assert_eq!(
+                data.read_raw_file(&gist.id, "src", None).err(),
+                Some(ServiceError::FileNotFound)
+            );
+            assert_eq!(
+                data.read_raw_file(&gist.id, "nonexistent", None).err(),
+                Some(ServiceError::FileNotFound)
+            );
+            assert_eq!(
+                data.read_raw_file(&gist.id, "logo.png", Some("zzzz")).err(),
+                Some(ServiceError::RevisionNotFound)
+            );
+        }
+    }
+}
@@ -17,9 +17,13 @@
 use std::path::Path;
 
 use pulldown_cmark::{html, Options, Parser};
+use syntect::easy::HighlightLines;
 use syntect::highlighting::{Color, ThemeSet};
-use syntect::html::highlighted_html_for_string;
+use syntect::html::{
+    highlighted_html_for_string, styled_line_to_highlighted_html, IncludeBackground,
+};
 use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
 
 use crate::errors::*;
 
@@ -93,6 +97,25 @@ impl<'a> SourcegraphQuery<'a> {
         output
     }
 
+    /// Syntax highlight code and return highlighted HTML of each line separately.
+    /// Unlike [syntax_highlight](Self::syntax_highlight), line numbers
+    /// and other decorations aren't added.
+    pub fn highlight_lines(&self) -> Vec<String> {
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["InspiredGitHub"];
+
+        SYNTAX_SET.with(|ss| {
+            let language = self.determine_language(ss).unwrap();
+            let mut highlighter = HighlightLines::new(language, theme);
+            LinesWithEndings::from(self.code)
+                .map(|line| {
+                    let regions = highlighter.highlight(line, ss);
+                    styled_line_to_highlighted_html(&regions, IncludeBackground::No)
+                })
+                .collect()
+        })
+    }
+
     // adopted from
     // https://github.com/sourcegraph/sourcegraph/blob/9fe138ae75fd64dce06b621572b252a9c9c8da70/docker-images/syntax-highlighter/crates/sg-syntax/src/lib.rs#L81
     // with minimum modifications. Crate was MIT licensed at the time(2022-03-12 11:11)
@@ -177,6 +200,18 @@ mod tests {
         let _result = query.syntax_highlight();
     }
 
+    #[test]
+    fn highlight_lines_works() {
+        let query = SourcegraphQuery {
+            filepath: "foo.rs",
+            code: "fn main() {\n    println!(\"hello\");\n}",
+        };
+        let lines = query.highlight_lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("main"));
+        assert!(lines[2].contains('}'));
+    }
+
     #[test]
     // renders markdown file into HTML
     fn markdown_render_works() {
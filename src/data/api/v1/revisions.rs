@@ -0,0 +1,267 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Gist revision history
+use db_core::prelude::*;
+use git2::{
+    Commit, Delta, DiffDelta, DiffFindOptions, ErrorClass, ErrorCode, Patch, Repository, Sort,
+};
+use serde::{Deserialize, Serialize};
+
+use super::gists::GistID;
+use crate::errors::*;
+use crate::*;
+
+/// Kind of change a revision made to a file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+impl From<Delta> for ChangeType {
+    fn from(d: Delta) -> Self {
+        match d {
+            Delta::Added | Delta::Copied => ChangeType::Added,
+            Delta::Deleted => ChangeType::Deleted,
+            Delta::Renamed => ChangeType::Renamed,
+            _ => ChangeType::Modified,
+        }
+    }
+}
+
+/// Get name of the file that `delta` describes. Previous name of the file is
+/// also returned when `delta` is a rename.
+pub(crate) fn delta_filenames(delta: &DiffDelta) -> (String, Option<String>) {
+    let path_str = |f: git2::DiffFile| {
+        f.path()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    match delta.status().into() {
+        ChangeType::Deleted => (path_str(delta.old_file()), None),
+        ChangeType::Renamed => (path_str(delta.new_file()), Some(path_str(delta.old_file()))),
+        _ => (path_str(delta.new_file()), None),
+    }
+}
+
+/// File changed in a revision
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangedFile {
+    pub filename: String,
+    /// previous name of the file, set only when the file was renamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_filename: Option<String>,
+    pub change: ChangeType,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// A single commit in a gist's history
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// commit ID
+    pub id: String,
+    /// commit time, in seconds since unix epoch
+    pub timestamp: i64,
+    pub files: Vec<ChangedFile>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl Revision {
+    fn from_commit(repo: &Repository, commit: &Commit) -> ServiceResult<Self> {
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+
+        let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let mut files = Vec::with_capacity(diff.deltas().len());
+        let mut additions = 0;
+        let mut deletions = 0;
+        for (idx, delta) in diff.deltas().enumerate() {
+            let (file_additions, file_deletions) = match Patch::from_diff(&diff, idx)? {
+                Some(patch) => {
+                    let (_, a, d) = patch.line_stats()?;
+                    (a, d)
+                }
+                None => (0, 0),
+            };
+            additions += file_additions;
+            deletions += file_deletions;
+
+            let change: ChangeType = delta.status().into();
+            let (filename, old_filename) = delta_filenames(&delta);
+
+            files.push(ChangedFile {
+                filename,
+                old_filename,
+                change,
+                additions: file_additions,
+                deletions: file_deletions,
+            });
+        }
+
+        Ok(Self {
+            id: commit.id().to_string(),
+            timestamp: commit.time().seconds(),
+            files,
+            additions,
+            deletions,
+        })
+    }
+}
+
+impl Data {
+    /// Walk commit history of a gist, newest first. Returns `limit` revisions,
+    /// skipping the first `page * limit` revisions.
+    pub async fn gist_revisions<T: GPDatabse>(
+        &self,
+        _db: &T,
+        gist_id: &GistID<'_>,
+        page: usize,
+        limit: usize,
+    ) -> ServiceResult<Vec<Revision>> {
+        let inner = |repo: &Repository| -> ServiceResult<Vec<Revision>> {
+            if let Err(e) = repo.head() {
+                if e.code() == ErrorCode::UnbornBranch && e.class() == ErrorClass::Reference {
+                    // gist doesn't have any commits yet
+                    return Ok(Vec::default());
+                }
+                return Err(e.into());
+            }
+
+            let mut revwalk = repo.revwalk()?;
+            revwalk.push_head()?;
+            revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+            let mut revisions = Vec::with_capacity(limit);
+            for oid in revwalk.skip(page * limit).take(limit) {
+                let commit = repo.find_commit(oid?)?;
+                revisions.push(Revision::from_commit(repo, &commit)?);
+            }
+            Ok(revisions)
+        };
+
+        match gist_id {
+            GistID::ID(path) => {
+                let repo = git2::Repository::open(self.get_repository_path(path))?;
+                inner(&repo)
+            }
+            GistID::Repository(repository) => inner(repository),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::api::v1::gists::{ContentType, CreateGist, FileInfo, FileMode, FileType};
+    use crate::tests::*;
+
+    #[actix_rt::test]
+    async fn test_gist_revisions_work() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "revisiontestuser";
+            const EMAIL: &str = "revisiontestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            let create_gist_msg = CreateGist {
+                owner: NAME,
+                description: None,
+                visibility: &GistVisibility::Public,
+            };
+            let mut gist = data.new_gist(db, &create_gist_msg).await.unwrap();
+            assert!(data
+                .gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                .await
+                .unwrap()
+                .is_empty());
+
+            let files = [
+                FileInfo {
+                    filename: "foo".into(),
+                    content: FileType::File(ContentType::Text("a\nb\n".into())),
+                    mode: FileMode::Regular,
+                },
+                FileInfo {
+                    filename: "bar".into(),
+                    content: FileType::File(ContentType::Text("a\n".into())),
+                    mode: FileMode::Regular,
+                },
+            ];
+            data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                .await
+                .unwrap();
+
+            let files2 = [FileInfo {
+                filename: "foo".into(),
+                content: FileType::File(ContentType::Text("a\nc\nd\n".into())),
+                mode: FileMode::Regular,
+            }];
+            data.write_file(db, &mut GistID::ID(&gist.id), &files2)
+                .await
+                .unwrap();
+
+            let revisions = data
+                .gist_revisions(db, &GistID::ID(&gist.id), 0, 10)
+                .await
+                .unwrap();
+            assert_eq!(revisions.len(), 2);
+
+            // newest first
+            let latest = &revisions[0];
+            assert_eq!(latest.files.len(), 1);
+            assert_eq!(latest.files[0].filename, "foo");
+            assert_eq!(latest.files[0].change, ChangeType::Modified);
+            assert_eq!(latest.additions, 2);
+            assert_eq!(latest.deletions, 1);
+
+            let first = &revisions[1];
+            assert_eq!(first.files.len(), 2);
+            assert!(first.files.iter().all(|f| f.change == ChangeType::Added));
+            assert_eq!(first.additions, 3);
+            assert_eq!(first.deletions, 0);
+
+            // pagination
+            let page = data
+                .gist_revisions(db, &GistID::ID(&gist.id), 1, 1)
+                .await
+                .unwrap();
+            assert_eq!(page, vec![first.clone()]);
+            assert!(data
+                .gist_revisions(db, &GistID::ID(&gist.id), 1, 2)
+                .await
+                .unwrap()
+                .is_empty());
+        }
+    }
+}
@@ -0,0 +1,257 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Gist search
+use db_core::prelude::*;
+use git2::{ErrorCode, ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+
+use crate::errors::*;
+use crate::*;
+
+/// Default number of search results per page
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+/// Maximum number of search results per page
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// Maximum number of bytes of file contents that are indexed per gist
+pub const MAX_INDEXED_CONTENT: usize = 512 * 1024;
+
+/// Text extracted from a gist for indexing
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchText {
+    /// paths of files in the gist, separated by newlines
+    pub filenames: String,
+    /// contents of text files in the gist, capped at [MAX_INDEXED_CONTENT] bytes
+    pub content: String,
+}
+
+impl SearchText {
+    /// Extract filenames and text file contents from HEAD of `repo`. Binary files are
+    /// indexed by name only.
+    pub fn from_repository(repo: &Repository) -> ServiceResult<Self> {
+        let mut text = Self::default();
+        let tree = match repo.head() {
+            Ok(head) => head.peel_to_tree()?,
+            // gist doesn't have any commits yet
+            Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(text),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut res = Ok(());
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() != Some(ObjectType::Blob) {
+                return TreeWalkResult::Ok;
+            }
+            let name = match entry.name() {
+                Some(name) => name,
+                None => return TreeWalkResult::Ok,
+            };
+            text.filenames.push_str(dir);
+            text.filenames.push_str(name);
+            text.filenames.push('\n');
+
+            let blob = match repo.find_blob(entry.id()) {
+                Ok(blob) => blob,
+                Err(e) => {
+                    res = Err(e);
+                    return TreeWalkResult::Abort;
+                }
+            };
+            if blob.is_binary() {
+                return TreeWalkResult::Ok;
+            }
+            if let Ok(content) = std::str::from_utf8(blob.content()) {
+                text.push_content(content);
+            }
+            TreeWalkResult::Ok
+        })?;
+        res?;
+        Ok(text)
+    }
+
+    /// Append `content`, truncating it to stay within [MAX_INDEXED_CONTENT]
+    fn push_content(&mut self, content: &str) {
+        // room for at least one byte of content and the trailing newline
+        let available = MAX_INDEXED_CONTENT.saturating_sub(self.content.len() + 1);
+        if available == 0 {
+            return;
+        }
+        let mut end = content.len().min(available);
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.content.push_str(&content[..end]);
+        self.content.push('\n');
+    }
+}
+
+impl Data {
+    /// Index HEAD of a gist for search. Called each time a commit is added to a gist.
+    pub async fn index_gist<T: GPDatabse>(&self, db: &T, public_id: &str) -> ServiceResult<()> {
+        let repo = Repository::open(self.get_repository_path(public_id))?;
+        let text = SearchText::from_repository(&repo)?;
+        let doc = GistSearchDocument {
+            public_id,
+            filenames: &text.filenames,
+            content: &text.content,
+        };
+        db.index_gist(&doc).await?;
+        Ok(())
+    }
+
+    /// Rebuild search index of all gists, after the database is restored or copied from
+    /// another database. Gists with missing repositories are skipped. Returns number of
+    /// indexed gists.
+    pub async fn reindex_gists<T: GPDatabse>(&self, db: &T) -> ServiceResult<usize> {
+        let mut indexed = 0;
+        for gist in db.get_all_gists().await?.iter() {
+            if !self.get_repository_path(&gist.public_id).exists() {
+                log::warn!("repository of gist {} is missing", gist.public_id);
+                continue;
+            }
+            self.index_gist(db, &gist.public_id).await?;
+            indexed += 1;
+        }
+        Ok(indexed)
+    }
+
+    /// Search gists that `viewer` can see. Private and unlisted gists are only returned to
+    /// their owners.
+    pub async fn search_gists<T: GPDatabse>(
+        &self,
+        db: &T,
+        query: &str,
+        viewer: Option<&str>,
+        page: usize,
+        limit: usize,
+    ) -> ServiceResult<Vec<Gist>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::default());
+        }
+        let gists = db
+            .search_gists(query, viewer, (page * limit) as u32, limit as u32)
+            .await?;
+        Ok(gists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::api::v1::gists::{
+        ContentType, CreateGist, FileInfo, FileMode, FileType, GistID,
+    };
+    use crate::tests::*;
+
+    #[test]
+    fn content_is_capped() {
+        let mut text = SearchText::default();
+        text.push_content("foo");
+        assert_eq!(text.content, "foo\n");
+        let long = "é".repeat(MAX_INDEXED_CONTENT);
+        text.push_content(&long);
+        assert!(text.content.len() <= MAX_INDEXED_CONTENT);
+        let len = text.content.len();
+        text.push_content("bar");
+        assert_eq!(text.content.len(), len);
+    }
+
+    #[actix_rt::test]
+    async fn test_gist_search_works() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "searchtestuser";
+            const NAME2: &str = "searchtestuser2";
+            const EMAIL: &str = "searchtestuser@sss.com";
+            const EMAIL2: &str = "searchtestuser2@sss.com";
+            const PASSWORD: &str = "longpassword2";
+            const TERM: &str = "quokkaharpsichord";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.delete_user(db, NAME2, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+
+            let mut gists = Vec::default();
+            for visibility in [
+                GistVisibility::Public,
+                GistVisibility::Unlisted,
+                GistVisibility::Private,
+            ] {
+                let msg = CreateGist {
+                    owner: NAME,
+                    description: None,
+                    visibility: &visibility,
+                };
+                let mut gist = data.new_gist(db, &msg).await.unwrap();
+                let files = [FileInfo {
+                    filename: "notes.md".into(),
+                    content: FileType::File(ContentType::Text(format!("some {TERM} notes"))),
+                    mode: FileMode::Regular,
+                }];
+                data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                    .await
+                    .unwrap();
+                gists.push(gist.id);
+            }
+
+            // gists are indexed on commit
+            let results = data
+                .search_gists(db, TERM, Some(NAME), 0, 10)
+                .await
+                .unwrap();
+            assert_eq!(results.len(), 3);
+            for viewer in [None, Some(NAME2)] {
+                let results = data.search_gists(db, TERM, viewer, 0, 10).await.unwrap();
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].public_id, gists[0]);
+            }
+            let results = data.search_gists(db, TERM, Some(NAME), 1, 2).await.unwrap();
+            assert_eq!(results.len(), 1);
+            assert!(data
+                .search_gists(db, "   ", None, 0, 10)
+                .await
+                .unwrap()
+                .is_empty());
+
+            // index is updated when files are replaced
+            let files = [FileInfo {
+                filename: "wombatregister.md".into(),
+                content: FileType::File(ContentType::Text("nothing to see here".into())),
+                mode: FileMode::Regular,
+            }];
+            data.replace_files(db, &mut GistID::ID(&gists[0]), &files)
+                .await
+                .unwrap();
+            assert!(data
+                .search_gists(db, TERM, None, 0, 10)
+                .await
+                .unwrap()
+                .is_empty());
+            let results = data
+                .search_gists(db, "wombatregister", None, 0, 10)
+                .await
+                .unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].public_id, gists[0]);
+        }
+    }
+}
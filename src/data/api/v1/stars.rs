@@ -0,0 +1,160 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Gist stars
+use db_core::prelude::*;
+
+use crate::errors::*;
+use crate::*;
+
+impl Data {
+    /// Star a gist on behalf of `username`. Private gists can only be starred by their owner.
+    pub async fn star_gist<T: GPDatabse>(
+        &self,
+        db: &T,
+        username: &str,
+        public_id: &str,
+    ) -> ServiceResult<()> {
+        let gist = db.get_gist(public_id).await?;
+        if gist.visibility == GistVisibility::Private && gist.owner != username {
+            return Err(ServiceError::GistNotFound);
+        }
+        db.star_gist(username, public_id).await?;
+        Ok(())
+    }
+
+    /// Get gists starred by `username` that `viewer` can see. Users see all of their starred
+    /// gists that they can read, everyone else only sees public ones.
+    pub async fn get_starred_gists<T: GPDatabse>(
+        &self,
+        db: &T,
+        username: &str,
+        viewer: Option<&str>,
+    ) -> ServiceResult<Vec<Gist>> {
+        let mut gists = db.get_starred_gists(username).await?;
+        if viewer == Some(username) {
+            gists.retain(|g| g.visibility != GistVisibility::Private || g.owner == username);
+        } else {
+            gists.retain(|g| g.visibility == GistVisibility::Public);
+        }
+        Ok(gists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::api::v1::gists::{
+        ContentType, CreateGist, FileInfo, FileMode, FileType, GistID,
+    };
+    use crate::tests::*;
+
+    #[actix_rt::test]
+    async fn test_gist_stars_work() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "startestuser";
+            const NAME2: &str = "startestuser2";
+            const EMAIL: &str = "startestuser@sss.com";
+            const EMAIL2: &str = "startestuser2@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.delete_user(db, NAME2, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+
+            let mut gists = Vec::default();
+            for visibility in [
+                GistVisibility::Public,
+                GistVisibility::Unlisted,
+                GistVisibility::Private,
+            ] {
+                let msg = CreateGist {
+                    owner: NAME,
+                    description: None,
+                    visibility: &visibility,
+                };
+                let mut gist = data.new_gist(db, &msg).await.unwrap();
+                let files = [FileInfo {
+                    filename: "foo".into(),
+                    content: FileType::File(ContentType::Text("foo".into())),
+                    mode: FileMode::Regular,
+                }];
+                data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+                    .await
+                    .unwrap();
+                gists.push(gist.id);
+            }
+
+            // private gists can only be starred by their owner
+            assert_eq!(
+                data.star_gist(db, NAME2, &gists[2]).await.err(),
+                Some(ServiceError::GistNotFound)
+            );
+            assert_eq!(
+                data.star_gist(db, NAME, "nonexistentgist").await.err(),
+                Some(ServiceError::GistNotFound)
+            );
+            for gist in gists.iter() {
+                data.star_gist(db, NAME, gist).await.unwrap();
+            }
+            for gist in gists.iter().take(2) {
+                data.star_gist(db, NAME2, gist).await.unwrap();
+            }
+
+            for (gist, stars) in gists.iter().zip([2, 2, 1]) {
+                let preview = data
+                    .gist_preview(db, &mut GistID::ID(gist), None)
+                    .await
+                    .unwrap();
+                assert_eq!(preview.stars, stars);
+            }
+
+            // users see all starred gists that they can read, others only see public ones
+            let starred = data.get_starred_gists(db, NAME, Some(NAME)).await.unwrap();
+            assert_eq!(starred.len(), 3);
+            let starred = data
+                .get_starred_gists(db, NAME2, Some(NAME2))
+                .await
+                .unwrap();
+            assert_eq!(starred.len(), 2);
+            for viewer in [None, Some(NAME)] {
+                let starred = data.get_starred_gists(db, NAME2, viewer).await.unwrap();
+                assert_eq!(starred.len(), 1);
+                assert_eq!(starred[0].public_id, gists[0]);
+            }
+
+            // gist made private after being starred is hidden from other users
+            let update = db_core::UpdateGist {
+                owner: NAME,
+                public_id: &gists[1],
+                description: None,
+                visibility: &GistVisibility::Private,
+            };
+            db.update_gist(&update).await.unwrap();
+            let starred = data
+                .get_starred_gists(db, NAME2, Some(NAME2))
+                .await
+                .unwrap();
+            assert_eq!(starred.len(), 1);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Personal access tokens
+use db_core::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::get_random;
+use crate::errors::*;
+use crate::*;
+
+/// Personal access tokens start with this prefix, which makes them easy to identify
+/// when they are leaked
+pub const ACCESS_TOKEN_PREFIX: &str = "gp_";
+/// number of random characters in a personal access token
+const ACCESS_TOKEN_LEN: usize = 40;
+/// maximum length of a personal access token name
+pub const MAX_ACCESS_TOKEN_NAME_LEN: usize = 100;
+
+/// Hash of a personal access token, as it is stored in the database. Tokens are long
+/// random strings so a fast hash is sufficient.
+pub fn hash_access_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Newly created personal access token. The token is only available at creation time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NewAccessToken {
+    /// secret that is used to authenticate
+    pub token: String,
+    #[serde(flatten)]
+    pub info: AccessToken,
+}
+
+impl Data {
+    /// Create a personal access token for `owner` that can be used for operations in `scopes`
+    pub async fn new_access_token<T: GPDatabse>(
+        &self,
+        db: &T,
+        owner: &str,
+        name: &str,
+        scopes: &[AccessTokenScope],
+    ) -> ServiceResult<NewAccessToken> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ServiceError::BadRequest(
+                "access token name can't be empty".into(),
+            ));
+        }
+        if name.len() > MAX_ACCESS_TOKEN_NAME_LEN {
+            return Err(ServiceError::BadRequest(format!(
+                "access token name can't be longer than {MAX_ACCESS_TOKEN_NAME_LEN} characters"
+            )));
+        }
+        let mut unique_scopes = Vec::with_capacity(scopes.len());
+        for scope in scopes.iter() {
+            if !unique_scopes.contains(scope) {
+                unique_scopes.push(*scope);
+            }
+        }
+        if unique_scopes.is_empty() {
+            return Err(ServiceError::BadRequest(
+                "access token must have at least one scope".into(),
+            ));
+        }
+
+        let token = format!("{ACCESS_TOKEN_PREFIX}{}", get_random(ACCESS_TOKEN_LEN));
+        let token_hash = hash_access_token(&token);
+        let msg = CreateAccessToken {
+            owner,
+            name,
+            token_hash: &token_hash,
+            scopes: &unique_scopes,
+        };
+        db.new_access_token(&msg).await?;
+        let info = db.get_access_token_by_hash(&token_hash).await?;
+        Ok(NewAccessToken { token, info })
+    }
+
+    /// Get personal access tokens belonging to `owner`, most recently created first
+    pub async fn get_access_tokens<T: GPDatabse>(
+        &self,
+        db: &T,
+        owner: &str,
+    ) -> ServiceResult<Vec<AccessToken>> {
+        Ok(db.get_access_tokens(owner).await?)
+    }
+
+    /// Revoke a personal access token
+    pub async fn delete_access_token<T: GPDatabse>(
+        &self,
+        db: &T,
+        owner: &str,
+        id: i64,
+    ) -> ServiceResult<()> {
+        db.delete_access_token(owner, id).await?;
+        Ok(())
+    }
+
+    /// Get owner of a personal access token, if the token can be used for operations in
+    /// `scope`. Tokens are marked as used on success.
+    pub async fn authenticate_access_token<T: GPDatabse>(
+        &self,
+        db: &T,
+        token: &str,
+        scope: AccessTokenScope,
+    ) -> ServiceResult<String> {
+        if !token.starts_with(ACCESS_TOKEN_PREFIX) {
+            return Err(ServiceError::InvalidAccessToken);
+        }
+        let access_token = match db.get_access_token_by_hash(&hash_access_token(token)).await {
+            Ok(access_token) => access_token,
+            Err(DBError::AccessTokenNotFound) => return Err(ServiceError::InvalidAccessToken),
+            Err(e) => return Err(e.into()),
+        };
+        if !access_token.scopes.contains(&scope) {
+            return Err(ServiceError::InsufficientScope(scope.to_str()));
+        }
+        db.touch_access_token(access_token.id).await?;
+        Ok(access_token.owner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[actix_rt::test]
+    async fn test_access_tokens_work() {
+        let config = [
+            sqlx_postgres::get_data().await,
+            sqlx_sqlite::get_data().await,
+        ];
+
+        for (db, data) in config.iter() {
+            const NAME: &str = "accesstokentestuser";
+            const EMAIL: &str = "accesstokentestuser@sss.com";
+            const PASSWORD: &str = "longpassword2";
+
+            let _ = data.delete_user(db, NAME, PASSWORD).await;
+            let _ = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+
+            for (name, scopes) in [
+                ("", vec![AccessTokenScope::ReadGists]),
+                ("  ", vec![AccessTokenScope::ReadGists]),
+                ("noscopes", vec![]),
+            ] {
+                assert!(matches!(
+                    data.new_access_token(db, NAME, name, &scopes).await.err(),
+                    Some(ServiceError::BadRequest(_))
+                ));
+            }
+            let long_name = "a".repeat(MAX_ACCESS_TOKEN_NAME_LEN + 1);
+            assert!(matches!(
+                data.new_access_token(db, NAME, &long_name, &[AccessTokenScope::Account])
+                    .await
+                    .err(),
+                Some(ServiceError::BadRequest(_))
+            ));
+
+            let scopes = [
+                AccessTokenScope::ReadGists,
+                AccessTokenScope::ReadGists,
+                AccessTokenScope::WriteGists,
+            ];
+            let token = data
+                .new_access_token(db, NAME, " ci ", &scopes)
+                .await
+                .unwrap();
+            assert!(token.token.starts_with(ACCESS_TOKEN_PREFIX));
+            assert_eq!(token.info.name, "ci");
+            assert_eq!(
+                token.info.scopes,
+                [AccessTokenScope::ReadGists, AccessTokenScope::WriteGists]
+            );
+            assert_eq!(
+                data.new_access_token(db, NAME, "ci", &scopes).await.err(),
+                Some(ServiceError::AccessTokenNameTaken)
+            );
+
+            // only the hash of the token is stored
+            let stored = db
+                .get_access_token_by_hash(&hash_access_token(&token.token))
+                .await
+                .unwrap();
+            assert_eq!(stored.id, token.info.id);
+            assert!(stored.last_used.is_none());
+
+            assert_eq!(
+                data.authenticate_access_token(db, &token.token, AccessTokenScope::ReadGists)
+                    .await
+                    .unwrap(),
+                NAME
+            );
+            let stored = db
+                .get_access_token_by_hash(&hash_access_token(&token.token))
+                .await
+                .unwrap();
+            assert!(stored.last_used.is_some());
+            assert_eq!(
+                data.authenticate_access_token(db, &token.token, AccessTokenScope::Account)
+                    .await
+                    .err(),
+                Some(ServiceError::InsufficientScope(
+                    AccessTokenScope::Account.to_str()
+                ))
+            );
+            for invalid in ["gp_doesntexist", "doesntexist", ""] {
+                assert_eq!(
+                    data.authenticate_access_token(db, invalid, AccessTokenScope::ReadGists)
+                        .await
+                        .err(),
+                    Some(ServiceError::InvalidAccessToken)
+                );
+            }
+
+            let tokens = data.get_access_tokens(db, NAME).await.unwrap();
+            assert_eq!(tokens.len(), 1);
+            assert_eq!(tokens[0].id, token.info.id);
+
+            data.delete_access_token(db, NAME, token.info.id)
+                .await
+                .unwrap();
+            assert_eq!(
+                data.delete_access_token(db, NAME, token.info.id)
+                    .await
+                    .err(),
+                Some(ServiceError::AccessTokenNotFound)
+            );
+            assert_eq!(
+                data.authenticate_access_token(db, &token.token, AccessTokenScope::ReadGists)
+                    .await
+                    .err(),
+                Some(ServiceError::InvalidAccessToken)
+            );
+            assert!(data.get_access_tokens(db, NAME).await.unwrap().is_empty());
+        }
+    }
+}
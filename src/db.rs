@@ -14,26 +14,63 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
-use crate::settings::Settings;
+use crate::settings::{DBType, Settings};
 use db_core::prelude::*;
 
 pub type BoxDB = Box<dyn GPDatabse>;
 
+/// Connect to the database configured in `settings`
+pub async fn get_data(settings: &Settings) -> BoxDB {
+    match settings.database.database_type {
+        DBType::Sqlite => sqlite::get_data(Some(settings.clone())).await,
+        DBType::Postgres => pg::get_data(Some(settings.clone())).await,
+        DBType::Memory => memory::get_data().await,
+    }
+}
+
+/// Connect to the database configured in `settings` and run migrations on it. Unlike
+/// [get_data], failures are returned instead of panicking.
+pub async fn connect(settings: &Settings) -> DBResult<BoxDB> {
+    async fn migrated<T: Migrate + 'static>(db: T) -> DBResult<BoxDB> {
+        db.migrate().await?;
+        Ok(Box::new(db))
+    }
+
+    match settings.database.database_type {
+        DBType::Sqlite => migrated(sqlite::connect(settings).await?).await,
+        DBType::Postgres => migrated(pg::connect(settings).await?).await,
+        DBType::Memory => migrated(memory::connect().await?).await,
+    }
+}
+
+/// Run migrations on the database configured in `settings`
+pub async fn migrate(settings: &Settings) -> DBResult<()> {
+    match settings.database.database_type {
+        DBType::Sqlite => sqlite::connect(settings).await?.migrate().await,
+        DBType::Postgres => pg::connect(settings).await?.migrate().await,
+        DBType::Memory => memory::connect().await?.migrate().await,
+    }
+}
+
 pub mod pg {
 
     use super::*;
-    use db_sqlx_postgres::{ConnectionOptions, Fresh};
+    use db_sqlx_postgres::{ConnectionOptions, Database, Fresh};
     use sqlx::postgres::PgPoolOptions;
 
-    pub async fn get_data(settings: Option<Settings>) -> BoxDB {
-        let settings = settings.unwrap_or_else(|| Settings::new().unwrap());
+    pub async fn connect(settings: &Settings) -> DBResult<Database> {
         let pool = settings.database.pool;
         let pool_options = PgPoolOptions::new().max_connections(pool);
         let connection_options = ConnectionOptions::Fresh(Fresh {
             pool_options,
-            url: settings.database.url,
+            url: settings.database.url.clone(),
         });
-        let db = connection_options.connect().await.unwrap();
+        connection_options.connect().await
+    }
+
+    pub async fn get_data(settings: Option<Settings>) -> BoxDB {
+        let settings = settings.unwrap_or_else(|| Settings::new().unwrap());
+        let db = connect(&settings).await.unwrap();
         db.migrate().await.unwrap();
         Box::new(db)
     }
@@ -41,20 +78,38 @@ pub mod pg {
 
 pub mod sqlite {
     use super::*;
-    use db_sqlx_sqlite::{ConnectionOptions, Fresh};
+    use db_sqlx_sqlite::{ConnectionOptions, Database, Fresh};
     use sqlx::sqlite::SqlitePoolOptions;
 
-    pub async fn get_data(settings: Option<Settings>) -> BoxDB {
-        let settings = settings.unwrap_or_else(|| Settings::new().unwrap());
-
+    pub async fn connect(settings: &Settings) -> DBResult<Database> {
         let pool = settings.database.pool;
         let pool_options = SqlitePoolOptions::new().max_connections(pool);
         let connection_options = ConnectionOptions::Fresh(Fresh {
             pool_options,
-            url: settings.database.url,
+            url: settings.database.url.clone(),
         });
+        connection_options.connect().await
+    }
+
+    pub async fn get_data(settings: Option<Settings>) -> BoxDB {
+        let settings = settings.unwrap_or_else(|| Settings::new().unwrap());
+        let db = connect(&settings).await.unwrap();
+        db.migrate().await.unwrap();
+        Box::new(db)
+    }
+}
+
+pub mod memory {
+    use super::*;
+    use db_memory::{ConnectionOptions, Database};
+
+    /// Create a new, empty in-memory database. Data is lost when gitpad exits
+    pub async fn connect() -> DBResult<Database> {
+        ConnectionOptions::Fresh.connect().await
+    }
 
-        let db = connection_options.connect().await.unwrap();
+    pub async fn get_data() -> BoxDB {
+        let db = connect().await.unwrap();
         db.migrate().await.unwrap();
         Box::new(db)
     }
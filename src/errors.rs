@@ -89,6 +89,14 @@ pub enum ServiceError {
     /// comment not found
     CommentNotFound,
 
+    #[display(fmt = "File not found")]
+    /// file not found
+    FileNotFound,
+
+    #[display(fmt = "Revision not found")]
+    /// revision not found
+    RevisionNotFound,
+
     /// when the value passed contains profainity
     #[display(fmt = "Can't allow profanity in usernames")]
     ProfainityError,
@@ -134,6 +142,34 @@ pub enum ServiceError {
 
     #[display(fmt = "Gist is empty, at least one file is required to create gist")]
     GistEmpty,
+
+    #[display(fmt = "Unsupported file mode {:o}", _0)]
+    /// gist contains a file with a mode that GitPad can't serve, like submodules
+    UnsupportedFileMode(#[error(not(source))] i32),
+
+    #[display(fmt = "Access token not found")]
+    /// access token not found
+    AccessTokenNotFound,
+
+    /// user already has an access token with the same name
+    #[display(fmt = "Access token name not available")]
+    AccessTokenNameTaken,
+
+    #[display(fmt = "Invalid access token")]
+    /// access token is malformed, revoked or doesn't exist
+    InvalidAccessToken,
+
+    #[display(fmt = "Access token doesn't have the {} scope", _0)]
+    /// access token doesn't have the scope required for the operation
+    InsufficientScope(#[error(not(source))] &'static str),
+
+    #[display(fmt = "Importing repositories is disabled on this instance")]
+    /// `repository.import_root` isn't set
+    ImportDisabled,
+
+    #[display(fmt = "Instance already contains data")]
+    /// backups can only be restored on empty instances
+    InstanceNotEmpty,
 }
 
 impl From<CredsError> for ServiceError {
@@ -165,6 +201,22 @@ impl From<ParseError> for ServiceError {
     }
 }
 
+impl From<git2::Error> for ServiceError {
+    #[cfg(not(tarpaulin_include))]
+    fn from(e: git2::Error) -> ServiceError {
+        log::error!("{:?}", e);
+        ServiceError::InternalServerError
+    }
+}
+
+impl From<zip::result::ZipError> for ServiceError {
+    #[cfg(not(tarpaulin_include))]
+    fn from(e: zip::result::ZipError) -> ServiceError {
+        log::error!("{:?}", e);
+        ServiceError::InternalServerError
+    }
+}
+
 impl From<DBError> for ServiceError {
     #[cfg(not(tarpaulin_include))]
     fn from(e: DBError) -> Self {
@@ -180,6 +232,11 @@ impl From<DBError> for ServiceError {
             DBError::CommentNotFound => ServiceError::CommentNotFound,
             DBError::GistIDTaken => ServiceError::InternalServerError,
             DBError::UnknownVisibilitySpecifier(_) => ServiceError::InternalServerError,
+            DBError::AccessTokenNotFound => ServiceError::AccessTokenNotFound,
+            DBError::DuplicateAccessTokenName => ServiceError::AccessTokenNameTaken,
+            DBError::UnknownAccessTokenScope(_) => ServiceError::InternalServerError,
+            DBError::DatabaseNotEmpty => ServiceError::InstanceNotEmpty,
+            DBError::RowCountMismatch { .. } => ServiceError::InternalServerError,
         }
     }
 }
@@ -234,11 +291,21 @@ impl ResponseError for ServiceError {
 
             ServiceError::GistNotFound => StatusCode::NOT_FOUND,
             ServiceError::CommentNotFound => StatusCode::NOT_FOUND,
+            ServiceError::FileNotFound => StatusCode::NOT_FOUND,
+            ServiceError::RevisionNotFound => StatusCode::NOT_FOUND,
             ServiceError::EmptyComment => StatusCode::BAD_REQUEST,
 
             ServiceError::UnauthorizedOperation(_) => StatusCode::UNAUTHORIZED,
             ServiceError::BadRequest(_) => StatusCode::BAD_REQUEST,
             ServiceError::GistEmpty => StatusCode::BAD_REQUEST,
+            ServiceError::UnsupportedFileMode(_) => StatusCode::NOT_IMPLEMENTED,
+
+            ServiceError::AccessTokenNotFound => StatusCode::NOT_FOUND,
+            ServiceError::AccessTokenNameTaken => StatusCode::BAD_REQUEST,
+            ServiceError::InvalidAccessToken => StatusCode::UNAUTHORIZED,
+            ServiceError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            ServiceError::ImportDisabled => StatusCode::FORBIDDEN,
+            ServiceError::InstanceNotEmpty => StatusCode::CONFLICT,
         }
     }
 }
@@ -0,0 +1,59 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Periodic consistency checks between the database and repository storage
+use std::time::Duration;
+
+use tokio::spawn;
+use tokio::time::sleep;
+
+use crate::data::api::v1::fsck::FsckReport;
+use crate::db::BoxDB;
+use crate::*;
+
+fn log_report(report: &FsckReport) {
+    log::info!(
+        "Consistency check: {} gists, {} repositories, {} issues",
+        report.gists,
+        report.repositories,
+        report.issues.len()
+    );
+    for issue in report.issues.iter() {
+        log::warn!(
+            "{:?} {}: {}, action: {:?}, error: {}",
+            issue.kind,
+            issue.gist,
+            issue.detail.as_deref().unwrap_or_default(),
+            issue.action,
+            issue.error.as_deref().unwrap_or_default()
+        );
+    }
+}
+
+/// checks consistency of the database and repository storage periodically
+pub fn run(db: BoxDB, data: AppData, duration: Duration) {
+    let mode = data.settings.fsck.mode;
+    let fut = async move {
+        loop {
+            sleep(duration).await;
+            match data.fsck(&db, mode).await {
+                Ok(report) => log_report(&report),
+                Err(e) => log::error!("Error while checking consistency: {:?}", e),
+            }
+        }
+    };
+    spawn(fut);
+}
@@ -0,0 +1,486 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Git smart HTTP protocol, so that gists can be cloned, fetched and pushed to with git
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use actix_identity::Identity;
+use actix_web::dev::{Decompress, Payload};
+use actix_web::http::header::{self, ContentType};
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+use db_core::prelude::*;
+
+use crate::api::v1::routes::PostCommentPath;
+use crate::errors::*;
+use crate::settings::Settings;
+use crate::utils::get_random;
+use crate::AppData;
+
+pub mod routes;
+#[cfg(test)]
+mod tests;
+
+pub use routes::ROUTES;
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(info_refs);
+    cfg.service(upload_pack);
+    cfg.service(receive_pack);
+}
+
+/// Size of chunks in which output of git services is streamed to clients
+const STREAM_CHUNK_SIZE: usize = 8192;
+
+/// Hook that validates pushes, installed on all gist repositories
+const PRE_RECEIVE: &str = include_str!("./pre-receive");
+
+/// Git services that are available over smart HTTP
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum GitService {
+    #[serde(rename = "git-upload-pack")]
+    UploadPack,
+    #[serde(rename = "git-receive-pack")]
+    ReceivePack,
+}
+
+impl GitService {
+    /// name of the service, as used in the protocol
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UploadPack => "git-upload-pack",
+            Self::ReceivePack => "git-receive-pack",
+        }
+    }
+
+    /// git subcommand that implements the service
+    fn subcommand(&self) -> &'static str {
+        match self {
+            Self::UploadPack => "upload-pack",
+            Self::ReceivePack => "receive-pack",
+        }
+    }
+
+    fn advertisement_content_type(&self) -> ContentType {
+        ContentType(
+            format!("application/x-{}-advertisement", self.name())
+                .parse()
+                .unwrap(),
+        )
+    }
+
+    fn result_content_type(&self) -> ContentType {
+        ContentType(
+            format!("application/x-{}-result", self.name())
+                .parse()
+                .unwrap(),
+        )
+    }
+
+    /// Prepare git command that runs the service on `repository`. The protocol version
+    /// requested by the client is passed on to git.
+    fn command(
+        &self,
+        req: &HttpRequest,
+        settings: &Settings,
+        repository: &Path,
+        advertise_refs: bool,
+    ) -> Command {
+        let mut cmd = Command::new("git");
+        if *self == Self::ReceivePack {
+            cmd.arg("-c")
+                .arg(format!(
+                    "core.hooksPath={}",
+                    get_hooks_path(settings).display()
+                ))
+                .arg("-c")
+                .arg(format!(
+                    "receive.maxInputSize={}",
+                    settings.repository.max_push_size
+                ));
+        }
+        cmd.arg(self.subcommand()).arg("--stateless-rpc");
+        if advertise_refs {
+            cmd.arg("--advertise-refs");
+        }
+        cmd.arg(repository);
+        if let Some(protocol) = get_protocol(req) {
+            cmd.env("GIT_PROTOCOL", protocol);
+        }
+        cmd
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InfoRefsQuery {
+    /// unset when client speaks dumb HTTP protocol
+    pub service: Option<GitService>,
+}
+
+fn get_protocol(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Git-Protocol")
+        .and_then(|protocol| protocol.to_str().ok())
+}
+
+/// Encode `data` as a pkt-line
+fn pkt_line(data: &str) -> String {
+    format!("{:04x}{data}", data.len() + 4)
+}
+
+/// Directory containing hooks that are run on gist repositories
+fn get_hooks_path(settings: &Settings) -> PathBuf {
+    Path::new(&settings.repository.root).join(".hooks")
+}
+
+/// Install hooks, replacing ones from older versions of GitPad
+fn install_hooks(settings: &Settings) -> ServiceResult<()> {
+    let hooks = get_hooks_path(settings);
+    let pre_receive = hooks.join("pre-receive");
+    if fs::read(&pre_receive).ok().as_deref() == Some(PRE_RECEIVE.as_bytes()) {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&hooks)?;
+    // hook is renamed into place so that concurrent pushes never see a partial file
+    let tmp = hooks.join(format!(".pre-receive.{}", get_random(10)));
+    fs::write(&tmp, PRE_RECEIVE)?;
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&tmp, &pre_receive)?;
+    Ok(())
+}
+
+/// Get username from HTTP basic authorization credentials. The password is either the
+/// account secret or a personal access token with `scope`.
+async fn authenticate(
+    req: &HttpRequest,
+    db: &crate::DB,
+    data: &AppData,
+    scope: AccessTokenScope,
+) -> Option<String> {
+    let credentials = req
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let credentials = String::from_utf8(base64::decode(credentials.trim()).ok()?).ok()?;
+    let (username, secret) = credentials.split_once(':')?;
+    let authenticated = match db.get_secret(username).await {
+        Ok(s) if secrets_match(&s, secret) => true,
+        Ok(_) => matches!(
+            data.authenticate_access_token(db.as_ref(), secret, scope)
+                .await,
+            Ok(owner) if owner == username
+        ),
+        Err(_) => false,
+    };
+    if authenticated {
+        Some(username.to_owned())
+    } else {
+        log::info!("git authentication failed for user {username}");
+        None
+    }
+}
+
+/// Compare secrets in constant time. Secrets are hashed first, so that the comparison
+/// doesn't leak their lengths either.
+fn secrets_match(a: &str, b: &str) -> bool {
+    let a = Sha256::digest(a.as_bytes());
+    let b = Sha256::digest(b.as_bytes());
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Ask git client for credentials
+fn auth_challenge() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"GitPad\""))
+        .finish()
+}
+
+/// Get gist if `username` can push to it
+async fn get_writable_gist(
+    db: &crate::DB,
+    username: &str,
+    path: &PostCommentPath,
+) -> ServiceResult<Gist> {
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.owner != path.username {
+        return Err(ServiceError::GistNotFound);
+    }
+    if gist.owner != username {
+        if gist.visibility == GistVisibility::Private {
+            return Err(ServiceError::GistNotFound);
+        }
+        return Err(ServiceError::UnauthorizedOperation(
+            "This user is not the owner of the gist to push to it".into(),
+        ));
+    }
+    Ok(gist)
+}
+
+/// Get gist if the requesting user can read it. Users are identified by HTTP basic
+/// authorization credentials when they are present, so that git clients can clone private
+/// gists, and by their session otherwise. Returns `None` when the client should be asked for
+/// credentials.
+async fn get_readable_gist(
+    req: &HttpRequest,
+    id: &Identity,
+    db: &crate::DB,
+    data: &AppData,
+    path: &PostCommentPath,
+) -> ServiceResult<Option<Gist>> {
+    let reader = if req.headers().contains_key(header::AUTHORIZATION) {
+        match authenticate(req, db, data, AccessTokenScope::ReadGists).await {
+            Some(username) => Some(username),
+            None => return Ok(None),
+        }
+    } else {
+        id.identity()
+    };
+
+    let gist = db.get_gist(&path.gist).await?;
+    if gist.owner != path.username {
+        return Err(ServiceError::GistNotFound);
+    }
+    if gist.visibility == GistVisibility::Private && reader.as_ref() != Some(&gist.owner) {
+        // git clients only send credentials after being asked for them
+        if reader.is_none() {
+            return Ok(None);
+        }
+        return Err(ServiceError::GistNotFound);
+    }
+    Ok(Some(gist))
+}
+
+#[my_codegen::get(path = "crate::GIT_ROUTES.info_refs")]
+async fn info_refs(
+    req: HttpRequest,
+    path: web::Path<PostCommentPath>,
+    query: web::Query<InfoRefsQuery>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<HttpResponse> {
+    let service = query
+        .service
+        .ok_or_else(|| ServiceError::BadRequest("dumb HTTP protocol is not supported".into()))?;
+    let gist = match service {
+        GitService::UploadPack => match get_readable_gist(&req, &id, &db, &data, &path).await? {
+            Some(gist) => gist,
+            None => return Ok(auth_challenge()),
+        },
+        GitService::ReceivePack => {
+            match authenticate(&req, &db, &data, AccessTokenScope::WriteGists).await {
+                Some(username) => get_writable_gist(&db, &username, &path).await?,
+                None => return Ok(auth_challenge()),
+            }
+        }
+    };
+    let repository = data.get_repository_path(&gist.public_id);
+
+    let mut child = service
+        .command(&req, &data.settings, &repository, true)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+
+    // git http-backend doesn't announce the service when protocol v2 is used
+    let mut announcement = Vec::default();
+    if !matches!(get_protocol(&req), Some(protocol) if protocol.contains("version=2")) {
+        announcement
+            .extend_from_slice(pkt_line(&format!("# service={}\n", service.name())).as_bytes());
+        announcement.extend_from_slice(b"0000");
+    }
+    let body = futures::stream::once(async move { Ok(web::Bytes::from(announcement)) })
+        .chain(stream_stdout(service, repository, child, stdout));
+
+    Ok(HttpResponse::Ok()
+        .content_type(service.advertisement_content_type())
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(body))
+}
+
+/// Spawn `service` on a gist's repository, with pipes for its standard input and output
+fn spawn_service(
+    req: &HttpRequest,
+    service: GitService,
+    settings: &Settings,
+    repository: &Path,
+) -> ServiceResult<(Child, ChildStdin, ChildStdout)> {
+    let mut child = service
+        .command(req, settings, repository, false)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    Ok((child, stdin, stdout))
+}
+
+/// Write request body to standard input of a git service
+async fn write_request(mut body: Decompress<Payload>, mut stdin: ChildStdin) -> ServiceResult<()> {
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+        match stdin.write_all(&chunk).await {
+            // git stops reading when a push is rejected, it reports why to the client
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => break,
+            res => res?,
+        }
+    }
+    Ok(())
+}
+
+/// Stream standard output of a git service, so that large packs aren't held in memory.
+/// The response has already started when the service fails, so failures are only logged.
+fn stream_stdout(
+    service: GitService,
+    repository: PathBuf,
+    child: Child,
+    stdout: ChildStdout,
+) -> impl Stream<Item = Result<web::Bytes, std::io::Error>> {
+    futures::stream::unfold(Some((child, stdout, repository)), move |state| async move {
+        let (mut child, mut stdout, repository) = state?;
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        match stdout.read(&mut buf).await {
+            Ok(0) => {
+                if !matches!(child.wait().await, Ok(status) if status.success()) {
+                    log::error!("{} failed on {:?}", service.name(), repository);
+                }
+                None
+            }
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(web::Bytes::from(buf)), Some((child, stdout, repository))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// Run `service` on a gist's repository, with request body as input. The service's output
+/// is collected before responding, so that its result is known.
+async fn run_service(
+    req: &HttpRequest,
+    payload: web::Payload,
+    service: GitService,
+    settings: &Settings,
+    repository: &Path,
+) -> ServiceResult<HttpResponse> {
+    let (mut child, stdin, mut stdout) = spawn_service(req, service, settings, repository)?;
+
+    // git clients compress large requests
+    let body = Decompress::from_headers(payload.into_inner(), req.headers());
+    let read = async {
+        let mut resp = Vec::default();
+        stdout.read_to_end(&mut resp).await?;
+        Ok::<Vec<u8>, ServiceError>(resp)
+    };
+    let (_, resp) = futures::try_join!(write_request(body, stdin), read)?;
+
+    if !child.wait().await?.success() {
+        log::error!("{} failed on {:?}", service.name(), repository);
+        return Err(ServiceError::InternalServerError);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(service.result_content_type())
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .body(resp))
+}
+
+#[my_codegen::post(path = "crate::GIT_ROUTES.upload_pack")]
+async fn upload_pack(
+    req: HttpRequest,
+    payload: web::Payload,
+    path: web::Path<PostCommentPath>,
+    id: Identity,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<HttpResponse> {
+    let gist = match get_readable_gist(&req, &id, &db, &data, &path).await? {
+        Some(gist) => gist,
+        None => return Ok(auth_challenge()),
+    };
+    let repository = data.get_repository_path(&gist.public_id);
+    let service = GitService::UploadPack;
+    let (child, stdin, stdout) = spawn_service(&req, service, &data.settings, &repository)?;
+
+    // git clients compress large requests
+    let body = Decompress::from_headers(payload.into_inner(), req.headers());
+    actix_rt::spawn(async move {
+        if let Err(e) = write_request(body, stdin).await {
+            log::error!("{} failed to read request: {}", service.name(), e);
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(service.result_content_type())
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(stream_stdout(service, repository, child, stdout)))
+}
+
+/// Get the commit that a gist's HEAD points to
+fn get_head(repository: &Path) -> ServiceResult<Option<git2::Oid>> {
+    let repo = git2::Repository::open(repository)?;
+    let head = repo.refname_to_id("HEAD").ok();
+    Ok(head)
+}
+
+#[my_codegen::post(path = "crate::GIT_ROUTES.receive_pack")]
+async fn receive_pack(
+    req: HttpRequest,
+    payload: web::Payload,
+    path: web::Path<PostCommentPath>,
+    db: crate::DB,
+    data: AppData,
+) -> ServiceResult<HttpResponse> {
+    let username = match authenticate(&req, &db, &data, AccessTokenScope::WriteGists).await {
+        Some(username) => username,
+        None => return Ok(auth_challenge()),
+    };
+    let gist = get_writable_gist(&db, &username, &path).await?;
+    let repository = data.get_repository_path(&gist.public_id);
+    install_hooks(&data.settings)?;
+
+    let old_head = get_head(&repository)?;
+    let resp = run_service(
+        &req,
+        payload,
+        GitService::ReceivePack,
+        &data.settings,
+        &repository,
+    )
+    .await?;
+    // rejected pushes are reported to the client by git, gist is left untouched
+    if get_head(&repository)? != old_head {
+        db.touch_gist(&gist.public_id).await?;
+        data.index_gist(db.as_ref(), &gist.public_id).await?;
+    }
+    Ok(resp)
+}
@@ -0,0 +1,87 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Git smart HTTP routes
+use crate::api::v1::routes::PostCommentPath;
+
+/// constant [Git](Git) instance
+pub const ROUTES: Git = Git::new();
+
+/// Git smart HTTP routes
+pub struct Git {
+    /// ref advertisement
+    pub info_refs: &'static str,
+    /// fetch and clone
+    pub upload_pack: &'static str,
+    /// push
+    pub receive_pack: &'static str,
+}
+
+impl Git {
+    /// create new instance of Git routes
+    pub const fn new() -> Git {
+        let info_refs = "/~{username}/{gist}.git/info/refs";
+        let upload_pack = "/~{username}/{gist}.git/git-upload-pack";
+        let receive_pack = "/~{username}/{gist}.git/git-receive-pack";
+        Git {
+            info_refs,
+            upload_pack,
+            receive_pack,
+        }
+    }
+
+    /// get info_refs route with placeholders replaced with values provided.
+    pub fn get_info_refs_route(&self, components: &PostCommentPath) -> String {
+        self.info_refs
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get upload_pack route with placeholders replaced with values provided.
+    pub fn get_upload_pack_route(&self, components: &PostCommentPath) -> String {
+        self.upload_pack
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get receive_pack route with placeholders replaced with values provided.
+    pub fn get_receive_pack_route(&self, components: &PostCommentPath) -> String {
+        self.receive_pack
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn git_route_substitution_works() {
+        const NAME: &str = "bob";
+        const GIST: &str = "foo";
+        let info_refs = format!("/~{NAME}/{GIST}.git/info/refs");
+        let upload_pack = format!("/~{NAME}/{GIST}.git/git-upload-pack");
+        let receive_pack = format!("/~{NAME}/{GIST}.git/git-receive-pack");
+
+        let path = PostCommentPath {
+            username: NAME.into(),
+            gist: GIST.into(),
+        };
+        assert_eq!(info_refs, ROUTES.get_info_refs_route(&path));
+        assert_eq!(upload_pack, ROUTES.get_upload_pack_route(&path));
+        assert_eq!(receive_pack, ROUTES.get_receive_pack_route(&path));
+    }
+}
@@ -0,0 +1,597 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::path::Path;
+use std::process::Command;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+
+use db_core::prelude::*;
+
+use super::{pkt_line, secrets_match};
+use crate::api::v1::routes::PostCommentPath;
+use crate::data::api::v1::gists::{ContentType, CreateGist, FileInfo, FileMode, FileType, GistID};
+use crate::data::Data;
+use crate::tests::*;
+use crate::utils::get_random;
+use crate::*;
+
+#[actix_rt::test]
+async fn postgres_git_smart_http_works() {
+    let (db, data) = sqlx_postgres::get_data().await;
+    git_smart_http_works(data, db).await;
+}
+
+#[actix_rt::test]
+async fn sqlite_git_smart_http_works() {
+    let (db, data) = sqlx_sqlite::get_data().await;
+    git_smart_http_works(data, db).await;
+}
+
+#[actix_rt::test]
+async fn postgres_git_push_works() {
+    let (db, data) = sqlx_postgres::get_data().await;
+    git_push_works(data, db).await;
+}
+
+#[actix_rt::test]
+async fn sqlite_git_push_works() {
+    let (db, data) = sqlx_sqlite::get_data().await;
+    git_push_works(data, db).await;
+}
+
+#[actix_rt::test]
+async fn pkt_line_works() {
+    assert_eq!(
+        pkt_line("# service=git-upload-pack\n"),
+        "001e# service=git-upload-pack\n"
+    );
+    assert_eq!(pkt_line("done\n"), "0009done\n");
+}
+
+#[test]
+fn secrets_match_works() {
+    assert!(secrets_match("secret", "secret"));
+    assert!(!secrets_match("secret", "secreT"));
+    assert!(!secrets_match("secret", "secret2"));
+    assert!(!secrets_match("secret", ""));
+}
+
+/// Request pack with all objects reachable from `want`
+fn upload_pack_request(want: &str) -> String {
+    format!(
+        "{}0000{}",
+        pkt_line(&format!("want {want}\n")),
+        pkt_line("done\n")
+    )
+}
+
+/// Run git in `dir` and get its output
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=gitpad",
+            "-c",
+            "user.email=gitpad@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+/// Request that updates `refname` from `old` to `new`, with a pack containing the commits in
+/// between
+fn receive_pack_request(dir: &Path, old: &str, new: &str, refname: &str) -> Vec<u8> {
+    let mut req = pkt_line(&format!("{old} {new} {refname}\0report-status\n")).into_bytes();
+    req.extend_from_slice(b"0000");
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "printf '{new}\\n^{old}\\n' | git pack-objects --revs --stdout"
+        ))
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    req.extend_from_slice(&output.stdout);
+    req
+}
+
+/// Basic authorization header value
+fn basic_auth(username: &str, secret: &str) -> String {
+    format!("Basic {}", base64::encode(format!("{username}:{secret}")))
+}
+
+async fn git_push_works(data: Arc<Data>, db: BoxDB) {
+    const NAME: &str = "gitpushuser";
+    const NAME2: &str = "gitpushuser2";
+    const EMAIL: &str = "gitpushuser@example.com";
+    const EMAIL2: &str = "gitpushuser2@example.com";
+    const PASSWORD: &str = "longpassword2";
+    let db = &db;
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
+    data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+    data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+    let secret = db.get_secret(NAME).await.unwrap();
+    let secret2 = db.get_secret(NAME2).await.unwrap();
+    let app = get_app!(data, db).await;
+
+    let files = [FileInfo {
+        filename: "foo.md".into(),
+        content: FileType::File(ContentType::Text("foo".into())),
+        mode: FileMode::Regular,
+    }];
+
+    for visibility in [GistVisibility::Public, GistVisibility::Private] {
+        let msg = CreateGist {
+            owner: NAME,
+            description: None,
+            visibility: &visibility,
+        };
+        let mut gist = data.new_gist(db, &msg).await.unwrap();
+        data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+            .await
+            .unwrap();
+        let path = PostCommentPath {
+            username: NAME.into(),
+            gist: gist.id.clone(),
+        };
+        let info_refs = format!(
+            "{}?service=git-receive-pack",
+            GIT_ROUTES.get_info_refs_route(&path)
+        );
+        let receive_pack = GIT_ROUTES.get_receive_pack_route(&path);
+
+        // unauthenticated clients are asked for credentials
+        let resp = get_request!(&app, &info_refs);
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&info_refs)
+                .insert_header((header::AUTHORIZATION, basic_auth(NAME, "wrongsecret")))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        // only owner can push
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&info_refs)
+                .insert_header((header::AUTHORIZATION, basic_auth(NAME2, &secret2)))
+                .to_request(),
+        )
+        .await;
+        if visibility == GistVisibility::Private {
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            continue;
+        }
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(!resp.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&info_refs)
+                .insert_header((header::AUTHORIZATION, basic_auth(NAME, &secret)))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-git-receive-pack-advertisement"
+        );
+
+        // personal access tokens with write scope can be used instead of the secret
+        for (scope, status) in [
+            (AccessTokenScope::WriteGists, StatusCode::OK),
+            (AccessTokenScope::ReadGists, StatusCode::UNAUTHORIZED),
+        ] {
+            let token = data
+                .new_access_token(db, NAME, &format!("git {}", gist.id), &[scope])
+                .await
+                .unwrap();
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri(&info_refs)
+                    .insert_header((header::AUTHORIZATION, basic_auth(NAME, &token.token)))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), status);
+            // tokens can't be used to push as other users
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri(&info_refs)
+                    .insert_header((header::AUTHORIZATION, basic_auth(NAME2, &token.token)))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+            assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+            data.delete_access_token(db, NAME, token.info.id)
+                .await
+                .unwrap();
+        }
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).starts_with("001f# service=git-receive-pack\n0000"));
+
+        // prepare commits in a clone of the gist
+        let clone = std::env::temp_dir().join(get_random(10));
+        let repository = data.get_repository_path(&gist.id);
+        git(
+            &std::env::temp_dir(),
+            &[
+                "clone",
+                "-q",
+                repository.to_str().unwrap(),
+                clone.to_str().unwrap(),
+            ],
+        );
+        let head = git(&clone, &["rev-parse", "HEAD"]);
+        let refname = git(&clone, &["symbolic-ref", "HEAD"]);
+
+        std::fs::create_dir(clone.join("bin")).unwrap();
+        std::fs::write(clone.join("bin/run.sh"), "echo foo").unwrap();
+        git(&clone, &["add", "."]);
+        git(&clone, &["update-index", "--chmod=+x", "bin/run.sh"]);
+        git(&clone, &["commit", "-q", "-m", "add script"]);
+        let valid = git(&clone, &["rev-parse", "HEAD"]);
+
+        std::os::unix::fs::symlink("foo.md", clone.join("link.md")).unwrap();
+        git(&clone, &["add", "."]);
+        git(&clone, &["commit", "-q", "-m", "add symlink"]);
+        let symlink = git(&clone, &["rev-parse", "HEAD"]);
+
+        let cacheinfo = format!("160000,{head},submodule");
+        git(
+            &clone,
+            &["update-index", "--add", "--cacheinfo", cacheinfo.as_str()],
+        );
+        git(&clone, &["commit", "-q", "-m", "add submodule"]);
+        let submodule = git(&clone, &["rev-parse", "HEAD"]);
+
+        let push = |req: Vec<u8>, secret: &str| {
+            test::TestRequest::post()
+                .uri(&receive_pack)
+                .insert_header((
+                    header::CONTENT_TYPE,
+                    "application/x-git-receive-pack-request",
+                ))
+                .insert_header((header::AUTHORIZATION, basic_auth(NAME, secret)))
+                .set_payload(req)
+                .to_request()
+        };
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri(&receive_pack)
+                .set_payload(receive_pack_request(&clone, &head, &valid, &refname))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // unsupported file modes are rejected
+        let resp = test::call_service(
+            &app,
+            push(
+                receive_pack_request(&clone, &head, &submodule, &refname),
+                &secret,
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains(&format!("ng {refname}")));
+
+        // only the gist's branch can be pushed to
+        let resp = test::call_service(
+            &app,
+            push(
+                receive_pack_request(&clone, &head, &valid, "refs/heads/foo"),
+                &secret,
+            ),
+        )
+        .await;
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains("ng refs/heads/foo"));
+
+        // pushes over size limit are rejected
+        let mut settings = data.settings.clone();
+        settings.repository.max_push_size = 64;
+        let limited = Data::new(Some(settings));
+        let limited_app = get_app!(limited, db).await;
+        let resp = test::call_service(
+            &limited_app,
+            push(
+                receive_pack_request(&clone, &head, &valid, &refname),
+                &secret,
+            ),
+        )
+        .await;
+        let body = test::read_body(resp).await;
+        assert!(!String::from_utf8_lossy(&body).contains(&format!("ok {refname}")));
+        assert_eq!(git(&repository, &["rev-parse", "HEAD"]), head);
+        let updated = db.get_gist(&gist.id).await.unwrap().updated;
+
+        // push
+        let resp = test::call_service(
+            &app,
+            push(
+                receive_pack_request(&clone, &head, &valid, &refname),
+                &secret,
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-git-receive-pack-result"
+        );
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains(&format!("ok {refname}")));
+        assert_eq!(git(&repository, &["rev-parse", "HEAD"]), valid);
+        assert!(db.get_gist(&gist.id).await.unwrap().updated >= updated);
+
+        let preview = data
+            .gist_preview(db, &mut GistID::ID(&gist.id), None)
+            .await
+            .unwrap();
+        assert!(preview.files.iter().any(|f| f.filename == "bin"));
+        let script = data
+            .read_file(db, &GistID::ID(&gist.id), "bin/run.sh", None)
+            .await
+            .unwrap();
+        assert_eq!(script.mode, FileMode::Executable);
+
+        // symlinks are stored as links
+        let resp = test::call_service(
+            &app,
+            push(
+                receive_pack_request(&clone, &valid, &symlink, &refname),
+                &secret,
+            ),
+        )
+        .await;
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains(&format!("ok {refname}")));
+        let link = data
+            .read_file(db, &GistID::ID(&gist.id), "link.md", None)
+            .await
+            .unwrap();
+        assert_eq!(link.mode, FileMode::Symlink);
+        assert_eq!(
+            link.content,
+            FileType::File(ContentType::Text("foo.md".into()))
+        );
+
+        std::fs::remove_dir_all(&clone).unwrap();
+    }
+}
+
+async fn git_smart_http_works(data: Arc<Data>, db: BoxDB) {
+    const NAME: &str = "gitsmarthttpuser";
+    const NAME2: &str = "gitsmarthttpuser2";
+    const EMAIL: &str = "gitsmarthttpuser@example.com";
+    const EMAIL2: &str = "gitsmarthttpuser2@example.com";
+    const PASSWORD: &str = "longpassword2";
+    let db = &db;
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
+    let (_, signin_resp) = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+    let cookies = get_cookie!(signin_resp);
+    let (_, signin_resp2) = data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+    let cookies2 = get_cookie!(signin_resp2);
+    let secret = db.get_secret(NAME).await.unwrap();
+    let secret2 = db.get_secret(NAME2).await.unwrap();
+    let app = get_app!(data, db).await;
+
+    let files = [FileInfo {
+        filename: "foo.md".into(),
+        content: FileType::File(ContentType::Text("foo".into())),
+        mode: FileMode::Regular,
+    }];
+
+    for visibility in [
+        GistVisibility::Public,
+        GistVisibility::Unlisted,
+        GistVisibility::Private,
+    ] {
+        let msg = CreateGist {
+            owner: NAME,
+            description: None,
+            visibility: &visibility,
+        };
+        let mut gist = data.new_gist(db, &msg).await.unwrap();
+        data.write_file(db, &mut GistID::Repository(&mut gist.repository), &files)
+            .await
+            .unwrap();
+        let head = data
+            .gist_revisions(db, &GistID::ID(&gist.id), 0, 1)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap()
+            .id;
+
+        let path = PostCommentPath {
+            username: NAME.into(),
+            gist: gist.id.clone(),
+        };
+        let info_refs = format!(
+            "{}?service=git-upload-pack",
+            GIT_ROUTES.get_info_refs_route(&path)
+        );
+        let upload_pack = GIT_ROUTES.get_upload_pack_route(&path);
+
+        // dumb HTTP protocol
+        let resp = get_request!(&app, &GIT_ROUTES.get_info_refs_route(&path));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // gist is only served under its owner's name
+        let wrong_owner = PostCommentPath {
+            username: NAME2.into(),
+            gist: gist.id.clone(),
+        };
+        let resp = get_request!(
+            &app,
+            &format!(
+                "{}?service=git-upload-pack",
+                GIT_ROUTES.get_info_refs_route(&wrong_owner)
+            ),
+            cookies.clone()
+        );
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = test::call_service(
+            &app,
+            post_request!(&GIT_ROUTES.get_upload_pack_route(&wrong_owner))
+                .set_payload(upload_pack_request(&head))
+                .cookie(cookies.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        if visibility == GistVisibility::Private {
+            // unauthenticated clients are asked for credentials
+            let resp = get_request!(&app, &info_refs);
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+            assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+            let resp = test::call_service(
+                &app,
+                post_request!(&upload_pack)
+                    .set_payload(upload_pack_request(&head))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+            let resp = get_request!(&app, &info_refs, cookies2.clone());
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri(&info_refs)
+                    .insert_header((header::AUTHORIZATION, basic_auth(NAME2, &secret2)))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri(&info_refs)
+                    .insert_header((header::AUTHORIZATION, basic_auth(NAME, "wrongsecret")))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+            // owners can clone with their secret or a token that can read gists
+            let token = data
+                .new_access_token(
+                    db,
+                    NAME,
+                    &format!("git {}", gist.id),
+                    &[AccessTokenScope::ReadGists],
+                )
+                .await
+                .unwrap();
+            for secret in [&secret, &token.token] {
+                let resp = test::call_service(
+                    &app,
+                    test::TestRequest::get()
+                        .uri(&info_refs)
+                        .insert_header((header::AUTHORIZATION, basic_auth(NAME, secret)))
+                        .to_request(),
+                )
+                .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                let resp = test::call_service(
+                    &app,
+                    post_request!(&upload_pack)
+                        .insert_header((header::AUTHORIZATION, basic_auth(NAME, secret)))
+                        .set_payload(upload_pack_request(&head))
+                        .to_request(),
+                )
+                .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                let body = test::read_body(resp).await;
+                assert!(body.windows(4).any(|w| w == b"PACK"));
+            }
+            data.delete_access_token(db, NAME, token.info.id)
+                .await
+                .unwrap();
+        }
+
+        let cookies = if visibility == GistVisibility::Private {
+            cookies.clone()
+        } else {
+            cookies2.clone()
+        };
+
+        // ref advertisement
+        let resp = get_request!(&app, &info_refs, cookies.clone());
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-git-upload-pack-advertisement"
+        );
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.starts_with("001e# service=git-upload-pack\n0000"));
+        assert!(body.contains(&format!("{head} HEAD")));
+
+        // fetch
+        let resp = test::call_service(
+            &app,
+            post_request!(&upload_pack)
+                .insert_header((
+                    header::CONTENT_TYPE,
+                    "application/x-git-upload-pack-request",
+                ))
+                .set_payload(upload_pack_request(&head))
+                .cookie(cookies)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-git-upload-pack-result"
+        );
+        let body = test::read_body(resp).await;
+        assert!(body.starts_with(b"0008NAK\n"));
+        assert!(body.windows(4).any(|w| w == b"PACK"));
+    }
+}
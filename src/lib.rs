@@ -0,0 +1,81 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::sync::Arc;
+
+use actix_identity::{CookieIdentityPolicy, IdentityService};
+use actix_web::{error::InternalError, http::StatusCode, web::Data as WebData, web::JsonConfig};
+use lazy_static::lazy_static;
+use static_assets::FileMap;
+
+use api::v1::bearer::BearerIdentityPolicy;
+
+mod api;
+pub mod cli;
+pub mod data;
+pub mod db;
+pub mod demo;
+pub mod errors;
+pub mod fsck;
+mod git;
+pub mod pages;
+pub mod routes;
+pub mod settings;
+mod static_assets;
+#[cfg(test)]
+mod tests;
+mod utils;
+
+pub use api::github::ROUTES as GITHUB_ROUTES;
+pub use api::v1::ROUTES as V1_API_ROUTES;
+pub use data::Data;
+pub use git::ROUTES as GIT_ROUTES;
+pub use settings::Settings;
+
+pub const CACHE_AGE: u32 = 604800;
+
+pub const GIT_COMMIT_HASH: &str = env!("GIT_HASH");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const PKG_NAME: &str = env!("CARGO_PKG_NAME");
+pub const PKG_DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
+pub const PKG_HOMEPAGE: &str = env!("CARGO_PKG_HOMEPAGE");
+
+pub type AppData = WebData<Arc<data::Data>>;
+pub type DB = WebData<Box<dyn db_core::GPDatabse>>;
+
+lazy_static! {
+    pub static ref FILES: FileMap = FileMap::new();
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn get_json_err() -> JsonConfig {
+    JsonConfig::default()
+        .error_handler(|err, _| InternalError::new(err, StatusCode::BAD_REQUEST).into())
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn get_identity_service(settings: &Settings) -> IdentityService<BearerIdentityPolicy> {
+    let cookie_secret = &settings.server.cookie_secret;
+    IdentityService::new(BearerIdentityPolicy::new(
+        CookieIdentityPolicy::new(cookie_secret.as_bytes())
+            .path("/")
+            .name("Authorization")
+            //TODO change cookie age
+            .max_age_secs(216000)
+            .domain(&settings.server.domain)
+            .secure(false),
+    ))
+}
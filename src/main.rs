@@ -14,55 +14,19 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
-use std::env;
-use std::sync::Arc;
+use std::time::Duration;
 
-use actix_identity::{CookieIdentityPolicy, IdentityService};
-use actix_web::{
-    error::InternalError, http::StatusCode, middleware as actix_middleware, web::Data as WebData,
-    web::JsonConfig, App, HttpServer,
-};
-use lazy_static::lazy_static;
+use actix_web::{middleware as actix_middleware, web::Data as WebData, App, HttpServer};
 use log::info;
-use static_assets::FileMap;
 
-mod api;
-pub mod data;
-mod db;
-pub mod demo;
-pub mod errors;
-mod pages;
-mod routes;
-mod settings;
-mod static_assets;
-#[cfg(test)]
-mod tests;
-mod utils;
-
-pub use api::v1::ROUTES as V1_API_ROUTES;
-pub use data::Data;
-pub use settings::Settings;
-
-pub const CACHE_AGE: u32 = 604800;
-
-pub const GIT_COMMIT_HASH: &str = env!("GIT_HASH");
-pub const VERSION: &str = env!("CARGO_PKG_VERSION");
-pub const PKG_NAME: &str = env!("CARGO_PKG_NAME");
-pub const PKG_DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
-pub const PKG_HOMEPAGE: &str = env!("CARGO_PKG_HOMEPAGE");
-
-pub type AppData = WebData<Arc<data::Data>>;
-pub type DB = WebData<Box<dyn db_core::GPDatabse>>;
-
-lazy_static! {
-    pub static ref FILES: FileMap = FileMap::new();
-}
+use gitpad::*;
 
 #[cfg(not(tarpaulin_include))]
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let settings = Settings::new().unwrap();
     pretty_env_logger::init();
+
     lazy_static::initialize(&pages::TEMPLATES);
     info!(
         "{}: {}.\nFor more information, see: {}\nBuild info:\nVersion: {} commit: {}",
@@ -78,13 +42,16 @@ async fn main() -> std::io::Result<()> {
     let socket_addr = settings.server.get_ip();
 
     log::info!("DB type: {}", settings.database.database_type);
-    let db = match settings.database.database_type {
-        settings::DBType::Sqlite => db::sqlite::get_data(Some(settings.clone())).await,
-        settings::DBType::Postgres => db::pg::get_data(Some(settings.clone())).await,
-    };
-    let db = WebData::new(db);
+    let db = WebData::new(db::get_data(&settings).await);
 
-    let data = WebData::new(data::Data::new(Some(settings)));
+    let data = WebData::new(Data::new(Some(settings)));
+    if let Some(interval) = data.settings.fsck.interval {
+        fsck::run(
+            db.as_ref().clone(),
+            data.clone(),
+            Duration::from_secs(interval),
+        );
+    }
     HttpServer::new(move || {
         App::new()
             .wrap(actix_middleware::Logger::default())
@@ -108,23 +75,3 @@ async fn main() -> std::io::Result<()> {
     .run()
     .await
 }
-
-#[cfg(not(tarpaulin_include))]
-pub fn get_json_err() -> JsonConfig {
-    JsonConfig::default()
-        .error_handler(|err, _| InternalError::new(err, StatusCode::BAD_REQUEST).into())
-}
-
-#[cfg(not(tarpaulin_include))]
-pub fn get_identity_service(settings: &Settings) -> IdentityService<CookieIdentityPolicy> {
-    let cookie_secret = &settings.server.cookie_secret;
-    IdentityService::new(
-        CookieIdentityPolicy::new(cookie_secret.as_bytes())
-            .path("/")
-            .name("Authorization")
-            //TODO change cookie age
-            .max_age_secs(216000)
-            .domain(&settings.server.domain)
-            .secure(false),
-    )
-}
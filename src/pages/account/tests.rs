@@ -0,0 +1,126 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use actix_web::http::StatusCode;
+use actix_web::test;
+
+use db_core::prelude::*;
+
+use super::tokens::AccessTokenForm;
+
+use crate::data::Data;
+use crate::tests::*;
+use crate::*;
+
+#[actix_rt::test]
+async fn postgres_pages_account_work() {
+    let (db, data) = sqlx_postgres::get_data().await;
+    access_tokens_page_works(data.clone(), db.clone()).await;
+}
+
+#[actix_rt::test]
+async fn sqlite_pages_account_work() {
+    let (db, data) = sqlx_sqlite::get_data().await;
+    access_tokens_page_works(data.clone(), db.clone()).await;
+}
+
+async fn access_tokens_page_works(data: Arc<Data>, db: BoxDB) {
+    const NAME: &str = "accesstokenpageuser";
+    const PASSWORD: &str = "longpassword2";
+    const EMAIL: &str = "accesstokenpageuser@a.com";
+    const NAME2: &str = "accesstokenpageuser2";
+    const EMAIL2: &str = "accesstokenpageuser2@a.com";
+    let db = &db;
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
+    let (_, signin_resp) = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
+    let cookies = get_cookie!(signin_resp);
+    let (_, signin_resp) = data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+    let cookies2 = get_cookie!(signin_resp);
+    let app = get_app!(data, db).await;
+
+    let resp = get_request!(&app, PAGES.account.tokens);
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    let resp = get_request!(&app, PAGES.account.tokens, cookies.clone());
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // tokens must have at least one scope
+    let mut form = AccessTokenForm {
+        name: "ci".into(),
+        ..Default::default()
+    };
+    let resp = test::call_service(
+        &app,
+        post_request!(&form, PAGES.account.tokens, FORM)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(data.get_access_tokens(db, NAME).await.unwrap().is_empty());
+
+    // newly created token is shown once
+    form.read_gists = true;
+    form.write_gists = true;
+    let resp = test::call_service(
+        &app,
+        post_request!(&form, PAGES.account.tokens, FORM)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let tokens = data.get_access_tokens(db, NAME).await.unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(
+        tokens[0].scopes,
+        [AccessTokenScope::ReadGists, AccessTokenScope::WriteGists]
+    );
+    assert!(body.contains(crate::data::api::v1::tokens::ACCESS_TOKEN_PREFIX));
+    let delete_route = PAGES.account.get_delete_token_route(tokens[0].id);
+    assert!(body.contains(&delete_route));
+
+    let resp = get_request!(&app, PAGES.account.tokens, cookies.clone());
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains(&delete_route));
+    assert!(!body.contains(crate::data::api::v1::tokens::ACCESS_TOKEN_PREFIX));
+
+    // tokens can only be revoked by their owners
+    let resp = test::call_service(
+        &app,
+        post_request!(&delete_route)
+            .cookie(cookies2.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(data.get_access_tokens(db, NAME).await.unwrap().len(), 1);
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&delete_route)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert!(data.get_access_tokens(db, NAME).await.unwrap().is_empty());
+
+    let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
+}
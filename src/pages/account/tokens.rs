@@ -0,0 +1,236 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use serde::*;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::data::api::v1::tokens::NewAccessToken;
+use crate::errors::*;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::*;
+
+pub const ACCESS_TOKENS: TemplateFile =
+    TemplateFile::new("access_tokens", "pages/account/tokens.html");
+
+pub fn register_templates(t: &mut tera::Tera) {
+    ACCESS_TOKENS.register(t).expect(ACCESS_TOKENS.name);
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(access_tokens);
+    cfg.service(new_access_token);
+    cfg.service(delete_access_token);
+}
+
+/// Form used to create a personal access token. Each scope is a checkbox.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AccessTokenForm {
+    pub name: String,
+    #[serde(default)]
+    pub read_gists: bool,
+    #[serde(default)]
+    pub write_gists: bool,
+    #[serde(default)]
+    pub account: bool,
+}
+
+impl AccessTokenForm {
+    /// Get scopes selected in the form
+    pub fn scopes(&self) -> Vec<AccessTokenScope> {
+        let mut scopes = Vec::with_capacity(3);
+        if self.read_gists {
+            scopes.push(AccessTokenScope::ReadGists);
+        }
+        if self.write_gists {
+            scopes.push(AccessTokenScope::WriteGists);
+        }
+        if self.account {
+            scopes.push(AccessTokenScope::Account);
+        }
+        scopes
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HTMLAccessToken {
+    pub name: String,
+    pub scopes: Vec<&'static str>,
+    pub created: i64,
+    pub last_used: Option<i64>,
+    pub delete_link: String,
+}
+
+impl From<&AccessToken> for HTMLAccessToken {
+    fn from(t: &AccessToken) -> Self {
+        Self {
+            name: t.name.clone(),
+            scopes: t.scopes.iter().map(|s| s.to_str()).collect(),
+            created: t.created,
+            last_used: t.last_used,
+            delete_link: PAGES.account.get_delete_token_route(t.id),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct AccessTokensPayload<'a> {
+    pub tokens: &'a [HTMLAccessToken],
+    /// token that was just created, shown only once
+    pub new_token: Option<&'a str>,
+    pub form: Option<&'a AccessTokenForm>,
+}
+
+#[derive(Clone)]
+pub struct AccessTokens {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for AccessTokens {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+impl AccessTokens {
+    pub fn new(username: &str, payload: AccessTokensPayload, settings: &Settings) -> Self {
+        let mut ctx = auth_ctx(Some(username), settings);
+        ctx.insert(PAYLOAD_KEY, &payload);
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(ACCESS_TOKENS.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+/// Get personal access tokens of `username`, ready to be rendered
+async fn get_tokens(
+    username: &str,
+    data: &AppData,
+    db: &crate::DB,
+) -> ServiceResult<Vec<HTMLAccessToken>> {
+    Ok(data
+        .get_access_tokens(db.as_ref(), username)
+        .await?
+        .iter()
+        .map(|t| t.into())
+        .collect())
+}
+
+#[my_codegen::get(path = "PAGES.account.tokens", wrap = "super::get_auth_middleware()")]
+async fn access_tokens(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+) -> PageResult<impl Responder, AccessTokens> {
+    let username = id.identity().unwrap();
+    let tokens = get_tokens(&username, &data, &db).await.map_err(|e| {
+        PageError::new(
+            AccessTokens::new(&username, AccessTokensPayload::default(), &data.settings),
+            e,
+        )
+    })?;
+    let payload = AccessTokensPayload {
+        tokens: &tokens,
+        ..Default::default()
+    };
+    let page = AccessTokens::new(&username, payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
+
+#[my_codegen::post(path = "PAGES.account.tokens", wrap = "super::get_auth_middleware()")]
+async fn new_access_token(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    payload: web::Form<AccessTokenForm>,
+) -> PageResult<impl Responder, AccessTokens> {
+    let username = id.identity().unwrap();
+    let res: ServiceResult<NewAccessToken> = data
+        .new_access_token(db.as_ref(), &username, &payload.name, &payload.scopes())
+        .await;
+    let tokens = get_tokens(&username, &data, &db).await.unwrap_or_default();
+    let token = res.map_err(|e| {
+        let page_payload = AccessTokensPayload {
+            tokens: &tokens,
+            new_token: None,
+            form: Some(&payload),
+        };
+        PageError::new(
+            AccessTokens::new(&username, page_payload, &data.settings),
+            e,
+        )
+    })?;
+
+    // the token can't be retrieved later, so it is rendered instead of redirecting
+    let page_payload = AccessTokensPayload {
+        tokens: &tokens,
+        new_token: Some(&token.token),
+        form: None,
+    };
+    let page = AccessTokens::new(&username, page_payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteTokenPath {
+    pub id: i64,
+}
+
+#[my_codegen::post(
+    path = "PAGES.account.delete_token",
+    wrap = "super::get_auth_middleware()"
+)]
+async fn delete_access_token(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<DeleteTokenPath>,
+) -> PageResult<impl Responder, AccessTokens> {
+    let username = id.identity().unwrap();
+    if let Err(e) = data
+        .delete_access_token(db.as_ref(), &username, path.id)
+        .await
+    {
+        let tokens = get_tokens(&username, &data, &db).await.unwrap_or_default();
+        let payload = AccessTokensPayload {
+            tokens: &tokens,
+            ..Default::default()
+        };
+        return Err(PageError::new(
+            AccessTokens::new(&username, payload, &data.settings),
+            e,
+        ));
+    }
+
+    Ok(HttpResponse::Found()
+        .insert_header((http::header::LOCATION, PAGES.account.tokens))
+        .finish())
+}
@@ -0,0 +1,161 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use serde::*;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::errors::*;
+use crate::pages::routes::GistProfilePathComponent;
+use crate::pages::routes::PostCommentPath;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::view::HTMLGistInfo;
+pub use super::*;
+
+pub const GIST_DELETE: TemplateFile =
+    TemplateFile::new("gist_delete", "pages/gists/delete/index.html");
+
+pub fn register_templates(t: &mut tera::Tera) {
+    GIST_DELETE.register(t).expect(GIST_DELETE.name);
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(delete_confirm);
+    cfg.service(delete_submit);
+}
+
+#[derive(Clone)]
+pub struct DeleteGist {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for DeleteGist {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DeletePayload<'a> {
+    pub gist: Option<&'a HTMLGistInfo>,
+}
+
+impl DeleteGist {
+    pub fn new(username: &str, payload: DeletePayload, settings: &Settings) -> Self {
+        let mut ctx = auth_ctx(Some(username), settings);
+        ctx.insert(PAYLOAD_KEY, &payload);
+        if let Some(gist) = payload.gist {
+            ctx.insert(
+                "gist_owner_link",
+                &PAGES.gist.get_profile_route(GistProfilePathComponent {
+                    username: &gist.owner,
+                }),
+            );
+            let gist_path = PostCommentPath {
+                username: gist.owner.clone(),
+                gist: gist.id.clone(),
+            };
+            ctx.insert("gist_link", &PAGES.gist.get_gist_route(&gist_path));
+            ctx.insert("form_action", &PAGES.gist.get_delete_route(&gist_path));
+        }
+
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_DELETE.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+/// Get gist that `username` wants to delete. Only owners can delete their gists.
+async fn get_deletable_gist(
+    username: &str,
+    data: &AppData,
+    db: &crate::DB,
+    path: &PostCommentPath,
+) -> PageResult<Gist, DeleteGist> {
+    let map_err = |e: ServiceError| -> PageError<DeleteGist> {
+        PageError::new(
+            DeleteGist::new(username, DeletePayload::default(), &data.settings),
+            e,
+        )
+    };
+
+    let gist = db.get_gist(&path.gist).await.map_err(|e| {
+        let err: ServiceError = e.into();
+        map_err(err)
+    })?;
+    if gist.owner != username {
+        return match gist.visibility {
+            GistVisibility::Public | GistVisibility::Unlisted => {
+                Err(map_err(ServiceError::UnauthorizedOperation(
+                    "This user is not the owner of the gist to delete it".into(),
+                )))
+            }
+            GistVisibility::Private => Err(map_err(ServiceError::GistNotFound)),
+        };
+    }
+    Ok(gist)
+}
+
+#[my_codegen::get(path = "PAGES.gist.delete", wrap = "super::get_auth_middleware()")]
+async fn delete_confirm(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, DeleteGist> {
+    let username = id.identity().unwrap();
+    let gist = get_deletable_gist(&username, &data, &db, &path).await?;
+    let gist: HTMLGistInfo = gist.into();
+    let payload = DeletePayload { gist: Some(&gist) };
+    let page = DeleteGist::new(&username, payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
+
+#[my_codegen::post(path = "PAGES.gist.delete", wrap = "super::get_auth_middleware()")]
+async fn delete_submit(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, DeleteGist> {
+    let username = id.identity().unwrap();
+    let gist = get_deletable_gist(&username, &data, &db, &path).await?;
+    data.delete_gist(db.as_ref(), &username, &gist.public_id)
+        .await
+        .map_err(|e| {
+            let gist: HTMLGistInfo = gist.into();
+            let payload = DeletePayload { gist: Some(&gist) };
+            PageError::new(DeleteGist::new(&username, payload, &data.settings), e)
+        })?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((http::header::LOCATION, PAGES.home))
+        .finish())
+}
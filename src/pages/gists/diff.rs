@@ -0,0 +1,308 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use serde::*;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::data::api::v1::diff::{DiffHunk, DiffLine, FileDiff, LineType};
+use crate::data::api::v1::gists::GistID;
+use crate::data::api::v1::render_html::GenerateHTML;
+use crate::errors::*;
+use crate::pages::routes::GistProfilePathComponent;
+use crate::pages::routes::PostCommentPath;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::view::HTMLGistInfo;
+pub use super::*;
+
+pub const GIST_DIFF: TemplateFile = TemplateFile::new("gist_diff", "pages/gists/diff/index.html");
+
+pub fn register_templates(t: &mut tera::Tera) {
+    GIST_DIFF.register(t).expect(GIST_DIFF.name);
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(view_diff);
+}
+
+/// Diff layout
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffView {
+    /// old and new lines are interleaved in a single column
+    #[default]
+    Unified,
+    /// old and new revisions are displayed side-by-side
+    Split,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DiffPageQuery {
+    /// old revision. Defaults to the parent of `to`
+    pub from: Option<String>,
+    /// new revision. Defaults to HEAD
+    pub to: Option<String>,
+    pub view: Option<DiffView>,
+}
+
+/// Row in side-by-side diff view
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitRow {
+    /// line from old revision
+    pub left: Option<DiffLine>,
+    /// line from new revision
+    pub right: Option<DiffLine>,
+}
+
+/// Pair deleted lines with the lines that replaced them, so that they appear on the
+/// same row
+fn split_hunk(hunk: &DiffHunk) -> Vec<SplitRow> {
+    fn flush(
+        rows: &mut Vec<SplitRow>,
+        deletions: &mut Vec<DiffLine>,
+        additions: &mut Vec<DiffLine>,
+    ) {
+        let mut deletions = deletions.drain(..);
+        let mut additions = additions.drain(..);
+        loop {
+            let row = SplitRow {
+                left: deletions.next(),
+                right: additions.next(),
+            };
+            if row.left.is_none() && row.right.is_none() {
+                break;
+            }
+            rows.push(row);
+        }
+    }
+
+    let mut rows = Vec::with_capacity(hunk.lines.len());
+    let mut deletions = Vec::new();
+    let mut additions = Vec::new();
+    for line in hunk.lines.iter() {
+        match line.kind {
+            LineType::Context => {
+                flush(&mut rows, &mut deletions, &mut additions);
+                rows.push(SplitRow {
+                    left: Some(line.clone()),
+                    right: Some(line.clone()),
+                });
+            }
+            LineType::Deletion => {
+                if !additions.is_empty() {
+                    flush(&mut rows, &mut deletions, &mut additions);
+                }
+                deletions.push(line.clone());
+            }
+            LineType::Addition => additions.push(line.clone()),
+        }
+    }
+    flush(&mut rows, &mut deletions, &mut additions);
+    rows
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HTMLFileDiff {
+    #[serde(flatten)]
+    pub f: FileDiff,
+    /// rows of each hunk, set only in [DiffView::Split]
+    pub split: Option<Vec<Vec<SplitRow>>>,
+}
+
+impl HTMLFileDiff {
+    pub fn new(mut f: FileDiff, view: DiffView) -> Self {
+        f.generate();
+        let split = if view == DiffView::Split {
+            Some(f.hunks.iter().map(split_hunk).collect())
+        } else {
+            None
+        };
+        Self { f, split }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DiffPayload<'a> {
+    pub gist: Option<&'a HTMLGistInfo>,
+    pub files: Option<&'a [HTMLFileDiff]>,
+    /// commit ID of old revision
+    pub from: Option<String>,
+    /// commit ID of new revision
+    pub to: Option<String>,
+    pub additions: usize,
+    pub deletions: usize,
+    pub view: DiffView,
+}
+
+#[derive(Clone)]
+pub struct GistDiff {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for GistDiff {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+impl GistDiff {
+    pub fn new(username: Option<&str>, payload: DiffPayload, settings: &Settings) -> Self {
+        let mut ctx = auth_ctx(username, settings);
+        ctx.insert(PAYLOAD_KEY, &payload);
+        if let Some(gist) = payload.gist {
+            ctx.insert(
+                "gist_owner_link",
+                &PAGES.gist.get_profile_route(GistProfilePathComponent {
+                    username: &gist.owner,
+                }),
+            );
+            let gist_path = PostCommentPath {
+                username: gist.owner.clone(),
+                gist: gist.id.clone(),
+            };
+            ctx.insert("gist_link", &PAGES.gist.get_gist_route(&gist_path));
+            ctx.insert(
+                "gist_revisions_link",
+                &PAGES.gist.get_revisions_route(&gist_path),
+            );
+
+            // links to switch between diff layouts
+            let mut diff_link = format!("{}?", PAGES.gist.get_diff_route(&gist_path));
+            if let Some(from) = &payload.from {
+                diff_link.push_str(&format!("from={from}&"));
+            }
+            if let Some(to) = &payload.to {
+                diff_link.push_str(&format!("to={to}&"));
+            }
+            ctx.insert("diff_unified_link", &format!("{diff_link}view=unified"));
+            ctx.insert("diff_split_link", &format!("{diff_link}view=split"));
+        }
+
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_DIFF.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+#[my_codegen::get(path = "PAGES.gist.diff")]
+async fn view_diff(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+    query: web::Query<DiffPageQuery>,
+) -> PageResult<impl Responder, GistDiff> {
+    let username = id.identity();
+
+    let map_err = |e: ServiceError| -> PageError<GistDiff> {
+        PageError::new(
+            GistDiff::new(username.as_deref(), DiffPayload::default(), &data.settings),
+            e,
+        )
+    };
+
+    let gist = db.get_gist(&path.gist).await.map_err(|e| {
+        let err: ServiceError = e.into();
+        map_err(err)
+    })?;
+
+    if gist.visibility == GistVisibility::Private && username.as_ref() != Some(&gist.owner) {
+        return Err(map_err(ServiceError::GistNotFound));
+    }
+
+    let mut diff = data
+        .gist_diff(
+            db.as_ref(),
+            &GistID::ID(&path.gist),
+            query.from.as_deref(),
+            query.to.as_deref(),
+        )
+        .await
+        .map_err(map_err)?;
+
+    let view = query.view.unwrap_or_default();
+    let files: Vec<HTMLFileDiff> = diff
+        .files
+        .drain(..)
+        .map(|f| HTMLFileDiff::new(f, view))
+        .collect();
+
+    let gist: HTMLGistInfo = gist.into();
+    let payload = DiffPayload {
+        gist: Some(&gist),
+        files: Some(&files),
+        from: diff.from,
+        to: Some(diff.to),
+        additions: diff.additions,
+        deletions: diff.deletions,
+        view,
+    };
+
+    let page = GistDiff::new(username.as_deref(), payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_hunk, DiffHunk, DiffLine, LineType};
+
+    #[test]
+    fn split_hunk_works() {
+        let line = |kind, old_lineno, new_lineno| DiffLine {
+            kind,
+            old_lineno,
+            new_lineno,
+            content: String::default(),
+        };
+        let hunk = DiffHunk {
+            header: String::default(),
+            old_start: 1,
+            old_lines: 4,
+            new_start: 1,
+            new_lines: 3,
+            lines: vec![
+                line(LineType::Context, Some(1), Some(1)),
+                line(LineType::Deletion, Some(2), None),
+                line(LineType::Deletion, Some(3), None),
+                line(LineType::Addition, None, Some(2)),
+                line(LineType::Context, Some(4), Some(3)),
+            ],
+        };
+
+        let rows = split_hunk(&hunk);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].left, rows[0].right);
+        assert_eq!(rows[1].left.as_ref().unwrap().old_lineno, Some(2));
+        assert_eq!(rows[1].right.as_ref().unwrap().new_lineno, Some(2));
+        assert_eq!(rows[2].left.as_ref().unwrap().old_lineno, Some(3));
+        assert!(rows[2].right.is_none());
+        assert_eq!(rows[3].left.as_ref().unwrap().old_lineno, Some(4));
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::api::v1::gists::ExploreQuery;
+use crate::errors::*;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::view::{list_gists, ListedGist};
+pub use super::*;
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(explore);
+}
+
+/// get explore page route for gists sorted by `sort`, starting after `after`
+fn get_explore_link(sort: GistSort, after: Option<&str>) -> String {
+    let sort = match sort {
+        GistSort::Created => "created",
+        GistSort::Updated => "updated",
+    };
+    match after {
+        Some(after) => format!(
+            "{}?sort={sort}&after={}",
+            PAGES.gist.explore,
+            urlencoding::encode(after)
+        ),
+        None => format!("{}?sort={sort}", PAGES.gist.explore),
+    }
+}
+
+#[derive(Clone)]
+pub struct Explore {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for Explore {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+impl Explore {
+    pub fn new(
+        username: Option<&str>,
+        sort: GistSort,
+        payload: &[ListedGist],
+        next: Option<&str>,
+        settings: &Settings,
+    ) -> Self {
+        let mut ctx = auth_ctx(username, settings);
+        ctx.insert(PAYLOAD_KEY, payload);
+        ctx.insert("explore_sort", &sort);
+        ctx.insert(
+            "explore_created_link",
+            &get_explore_link(GistSort::Created, None),
+        );
+        ctx.insert(
+            "explore_updated_link",
+            &get_explore_link(GistSort::Updated, None),
+        );
+        if let Some(next) = next {
+            ctx.insert("explore_next", &get_explore_link(sort, Some(next)));
+        }
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_EXPLORE.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+#[my_codegen::get(path = "PAGES.gist.explore")]
+async fn explore(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    query: web::Query<ExploreQuery>,
+) -> PageResult<impl Responder, Explore> {
+    let username = id.identity();
+    let sort = query.sort.unwrap_or_default();
+    let map_err = |e: ServiceError| -> PageError<Explore> {
+        PageError::new(
+            Explore::new(username.as_deref(), sort, &[], None, &data.settings),
+            e,
+        )
+    };
+
+    let resp = data
+        .explore_gists(db.as_ref(), sort, query.after.as_deref(), query.limit())
+        .await
+        .map_err(map_err)?;
+    let payload = list_gists(&data, &db, resp.gists).await;
+
+    let page = Explore::new(
+        username.as_deref(),
+        sort,
+        &payload,
+        resp.next.as_deref(),
+        &data.settings,
+    )
+    .render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
@@ -21,7 +21,14 @@ pub use super::{
     TEMPLATES,
 };
 
+pub mod delete;
+pub mod diff;
+pub mod explore;
 pub mod new;
+pub mod profile;
+pub mod revisions;
+pub mod search;
+pub mod starred;
 #[cfg(test)]
 mod tests;
 pub mod view;
@@ -29,16 +36,31 @@ pub mod view;
 pub const GIST_BASE: TemplateFile = TemplateFile::new("gistbase", "pages/gists/base.html");
 pub const GIST_EXPLORE: TemplateFile =
     TemplateFile::new("gist_explore", "pages/gists/explore.html");
+pub const GIST_SEARCH: TemplateFile = TemplateFile::new("gist_search", "pages/gists/search.html");
+pub const GIST_LIST: TemplateFile = TemplateFile::new("gist_list", "pages/gists/_list.html");
 
 pub fn register_templates(t: &mut tera::Tera) {
-    for template in [GIST_BASE, GIST_EXPLORE].iter() {
+    for template in [GIST_BASE, GIST_LIST, GIST_EXPLORE, GIST_SEARCH].iter() {
         template.register(t).expect(template.name);
     }
     new::register_templates(t);
     view::register_templates(t);
+    revisions::register_templates(t);
+    diff::register_templates(t);
+    delete::register_templates(t);
+    profile::register_templates(t);
+    starred::register_templates(t);
 }
 
 pub fn services(cfg: &mut web::ServiceConfig) {
     new::services(cfg);
+    explore::services(cfg);
+    search::services(cfg);
+    // registered before view, so that starred page isn't matched as a gist
+    starred::services(cfg);
     view::services(cfg);
+    profile::services(cfg);
+    revisions::services(cfg);
+    diff::services(cfg);
+    delete::services(cfg);
 }
@@ -21,10 +21,10 @@ use actix_web::http::header::ContentType;
 use serde::*;
 use tera::Context;
 
-use db_core::GistVisibility;
+use db_core::prelude::*;
 
 use crate::data::api::v1::gists::{
-    ContentType as GistContentType, CreateGist, FileInfo, FileType, GistID,
+    ContentType as GistContentType, CreateGist, FileInfo, FileMode, FileType, GistID,
 };
 use crate::errors::*;
 use crate::pages::routes::PostCommentPath;
@@ -42,6 +42,14 @@ pub fn register_templates(t: &mut tera::Tera) {
 pub fn services(cfg: &mut web::ServiceConfig) {
     cfg.service(new);
     cfg.service(new_submit);
+    cfg.service(edit_gist);
+    cfg.service(edit_gist_submit);
+}
+
+/// Gist that is being edited using the new gist form
+pub struct EditTarget<'a> {
+    pub path: &'a PostCommentPath,
+    pub visibility: &'a GistVisibility,
 }
 
 pub struct NewGist {
@@ -93,6 +101,13 @@ impl NewGist {
         TEMPLATES.render(NEW_GIST.name, &self.ctx.borrow()).unwrap()
     }
 
+    /// Submit form to the edit route of `edit`, instead of creating a new gist
+    pub fn set_edit(&self, edit: &EditTarget) {
+        let mut ctx = self.ctx.borrow_mut();
+        ctx.insert("form_action", &PAGES.gist.get_edit_route(edit.path));
+        ctx.insert("visibility", edit.visibility.to_str());
+    }
+
     pub fn page(username: &str, s: &Settings) -> String {
         let p = Self::new(username, s, None, None);
         p.render()
@@ -130,6 +145,7 @@ impl<T: Serialize + ToString> From<FieldNames<T>> for FileInfo {
         FileInfo {
             filename: f.filename.to_string(),
             content: FileType::File(GistContentType::Text(f.content.to_string())),
+            mode: FileMode::Regular,
         }
     }
 }
@@ -204,6 +220,15 @@ fn is_add_file(payload: &serde_json::Value) -> bool {
     payload.get("add_file").is_some()
 }
 
+/// Get index(starts at 0) of the file that should be removed from the form
+fn get_remove_file(payload: &serde_json::Value) -> Option<usize> {
+    let remove_file = payload.get("remove_file")?.as_str()?;
+    match remove_file.parse::<usize>() {
+        Ok(num) if num > 0 => Some(num - 1),
+        _ => None,
+    }
+}
+
 struct FormExtractedData<'a> {
     description: Option<&'a str>,
     visibility: GistVisibility,
@@ -215,17 +240,14 @@ fn extract_form<'a>(
     id: &Identity,
     data: &AppData,
     payload: &'a serde_json::Value,
+    edit: Option<&EditTarget>,
 ) -> PageResult<FormExtractedData<'a>, NewGist> {
     let username = id.identity().unwrap();
     let description = get_description(payload);
-    let gist = FieldNames::<&str>::from_serde_json(payload).map_err(|(resp, e)| {
-        PageError::new(
-            NewGist::new(&username, &data.settings, description, Some(&resp)),
-            e,
-        )
-    })?;
+    let gist = FieldNames::<&str>::from_serde_json(payload)
+        .map_err(|(resp, e)| map_err(&username, data, description, Some(&resp), edit, e))?;
     let visibility = get_visibility(payload)
-        .map_err(|e| map_err(&username, data, description, Some(&gist), e))?;
+        .map_err(|e| map_err(&username, data, description, Some(&gist), edit, e))?;
     let resp = FormExtractedData {
         description,
         visibility,
@@ -241,9 +263,51 @@ fn map_err(
     data: &AppData,
     description: Option<&str>,
     gist: Option<&[FieldNames<&str>]>,
+    edit: Option<&EditTarget>,
     e: ServiceError,
 ) -> PageError<NewGist> {
-    PageError::new(NewGist::new(username, &data.settings, description, gist), e)
+    let page = NewGist::new(username, &data.settings, description, gist);
+    if let Some(edit) = edit {
+        page.set_edit(edit);
+    }
+    PageError::new(page, e)
+}
+
+/// Render form again when files are added or removed from it. Returns `None` when the
+/// form was submitted to save the gist.
+fn rerender_form(
+    payload: &serde_json::Value,
+    form_data: &mut FormExtractedData,
+    data: &AppData,
+    edit: Option<&EditTarget>,
+) -> Option<HttpResponse> {
+    if is_add_file(payload) {
+        form_data.files.push(FieldNames::<&str>::default());
+    } else if let Some(num) = get_remove_file(payload) {
+        if num < form_data.files.len() {
+            form_data.files.remove(num);
+        }
+        if form_data.files.is_empty() {
+            form_data.files.push(FieldNames::<&str>::default());
+        }
+    } else {
+        return None;
+    }
+
+    let page = NewGist::new(
+        &form_data.username,
+        &data.settings,
+        form_data.description,
+        Some(&form_data.files),
+    );
+    if let Some(edit) = edit {
+        page.set_edit(edit);
+    }
+    Some(
+        HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(page.render()),
+    )
 }
 
 #[my_codegen::post(path = "PAGES.gist.new", wrap = "super::get_auth_middleware()")]
@@ -253,20 +317,10 @@ async fn new_submit(
     payload: web::Form<serde_json::Value>,
     id: Identity,
 ) -> PageResult<impl Responder, NewGist> {
-    let mut form_data = extract_form(&id, &data, &payload)?;
-    let html = ContentType::html();
-
-    if is_add_file(&payload) {
-        form_data.files.push(FieldNames::<&str>::default());
-        let page = NewGist::new(
-            &form_data.username,
-            &data.settings,
-            form_data.description,
-            Some(&form_data.files),
-        )
-        .render();
-        return Ok(HttpResponse::Ok().content_type(html).body(page));
-    };
+    let mut form_data = extract_form(&id, &data, &payload, None)?;
+    if let Some(resp) = rerender_form(&payload, &mut form_data, &data, None) {
+        return Ok(resp);
+    }
 
     let mut files: Vec<FileInfo> = Vec::with_capacity(form_data.files.len());
     form_data
@@ -281,6 +335,7 @@ async fn new_submit(
             &data,
             form_data.description,
             Some(&form_data.files),
+            None,
             e,
         )
     };
@@ -311,6 +366,169 @@ async fn new_submit(
         .finish())
 }
 
+/// Get gist that `username` wants to edit. Only owners can edit their gists.
+async fn get_editable_gist(
+    username: &str,
+    data: &AppData,
+    db: &crate::DB,
+    path: &PostCommentPath,
+) -> PageResult<Gist, NewGist> {
+    let map_err = |e: ServiceError| map_err(username, data, None, None, None, e);
+    let gist = db.get_gist(&path.gist).await.map_err(|e| {
+        let e: ServiceError = e.into();
+        map_err(e)
+    })?;
+    if gist.owner != username {
+        return match gist.visibility {
+            GistVisibility::Public | GistVisibility::Unlisted => {
+                Err(map_err(ServiceError::UnauthorizedOperation(
+                    "This user is not the owner of the gist to edit it".into(),
+                )))
+            }
+            GistVisibility::Private => Err(map_err(ServiceError::GistNotFound)),
+        };
+    }
+    Ok(gist)
+}
+
+#[my_codegen::get(path = "PAGES.gist.edit", wrap = "super::get_auth_middleware()")]
+async fn edit_gist(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, NewGist> {
+    let username = id.identity().unwrap();
+    let gist = get_editable_gist(&username, &data, &db, &path).await?;
+    let edit = EditTarget {
+        path: &path,
+        visibility: &gist.visibility,
+    };
+
+    let preview = data
+        .gist_preview(db.as_ref(), &mut GistID::ID(&gist.public_id), None)
+        .await
+        .map_err(|e| map_err(&username, &data, None, None, Some(&edit), e))?;
+
+    // binary files can't be edited in the form, they are preserved when the form is
+    // submitted
+    // nested files are edited with their paths as filenames
+    let preview_files: Vec<FileInfo> = preview
+        .files
+        .into_iter()
+        .flat_map(FileInfo::into_files)
+        .collect();
+    let files: Vec<FieldNames<&str>> = preview_files
+        .iter()
+        .filter_map(|f| match &f.content {
+            FileType::File(GistContentType::Text(content)) => Some(FieldNames {
+                filename: f.filename.as_str(),
+                content: content.as_str(),
+            }),
+            _ => None,
+        })
+        .collect();
+    let files = if files.is_empty() {
+        None
+    } else {
+        Some(files.as_slice())
+    };
+
+    let page = NewGist::new(
+        &username,
+        &data.settings,
+        preview.description.as_deref(),
+        files,
+    );
+    page.set_edit(&edit);
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page.render()))
+}
+
+#[my_codegen::post(path = "PAGES.gist.edit", wrap = "super::get_auth_middleware()")]
+async fn edit_gist_submit(
+    data: AppData,
+    db: crate::DB,
+    payload: web::Form<serde_json::Value>,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, NewGist> {
+    let username = id.identity().unwrap();
+    let gist = get_editable_gist(&username, &data, &db, &path).await?;
+    let edit = EditTarget {
+        path: &path,
+        visibility: &gist.visibility,
+    };
+
+    let mut form_data = extract_form(&id, &data, &payload, Some(&edit))?;
+    let visibility = form_data.visibility.clone();
+    let edit = EditTarget {
+        path: &path,
+        visibility: &visibility,
+    };
+    if let Some(resp) = rerender_form(&payload, &mut form_data, &data, Some(&edit)) {
+        return Ok(resp);
+    }
+
+    let map_err = |e: ServiceError| -> PageError<NewGist> {
+        map_err(
+            &form_data.username,
+            &data,
+            form_data.description,
+            Some(&form_data.files),
+            Some(&edit),
+            e,
+        )
+    };
+
+    let mut files: Vec<FileInfo> = Vec::with_capacity(form_data.files.len());
+    form_data
+        .files
+        .clone()
+        .drain(..)
+        .for_each(|f| files.push(f.into()));
+
+    let preview = data
+        .gist_preview(db.as_ref(), &mut GistID::ID(&gist.public_id), None)
+        .await
+        .map_err(&map_err)?;
+    let preview_files: Vec<FileInfo> = preview
+        .files
+        .into_iter()
+        .flat_map(FileInfo::into_files)
+        .collect();
+    // executables and symlinks keep their modes when they are edited
+    for file in files.iter_mut() {
+        if let Some(f) = preview_files.iter().find(|f| f.filename == file.filename) {
+            file.mode = f.mode;
+        }
+    }
+    preview_files
+        .into_iter()
+        .filter(|f| matches!(f.content, FileType::File(GistContentType::Binary(_))))
+        .filter(|f| !form_data.files.iter().any(|ff| ff.filename == f.filename))
+        .for_each(|f| files.push(f));
+
+    data.replace_files(db.as_ref(), &mut GistID::ID(&gist.public_id), &files)
+        .await
+        .map_err(&map_err)?;
+
+    let msg = UpdateGist {
+        owner: &form_data.username,
+        public_id: &gist.public_id,
+        description: form_data.description,
+        visibility: &visibility,
+    };
+    db.update_gist(&msg).await.map_err(|e| {
+        let e: ServiceError = e.into();
+        map_err(e)
+    })?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((http::header::LOCATION, PAGES.gist.get_gist_route(&path)))
+        .finish())
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -438,5 +656,10 @@ mod tests {
 
         assert!(!is_add_file(&json!({ "foo": "bar"})));
         assert!(is_add_file(&json!({ "add_file": "bar"})));
+
+        assert_eq!(get_remove_file(&json!({ "foo": "bar"})), None);
+        assert_eq!(get_remove_file(&json!({ "remove_file": "bar"})), None);
+        assert_eq!(get_remove_file(&json!({ "remove_file": "0"})), None);
+        assert_eq!(get_remove_file(&json!({ "remove_file": "2"})), Some(1));
     }
 }
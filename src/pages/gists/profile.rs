@@ -0,0 +1,138 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::errors::*;
+use crate::pages::routes::GistProfilePathComponent;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::view::{list_gists, ListedGist};
+pub use super::*;
+
+pub const GIST_PROFILE: TemplateFile =
+    TemplateFile::new("gist_profile", "pages/gists/profile/index.html");
+pub const GIST_PROFILE_TABS: TemplateFile =
+    TemplateFile::new("profile_tabs", "pages/gists/profile/_tabs.html");
+
+pub fn register_templates(t: &mut tera::Tera) {
+    for template in [GIST_PROFILE_TABS, GIST_PROFILE].iter() {
+        template.register(t).expect(template.name);
+    }
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(view_profile);
+}
+
+/// Context shared by all tabs of `profile`'s profile page. `tab` is the active tab.
+pub fn profile_ctx(
+    username: Option<&str>,
+    profile: &str,
+    tab: &str,
+    settings: &Settings,
+) -> Context {
+    let mut ctx = auth_ctx(username, settings);
+    ctx.insert("profile_username", profile);
+    ctx.insert("profile_tab", tab);
+    ctx.insert(
+        "profile_link",
+        &PAGES
+            .gist
+            .get_profile_route(GistProfilePathComponent { username: profile }),
+    );
+    ctx.insert(
+        "profile_starred_link",
+        &PAGES
+            .gist
+            .get_starred_route(GistProfilePathComponent { username: profile }),
+    );
+    ctx
+}
+
+#[derive(Clone)]
+pub struct Profile {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for Profile {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+impl Profile {
+    pub fn new(
+        username: Option<&str>,
+        profile: &str,
+        payload: &[ListedGist],
+        settings: &Settings,
+    ) -> Self {
+        let mut ctx = profile_ctx(username, profile, "gists", settings);
+        ctx.insert(PAYLOAD_KEY, payload);
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_PROFILE.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+#[my_codegen::get(path = "PAGES.gist.profile")]
+async fn view_profile(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<String>,
+) -> PageResult<impl Responder, Profile> {
+    let username = id.identity();
+    let map_err = |e: ServiceError| -> PageError<Profile> {
+        PageError::new(
+            Profile::new(username.as_deref(), &path, &[], &data.settings),
+            e,
+        )
+    };
+
+    if !db
+        .username_exists(&path)
+        .await
+        .map_err(|e| map_err(e.into()))?
+    {
+        return Err(map_err(ServiceError::AccountNotFound));
+    }
+
+    let gists = data
+        .get_user_gists(db.as_ref(), &path, username.as_deref())
+        .await
+        .map_err(map_err)?;
+
+    let payload = list_gists(&data, &db, gists).await;
+
+    let page = Profile::new(username.as_deref(), &path, &payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
@@ -0,0 +1,165 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use serde::*;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::api::v1::gists::RevisionsQuery;
+use crate::data::api::v1::gists::GistID;
+use crate::data::api::v1::revisions::Revision;
+use crate::errors::*;
+use crate::pages::routes::GistProfilePathComponent;
+use crate::pages::routes::PostCommentPath;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::view::HTMLGistInfo;
+pub use super::*;
+
+pub const GIST_REVISIONS: TemplateFile =
+    TemplateFile::new("gist_revisions", "pages/gists/revisions/index.html");
+
+pub fn register_templates(t: &mut tera::Tera) {
+    GIST_REVISIONS.register(t).expect(GIST_REVISIONS.name);
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(view_revisions);
+}
+
+#[derive(Clone)]
+pub struct GistRevisions {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for GistRevisions {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RevisionsPayload<'a> {
+    pub gist: Option<&'a HTMLGistInfo>,
+    pub revisions: Option<&'a [Revision]>,
+    /// link to previous page of revisions
+    pub prev: Option<String>,
+    /// link to next page of revisions
+    pub next: Option<String>,
+}
+
+impl GistRevisions {
+    pub fn new(username: Option<&str>, payload: RevisionsPayload, settings: &Settings) -> Self {
+        let mut ctx = auth_ctx(username, settings);
+        ctx.insert(PAYLOAD_KEY, &payload);
+        if let Some(gist) = payload.gist {
+            ctx.insert(
+                "gist_owner_link",
+                &PAGES.gist.get_profile_route(GistProfilePathComponent {
+                    username: &gist.owner,
+                }),
+            );
+            let gist_path = PostCommentPath {
+                username: gist.owner.clone(),
+                gist: gist.id.clone(),
+            };
+            ctx.insert("gist_link", &PAGES.gist.get_gist_route(&gist_path));
+            ctx.insert(
+                "gist_revisions_link",
+                &PAGES.gist.get_revisions_route(&gist_path),
+            );
+            ctx.insert("gist_diff_link", &PAGES.gist.get_diff_route(&gist_path));
+        }
+
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_REVISIONS.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+#[my_codegen::get(path = "PAGES.gist.revisions")]
+async fn view_revisions(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+    query: web::Query<RevisionsQuery>,
+) -> PageResult<impl Responder, GistRevisions> {
+    let username = id.identity();
+
+    let map_err = |e: ServiceError| -> PageError<GistRevisions> {
+        PageError::new(
+            GistRevisions::new(
+                username.as_deref(),
+                RevisionsPayload::default(),
+                &data.settings,
+            ),
+            e,
+        )
+    };
+
+    let gist = db.get_gist(&path.gist).await.map_err(|e| {
+        let err: ServiceError = e.into();
+        map_err(err)
+    })?;
+
+    if gist.visibility == GistVisibility::Private && username.as_ref() != Some(&gist.owner) {
+        return Err(map_err(ServiceError::GistNotFound));
+    }
+
+    let page = query.page();
+    let limit = query.limit();
+    let revisions = data
+        .gist_revisions(db.as_ref(), &GistID::ID(&path.gist), page, limit)
+        .await
+        .map_err(map_err)?;
+
+    let route = PAGES.gist.get_revisions_route(&path);
+    let prev = if page > 0 {
+        Some(format!("{route}?page={}&limit={limit}", page - 1))
+    } else {
+        None
+    };
+    let next = if revisions.len() == limit {
+        Some(format!("{route}?page={}&limit={limit}", page + 1))
+    } else {
+        None
+    };
+
+    let gist: HTMLGistInfo = gist.into();
+    let payload = RevisionsPayload {
+        gist: Some(&gist),
+        revisions: Some(&revisions),
+        prev,
+        next,
+    };
+
+    let page = GistRevisions::new(username.as_deref(), payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
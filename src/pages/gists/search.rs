@@ -0,0 +1,114 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use tera::Context;
+
+use crate::api::v1::gists::SearchQuery;
+use crate::errors::*;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::view::{list_gists, ListedGist};
+pub use super::*;
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(search);
+}
+
+/// get search page route for `query`, at page number `page`
+fn get_search_link(query: &SearchQuery, page: usize) -> String {
+    format!(
+        "{}?q={}&page={page}",
+        PAGES.gist.search,
+        urlencoding::encode(&query.q)
+    )
+}
+
+#[derive(Clone)]
+pub struct Search {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for Search {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+impl Search {
+    pub fn new(
+        username: Option<&str>,
+        query: &SearchQuery,
+        payload: &[ListedGist],
+        settings: &Settings,
+    ) -> Self {
+        let mut ctx = auth_ctx(username, settings);
+        ctx.insert(PAYLOAD_KEY, payload);
+        ctx.insert("search_query", &query.q);
+        let page = query.page.unwrap_or_default();
+        if page > 0 {
+            ctx.insert("search_prev", &get_search_link(query, page - 1));
+        }
+        if payload.len() == query.limit() {
+            ctx.insert("search_next", &get_search_link(query, page + 1));
+        }
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_SEARCH.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+#[my_codegen::get(path = "PAGES.gist.search")]
+async fn search(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    query: web::Query<SearchQuery>,
+) -> PageResult<impl Responder, Search> {
+    let username = id.identity();
+    let map_err = |e: ServiceError| -> PageError<Search> {
+        PageError::new(
+            Search::new(username.as_deref(), &query, &[], &data.settings),
+            e,
+        )
+    };
+
+    let gists = data
+        .search_gists(
+            db.as_ref(),
+            &query.q,
+            username.as_deref(),
+            query.page.unwrap_or_default(),
+            query.limit(),
+        )
+        .await
+        .map_err(map_err)?;
+    let payload = list_gists(&data, &db, gists).await;
+
+    let page = Search::new(username.as_deref(), &query, &payload, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
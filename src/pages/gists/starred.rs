@@ -0,0 +1,110 @@
+/*
+ * Copyright (C) 2022  Aravinth Manivannan <realaravinth@batsense.net>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+
+use actix_identity::Identity;
+use actix_web::http::header::ContentType;
+use tera::Context;
+
+use db_core::prelude::*;
+
+use crate::errors::*;
+use crate::settings::Settings;
+use crate::AppData;
+
+pub use super::profile::profile_ctx;
+pub use super::view::GistLink;
+pub use super::*;
+
+pub const GIST_STARRED: TemplateFile =
+    TemplateFile::new("gist_starred", "pages/gists/profile/starred.html");
+
+pub fn register_templates(t: &mut tera::Tera) {
+    GIST_STARRED.register(t).expect(GIST_STARRED.name);
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(starred);
+}
+
+#[derive(Clone)]
+pub struct StarredGists {
+    ctx: RefCell<Context>,
+}
+
+impl CtxError for StarredGists {
+    fn with_error(&self, e: &ReadableError) -> String {
+        self.ctx.borrow_mut().insert(ERROR_KEY, e);
+        self.render()
+    }
+}
+
+impl StarredGists {
+    pub fn new(
+        username: Option<&str>,
+        profile: &str,
+        payload: &[GistLink],
+        settings: &Settings,
+    ) -> Self {
+        let mut ctx = profile_ctx(username, profile, "starred", settings);
+        ctx.insert(PAYLOAD_KEY, payload);
+        let ctx = RefCell::new(ctx);
+        Self { ctx }
+    }
+
+    pub fn render(&self) -> String {
+        TEMPLATES
+            .render(GIST_STARRED.name, &self.ctx.borrow())
+            .unwrap()
+    }
+}
+
+#[my_codegen::get(path = "PAGES.gist.starred")]
+async fn starred(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<String>,
+) -> PageResult<impl Responder, StarredGists> {
+    let username = id.identity();
+    let map_err = |e: ServiceError| -> PageError<StarredGists> {
+        PageError::new(
+            StarredGists::new(username.as_deref(), &path, &[], &data.settings),
+            e,
+        )
+    };
+
+    if !db
+        .username_exists(&path)
+        .await
+        .map_err(|e| map_err(e.into()))?
+    {
+        return Err(map_err(ServiceError::AccountNotFound));
+    }
+
+    let gists: Vec<GistLink> = data
+        .get_starred_gists(db.as_ref(), &path, username.as_deref())
+        .await
+        .map_err(map_err)?
+        .iter()
+        .map(|g| g.into())
+        .collect();
+
+    let page = StarredGists::new(username.as_deref(), &path, &gists, &data.settings).render();
+    let html = ContentType::html();
+    Ok(HttpResponse::Ok().content_type(html).body(page))
+}
@@ -25,6 +25,7 @@ use pages::routes::PostCommentPath;
 use super::new::*;
 
 use crate::api::v1::gists::PostCommentRequest;
+use crate::data::api::v1::gists::{ContentType, FileInfo, FileMode, FileType, GistID};
 use crate::data::Data;
 use crate::errors::*;
 use crate::tests::*;
@@ -46,10 +47,13 @@ async fn gists_new_route_works(data: Arc<Data>, db: BoxDB) {
     const NAME: &str = "newgisttestuserexists";
     const PASSWORD: &str = "longpassword2";
     const EMAIL: &str = "newgisttestuserexists@a.com2";
+    const NAME2: &str = "newgisttestuserexists2";
+    const EMAIL2: &str = "newgisttestuserexists2@a.com2";
     const COMMENT: &str = "this string is never used anywhere but for commenting, so that I can get away with body inlcudes";
     let db = &db;
 
     let _ = data.delete_user(db, NAME, PASSWORD).await;
+    let _ = data.delete_user(db, NAME2, PASSWORD).await;
 
     let (_, signin_resp) = data.register_and_signin(db, NAME, EMAIL, PASSWORD).await;
     let cookies = get_cookie!(signin_resp);
@@ -117,6 +121,325 @@ async fn gists_new_route_works(data: Arc<Data>, db: BoxDB) {
     let gist_html_page = get_request!(&app, &gist_html_route);
     assert_eq!(gist_html_page.status(), StatusCode::OK);
 
+    // profile lists private gists only to their owner
+    let gist_link = PAGES.gist.get_gist_route(&PostCommentPath {
+        username: NAME.into(),
+        gist: gist.into(),
+    });
+    let profile_route = PAGES
+        .gist
+        .get_profile_route(pages::routes::GistProfilePathComponent { username: NAME });
+    let profile_page = get_request!(&app, &profile_route, cookies.clone());
+    assert_eq!(profile_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(profile_page).await.to_vec()).unwrap();
+    assert!(body.contains(&gist_link));
+    assert!(body.contains("foo.md"));
+    let profile_page = get_request!(&app, &profile_route);
+    assert_eq!(profile_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(profile_page).await.to_vec()).unwrap();
+    assert!(!body.contains(&gist_link));
+    let profile_page = get_request!(
+        &app,
+        &PAGES
+            .gist
+            .get_profile_route(pages::routes::GistProfilePathComponent {
+                username: "nonexistentuserprofile",
+            })
+    );
+    assert_eq!(profile_page.status(), StatusCode::NOT_FOUND);
+
+    // gist revisions
+    let revisions_route = PAGES.gist.get_revisions_route(&gist_route_componenet);
+    let revisions_page = get_request!(&app, &revisions_route, cookies.clone());
+    assert_eq!(revisions_page.status(), StatusCode::OK);
+    // private gist
+    let revisions_page = get_request!(&app, &revisions_route);
+    assert_eq!(revisions_page.status(), StatusCode::NOT_FOUND);
+
+    // gist at revision
+    let revisions = data
+        .gist_revisions(db, &GistID::ID(gist), 0, 1)
+        .await
+        .unwrap();
+    let rev_route = PAGES
+        .gist
+        .get_gist_revision_route(&gist_route_componenet, &revisions[0].id);
+    let rev_page = get_request!(&app, &rev_route, cookies.clone());
+    assert_eq!(rev_page.status(), StatusCode::OK);
+    let rev_route = PAGES
+        .gist
+        .get_gist_revision_route(&gist_route_componenet, "zzzzzzzz");
+    let rev_page = get_request!(&app, &rev_route, cookies.clone());
+    assert_eq!(
+        rev_page.status(),
+        ServiceError::RevisionNotFound.status_code()
+    );
+
+    // gist diff
+    let diff_route = PAGES.gist.get_diff_route(&gist_route_componenet);
+    let diff_page = get_request!(&app, &diff_route, cookies.clone());
+    assert_eq!(diff_page.status(), StatusCode::OK);
+    let diff_page = get_request!(&app, &format!("{diff_route}?view=split"), cookies.clone());
+    assert_eq!(diff_page.status(), StatusCode::OK);
+    let diff_page = get_request!(&app, &format!("{diff_route}?from=zzzz"), cookies.clone());
+    assert_eq!(
+        diff_page.status(),
+        ServiceError::RevisionNotFound.status_code()
+    );
+    // private gist
+    let diff_page = get_request!(&app, &diff_route);
+    assert_eq!(diff_page.status(), StatusCode::NOT_FOUND);
+
+    // edit gist
+    let gist_path = PostCommentPath {
+        username: NAME.into(),
+        gist: gist.into(),
+    };
+    let edit_route = PAGES.gist.get_edit_route(&gist_path);
+    let edit_page = get_request!(&app, &edit_route);
+    assert_eq!(edit_page.status(), StatusCode::FOUND);
+    let (_, signin_resp2) = data.register_and_signin(db, NAME2, EMAIL2, PASSWORD).await;
+    let cookies2 = get_cookie!(signin_resp2);
+    let edit_page = get_request!(&app, &edit_route, cookies2.clone());
+    assert_eq!(edit_page.status(), StatusCode::NOT_FOUND);
+    let edit_page = get_request!(&app, &edit_route, cookies.clone());
+    assert_eq!(edit_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(edit_page).await.to_vec()).unwrap();
+    assert!(body.contains("Update Gist"));
+    assert!(body.contains(&edit_route));
+
+    let files2 = FieldNames::<String>::new(2);
+    let payload = serde_json::json!({
+        "description": "edited",
+        "visibility": GistVisibility::Public.to_str(),
+        files.filename.clone() : "foo.rs",
+        files.content.clone() : "fn foo() {}",
+        files2.filename.clone() : "bar.md",
+        files2.content.clone() : "bar",
+    });
+
+    // remove file from form
+    let mut remove_file = payload.clone();
+    remove_file["remove_file"] = "2".into();
+    let resp = test::call_service(
+        &app,
+        post_request!(&remove_file, &edit_route, FORM)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(!body.contains("bar.md"));
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&payload, &edit_route, FORM)
+            .cookie(cookies2.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&payload, &edit_route, FORM)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(
+        resp.headers().get(header::LOCATION).unwrap(),
+        &PAGES.gist.get_gist_route(&gist_path)
+    );
+    let edited = data
+        .gist_preview(db, &mut GistID::ID(gist), None)
+        .await
+        .unwrap();
+    assert_eq!(edited.description.as_deref(), Some("edited"));
+    assert_eq!(edited.visibility, GistVisibility::Public);
+    assert_eq!(edited.files.len(), 2);
+    assert!(edited.files.iter().all(|f| f.filename != "foo.md"));
+
+    // owner sees edit link
+    let gist_page = get_request!(&app, &gist_html_route, cookies.clone());
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(body.contains(&edit_route));
+    let gist_page = get_request!(&app, &gist_html_route, cookies2.clone());
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(!body.contains(&edit_route));
+
+    // fork gist
+    let fork_route = PAGES.gist.get_fork_route(&gist_path);
+    let gist_page = get_request!(&app, &gist_html_route, cookies.clone());
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(!body.contains(&fork_route));
+    let gist_page = get_request!(&app, &gist_html_route, cookies2.clone());
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(body.contains(&fork_route));
+
+    let resp = test::call_service(&app, post_request!(&fork_route).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert!(!db
+        .get_forks(gist)
+        .await
+        .unwrap()
+        .iter()
+        .any(|f| f.owner == NAME));
+    let resp = test::call_service(
+        &app,
+        post_request!(&fork_route)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(
+        &app,
+        post_request!(&fork_route)
+            .cookie(cookies2.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    let fork_link = resp
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(fork_link.starts_with(&format!("/~{NAME2}/")));
+    let fork_page = get_request!(&app, fork_link);
+    assert_eq!(fork_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(fork_page).await.to_vec()).unwrap();
+    assert!(body.contains("Forked from"));
+    assert!(body.contains(&PAGES.gist.get_gist_route(&gist_path)));
+    let gist_page = get_request!(&app, &gist_html_route);
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(body.contains(fork_link));
+
+    // public gists are listed on profile
+    let profile_page = get_request!(&app, &profile_route);
+    let body = String::from_utf8(test::read_body(profile_page).await.to_vec()).unwrap();
+    assert!(body.contains(&gist_link));
+    assert!(body.contains("edited"));
+
+    // recently updated public gists are listed on explore page
+    let explore_page = get_request!(&app, &format!("{}?sort=updated", PAGES.gist.explore));
+    assert_eq!(explore_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(explore_page).await.to_vec()).unwrap();
+    assert!(body.contains(&gist_link));
+    let explore_page = get_request!(&app, &format!("{}?after=foo", PAGES.gist.explore));
+    assert_eq!(explore_page.status(), StatusCode::BAD_REQUEST);
+
+    // public gists can be searched by their description
+    let search_page = get_request!(&app, PAGES.gist.search);
+    assert_eq!(search_page.status(), StatusCode::OK);
+    let search_page = get_request!(&app, &format!("{}?q=edited&limit=100", PAGES.gist.search));
+    assert_eq!(search_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(search_page).await.to_vec()).unwrap();
+    assert!(body.contains(&gist_link));
+
+    // star gist
+    let star_route = PAGES.gist.get_star_route(&gist_path);
+    let unstar_route = PAGES.gist.get_unstar_route(&gist_path);
+    let starred_route = PAGES
+        .gist
+        .get_starred_route(pages::routes::GistProfilePathComponent { username: NAME2 });
+    let gist_page = get_request!(&app, &gist_html_route);
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(!body.contains(&star_route));
+    assert!(body.contains("0 stars"));
+    let resp = test::call_service(&app, post_request!(&star_route).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert!(!db.is_starred(NAME2, gist).await.unwrap());
+
+    let gist_page = get_request!(&app, &gist_html_route, cookies2.clone());
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(body.contains(&star_route));
+    let resp = test::call_service(
+        &app,
+        post_request!(&star_route)
+            .cookie(cookies2.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(
+        resp.headers().get(header::LOCATION).unwrap(),
+        &PAGES.gist.get_gist_route(&gist_path)
+    );
+    assert!(db.is_starred(NAME2, gist).await.unwrap());
+    let gist_page = get_request!(&app, &gist_html_route, cookies2.clone());
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(body.contains(&unstar_route));
+    assert!(body.contains("1 star"));
+
+    let starred_page = get_request!(&app, &starred_route);
+    assert_eq!(starred_page.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(starred_page).await.to_vec()).unwrap();
+    assert!(body.contains(&PAGES.gist.get_gist_route(&gist_path)));
+    let starred_page = get_request!(
+        &app,
+        &PAGES
+            .gist
+            .get_starred_route(pages::routes::GistProfilePathComponent {
+                username: "nonexistentuserstarred",
+            })
+    );
+    assert_eq!(starred_page.status(), StatusCode::NOT_FOUND);
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&unstar_route)
+            .cookie(cookies2.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert!(!db.is_starred(NAME2, gist).await.unwrap());
+    let starred_page = get_request!(&app, &starred_route);
+    let body = String::from_utf8(test::read_body(starred_page).await.to_vec()).unwrap();
+    assert!(!body.contains(&PAGES.gist.get_gist_route(&gist_path)));
+
+    // nested files are rendered under their directories
+    let nested = [
+        FileInfo {
+            filename: "src/nested.rs".into(),
+            content: FileType::File(ContentType::Text("fn nested() {}".into())),
+            mode: FileMode::Regular,
+        },
+        FileInfo {
+            filename: "src/link.rs".into(),
+            content: FileType::File(ContentType::Text("./nested.rs".into())),
+            mode: FileMode::Symlink,
+        },
+        FileInfo {
+            filename: "run.sh".into(),
+            content: FileType::File(ContentType::Text("echo foo".into())),
+            mode: FileMode::Executable,
+        },
+    ];
+    data.write_file(db, &mut GistID::ID(gist), &nested)
+        .await
+        .unwrap();
+    let gist_page = get_request!(&app, &gist_html_route);
+    let body = String::from_utf8(test::read_body(gist_page).await.to_vec()).unwrap();
+    assert!(body.contains(r#"id="src""#));
+    assert!(body.contains(r#"id="src/nested.rs""#));
+    assert!(body.contains(&format!("{gist}/contents/src/nested.rs")));
+    assert!(body.contains(&format!("{gist}/raw/src/nested.rs")));
+    // archive download links
+    assert!(body.contains(&V1_API_ROUTES.gist.get_archive_route(
+        &gist_path,
+        crate::data::api::v1::archive::ArchiveFormat::TarGz
+    )));
+    // symlinks link to their targets
+    assert!(body.contains("Symbolic link to"));
+    assert!(body.contains(r##"class="gist__symlink-target" href="#src/nested.rs""##));
+    assert!(body.contains(r#"class="gist__filename-mode""#));
+
     // post comment
     let comment_url = PAGES.gist.get_post_comment_route(&gist_route_componenet);
     let comment = PostCommentRequest {
@@ -125,9 +448,53 @@ async fn gists_new_route_works(data: Arc<Data>, db: BoxDB) {
     let comment_resp = test::call_service(
         &app,
         post_request!(&comment, &comment_url, FORM)
-            .cookie(cookies)
+            .cookie(cookies.clone())
             .to_request(),
     )
     .await;
     assert_eq!(comment_resp.status(), StatusCode::FOUND);
+
+    // delete gist
+    let delete_route = PAGES.gist.get_delete_route(&gist_path);
+    let body = String::from_utf8(
+        test::read_body(get_request!(&app, &gist_html_route, cookies.clone()))
+            .await
+            .to_vec(),
+    )
+    .unwrap();
+    assert!(body.contains(&delete_route));
+    let delete_page = get_request!(&app, &delete_route);
+    assert_eq!(delete_page.status(), StatusCode::FOUND);
+    let delete_page = get_request!(&app, &delete_route, cookies2.clone());
+    assert_eq!(
+        delete_page.status(),
+        ServiceError::UnauthorizedOperation("".into()).status_code()
+    );
+    let delete_page = get_request!(&app, &delete_route, cookies.clone());
+    assert_eq!(delete_page.status(), StatusCode::OK);
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&delete_route)
+            .cookie(cookies2.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(
+        resp.status(),
+        ServiceError::UnauthorizedOperation("".into()).status_code()
+    );
+    assert!(db.gist_exists(gist).await.unwrap());
+
+    let resp = test::call_service(
+        &app,
+        post_request!(&delete_route)
+            .cookie(cookies.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert!(!db.gist_exists(gist).await.unwrap());
+    let gist_page = get_request!(&app, &gist_html_route, cookies.clone());
+    assert_eq!(gist_page.status(), StatusCode::NOT_FOUND);
 }
@@ -23,10 +23,12 @@ use tera::Context;
 
 use db_core::prelude::*;
 
-use crate::api::v1::{gists::PostCommentRequest, routes::GetFilePath};
+use crate::api::v1::gists::{PostCommentRequest, RevisionQuery};
+use crate::api::v1::routes::GetFilePath;
+use crate::data::api::v1::archive::ArchiveFormat;
 use crate::data::api::v1::render_html::GenerateHTML;
 use crate::data::api::v1::{
-    gists::{FileInfo, GistID, GistInfo},
+    gists::{ContentType as GistContentType, FileInfo, FileMode, FileType, GistID, GistInfo},
     render_html::render_markdown,
 };
 use crate::errors::*;
@@ -42,6 +44,8 @@ pub const GIST_TEXTFILE: TemplateFile =
     TemplateFile::new("gist_textfile", "pages/gists/view/_text.html");
 pub const GIST_FILENAME: TemplateFile =
     TemplateFile::new("gist_filename", "pages/gists/view/_filename.html");
+pub const GIST_DIRNAME: TemplateFile =
+    TemplateFile::new("gist_dirname", "pages/gists/view/_dirname.html");
 
 pub const GIST_META: TemplateFile = TemplateFile::new("gist_meta", "pages/gists/view/_meta.html");
 
@@ -55,6 +59,7 @@ pub fn register_templates(t: &mut tera::Tera) {
     for template in [
         VIEW_GIST,
         GIST_FILENAME,
+        GIST_DIRNAME,
         GIST_TEXTFILE,
         GIST_COMMENT_INPUT,
         GIST_COMMENTS,
@@ -69,17 +74,121 @@ pub fn register_templates(t: &mut tera::Tera) {
 pub fn services(cfg: &mut web::ServiceConfig) {
     cfg.service(view_preview);
     cfg.service(post_comment);
+    cfg.service(fork_gist);
+    cfg.service(star_gist);
+    cfg.service(unstar_gist);
+}
+
+/// Link to a gist, used to list related gists
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GistLink {
+    pub owner: String,
+    pub id: String,
+    pub link: String,
+    pub description: Option<String>,
+}
+
+impl From<&Gist> for GistLink {
+    fn from(g: &Gist) -> Self {
+        let link = PAGES.gist.get_gist_route(&PostCommentPath {
+            username: g.owner.clone(),
+            gist: g.public_id.clone(),
+        });
+        Self {
+            owner: g.owner.clone(),
+            id: g.public_id.clone(),
+            link,
+            description: g.description.clone(),
+        }
+    }
+}
+
+/// Gist in a list of gists. Only the first file is previewed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ListedGist {
+    pub gist: HTMLGistInfo,
+    pub link: String,
+    /// number of files in the gist
+    pub files: usize,
+}
+
+/// Prepare `gists` for listing. Gists that can't be previewed, like the ones without any
+/// commits, are listed without files.
+pub async fn list_gists(data: &AppData, db: &crate::DB, gists: Vec<Gist>) -> Vec<ListedGist> {
+    let mut list = Vec::with_capacity(gists.len());
+    for gist in gists.into_iter() {
+        let link = PAGES.gist.get_gist_route(&PostCommentPath {
+            username: gist.owner.clone(),
+            gist: gist.public_id.clone(),
+        });
+        let (gist, files) = match data
+            .gist_preview(db.as_ref(), &mut GistID::ID(&gist.public_id), None)
+            .await
+        {
+            Ok(mut info) => {
+                let mut files: Vec<FileInfo> = info
+                    .files
+                    .drain(..)
+                    .flat_map(FileInfo::into_files)
+                    .collect();
+                let count = files.len();
+                files.truncate(1);
+                info.files = files;
+                (HTMLGistInfo::from(info), count)
+            }
+            Err(_) => (HTMLGistInfo::from(gist), 0),
+        };
+        list.push(ListedGist { gist, link, files });
+    }
+    list
 }
 
 #[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct HTMLFileInfo {
+    /// file or directory. Filename is the path from root of the gist and contents of
+    /// directories are moved out, see [HTMLFileInfo::flatten]
     pub f: FileInfo,
     pub raw: String,
     pub highlighted_link: String,
+    /// number of directories that the file is nested in
+    pub depth: usize,
+    /// anchor of the file that a symlink points to, when the file is in the gist
+    pub symlink: Option<String>,
+}
+
+/// Resolve symlink `target` relative to the directory of the symlink at `path`. Returns
+/// `None` when `target` points outside the gist.
+fn resolve_symlink(path: &str, target: &str) -> Option<String> {
+    if target.starts_with('/') {
+        return None;
+    }
+    let mut components: Vec<&str> = path.split('/').collect();
+    components.pop();
+    for component in target.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        None
+    } else {
+        Some(format!("#{}", components.join("/")))
+    }
 }
 
 impl HTMLFileInfo {
-    pub fn new(mut f: FileInfo, owner: &str, gist_public_id: &str) -> Self {
+    /// links are pinned to `rev` when it is set
+    pub fn new(
+        mut f: FileInfo,
+        depth: usize,
+        owner: &str,
+        gist_public_id: &str,
+        rev: Option<&str>,
+    ) -> Self {
         f.generate();
         let owner = owner.to_string();
         let gist = gist_public_id.into();
@@ -88,14 +197,54 @@ impl HTMLFileInfo {
             gist,
             file: f.filename,
         };
-        let raw = crate::V1_API_ROUTES.gist.get_file_route(&raw_component);
-        let highlighted_link = PAGES.gist.get_file_route(&raw_component);
+        let mut raw = crate::V1_API_ROUTES.gist.get_raw_route(&raw_component);
+        let mut highlighted_link = PAGES.gist.get_file_route(&raw_component);
+        if let Some(rev) = rev {
+            raw = format!("{raw}?rev={rev}");
+            highlighted_link = format!("{highlighted_link}?rev={rev}");
+        }
+        let symlink = match &f.content {
+            FileType::File(GistContentType::Text(target)) if f.mode == FileMode::Symlink => {
+                resolve_symlink(&raw_component.file, target)
+            }
+            _ => None,
+        };
         f.filename = raw_component.file;
         Self {
             f,
             raw,
             highlighted_link,
+            depth,
+            symlink,
+        }
+    }
+
+    /// Flatten file tree rooted at `f`, so that it can be rendered without recursion.
+    /// Directories are followed by their contents.
+    pub fn flatten(f: FileInfo, owner: &str, gist_public_id: &str, rev: Option<&str>) -> Vec<Self> {
+        fn inner(
+            mut f: FileInfo,
+            depth: usize,
+            owner: &str,
+            gist_public_id: &str,
+            rev: Option<&str>,
+            files: &mut Vec<HTMLFileInfo>,
+        ) {
+            let children = match &mut f.content {
+                FileType::Dir(children) => std::mem::take(children),
+                FileType::File(_) => Vec::default(),
+            };
+            let path = f.filename.clone();
+            files.push(HTMLFileInfo::new(f, depth, owner, gist_public_id, rev));
+            for mut child in children.into_iter() {
+                child.filename = format!("{path}/{}", child.filename);
+                inner(child, depth + 1, owner, gist_public_id, rev, files);
+            }
         }
+
+        let mut files = Vec::default();
+        inner(f, 0, owner, gist_public_id, rev, &mut files);
+        files
     }
 }
 
@@ -108,14 +257,26 @@ pub struct HTMLGistInfo {
     pub updated: i64,
     pub visibility: GistVisibility,
     pub id: String,
+    /// commit ID of the revision that is being displayed
+    pub revision: Option<String>,
+    /// set when a specific revision was requested, instead of HEAD
+    pub pinned: bool,
+    /// number of users that have starred the gist
+    pub stars: Option<i64>,
 }
 
-impl From<GistInfo> for HTMLGistInfo {
-    fn from(mut g: GistInfo) -> Self {
+impl HTMLGistInfo {
+    /// links to files are pinned to [GistInfo::revision] when `pinned` is set
+    pub fn new(mut g: GistInfo, pinned: bool) -> Self {
+        let rev = if pinned {
+            Some(g.revision.as_str())
+        } else {
+            None
+        };
         let mut files = Vec::with_capacity(g.files.len());
         g.files
             .drain(..)
-            .for_each(|f| files.push(HTMLFileInfo::new(f, &g.owner, &g.id)));
+            .for_each(|f| files.extend(HTMLFileInfo::flatten(f, &g.owner, &g.id, rev)));
         Self {
             files,
             description: g.description,
@@ -124,6 +285,32 @@ impl From<GistInfo> for HTMLGistInfo {
             updated: g.updated,
             visibility: g.visibility,
             id: g.id,
+            revision: Some(g.revision),
+            pinned,
+            stars: Some(g.stars),
+        }
+    }
+}
+
+impl From<GistInfo> for HTMLGistInfo {
+    fn from(g: GistInfo) -> Self {
+        Self::new(g, false)
+    }
+}
+
+impl From<Gist> for HTMLGistInfo {
+    fn from(g: Gist) -> Self {
+        Self {
+            files: Vec::default(),
+            description: g.description,
+            owner: g.owner,
+            created: g.created,
+            updated: g.updated,
+            visibility: g.visibility,
+            id: g.public_id,
+            revision: None,
+            pinned: false,
+            stars: None,
         }
     }
 }
@@ -175,6 +362,45 @@ impl ViewGist {
                     gist: gist.id.clone(),
                 }),
             );
+
+            ctx.insert(
+                "gist_revisions_link",
+                &PAGES.gist.get_revisions_route(&PostCommentPath {
+                    username: gist.owner.clone(),
+                    gist: gist.id.clone(),
+                }),
+            );
+
+            let gist_path = PostCommentPath {
+                username: gist.owner.clone(),
+                gist: gist.id.clone(),
+            };
+            ctx.insert("gist_link", &PAGES.gist.get_gist_route(&gist_path));
+            if username == Some(gist.owner.as_str()) {
+                ctx.insert("gist_edit_link", &PAGES.gist.get_edit_route(&gist_path));
+                ctx.insert("gist_delete_link", &PAGES.gist.get_delete_route(&gist_path));
+            } else if username.is_some() && gist.visibility != GistVisibility::Private {
+                ctx.insert("gist_fork_link", &PAGES.gist.get_fork_route(&gist_path));
+            }
+            if let Some(rev) = &gist.revision {
+                ctx.insert(
+                    "gist_permalink",
+                    &PAGES.gist.get_gist_revision_route(&gist_path, rev),
+                );
+                // archives are pinned to the revision that is being viewed
+                for (key, format) in [
+                    ("gist_archive_tar_link", ArchiveFormat::TarGz),
+                    ("gist_archive_zip_link", ArchiveFormat::Zip),
+                ] {
+                    let mut link = crate::V1_API_ROUTES
+                        .gist
+                        .get_archive_route(&gist_path, format);
+                    if gist.pinned {
+                        link = format!("{link}?rev={rev}");
+                    }
+                    ctx.insert(key, &link);
+                }
+            }
         }
 
         if let Some(comments) = payload.comments {
@@ -196,6 +422,28 @@ impl ViewGist {
             .borrow_mut()
             .insert("new_comment", &comment.comment);
     }
+
+    /// set gist from which the gist was forked and forks of the gist
+    pub fn set_forks(&self, parent: Option<&Gist>, forks: &[Gist]) {
+        let mut ctx = self.ctx.borrow_mut();
+        if let Some(parent) = parent {
+            ctx.insert("gist_forked_from", &GistLink::from(parent));
+        }
+        if !forks.is_empty() {
+            let forks: Vec<GistLink> = forks.iter().map(|f| f.into()).collect();
+            ctx.insert("gist_forks", &forks);
+        }
+    }
+
+    /// set star/unstar link, depending on whether the viewer has starred the gist
+    pub fn set_star(&self, gist_path: &PostCommentPath, starred: bool) {
+        let mut ctx = self.ctx.borrow_mut();
+        if starred {
+            ctx.insert("gist_unstar_link", &PAGES.gist.get_unstar_route(gist_path));
+        } else {
+            ctx.insert("gist_star_link", &PAGES.gist.get_star_route(gist_path));
+        }
+    }
 }
 
 async fn view_util(
@@ -203,6 +451,7 @@ async fn view_util(
     db: &crate::DB,
     id: &Identity,
     path: &web::Path<PostCommentPath>,
+    rev: Option<&str>,
 ) -> PageResult<ViewGist, ViewGist> {
     let username = id.identity();
 
@@ -232,12 +481,12 @@ async fn view_util(
     }
 
     let gist = data
-        .gist_preview(db.as_ref(), &mut GistID::ID(&path.gist))
+        .gist_preview(db.as_ref(), &mut GistID::ID(&path.gist), rev)
         .await
         .map_err(|e| map_err(e, None))?;
 
     //gist.files.iter_mut().for_each(|file| file.generate());
-    let gist: HTMLGistInfo = gist.into();
+    let gist = HTMLGistInfo::new(gist, rev.is_some());
 
     let mut comments = db.get_comments_on_gist(&path.gist).await.map_err(|e| {
         let e: ServiceError = e.into();
@@ -248,12 +497,35 @@ async fn view_util(
         .iter_mut()
         .for_each(|mut comment| comment.comment = render_markdown(&comment.comment));
 
+    let parent = db
+        .get_fork_parent(&path.gist)
+        .await
+        .map_err(|e| map_err(e.into(), None))?
+        .filter(|p| p.visibility != GistVisibility::Private || username.as_ref() == Some(&p.owner));
+    let forks = data
+        .get_forks(db.as_ref(), &path.gist, username.as_deref())
+        .await
+        .map_err(|e| map_err(e, None))?;
+
     let ctx = PreviewPayload {
         gist: Some(&gist),
         comments: Some(&comments),
     };
 
-    Ok(ViewGist::new(username.as_deref(), ctx, &data.settings))
+    let page = ViewGist::new(username.as_deref(), ctx, &data.settings);
+    page.set_forks(parent.as_ref(), &forks);
+    if let Some(username) = &username {
+        let starred = db
+            .is_starred(username, &path.gist)
+            .await
+            .map_err(|e| map_err(e.into(), None))?;
+        let gist_path = PostCommentPath {
+            username: gist.owner.clone(),
+            gist: gist.id.clone(),
+        };
+        page.set_star(&gist_path, starred);
+    }
+    Ok(page)
 }
 
 #[my_codegen::get(path = "PAGES.gist.view_gist")]
@@ -262,8 +534,11 @@ async fn view_preview(
     db: crate::DB,
     id: Identity,
     path: web::Path<PostCommentPath>,
+    query: web::Query<RevisionQuery>,
 ) -> PageResult<impl Responder, ViewGist> {
-    let page = view_util(&data, &db, &id, &path).await?.render();
+    let page = view_util(&data, &db, &id, &path, query.rev.as_deref())
+        .await?
+        .render();
     let html = ContentType::html();
     Ok(HttpResponse::Ok().content_type(html).body(page))
 }
@@ -283,7 +558,7 @@ async fn post_comment(
 
     let username = id.identity();
 
-    let page = view_util(&data, &db, &id, &path).await?;
+    let page = view_util(&data, &db, &id, &path, None).await?;
     let map_err = |e: ServiceError| -> PageError<ViewGist> {
         let page = page.clone();
         page.set_comment(&payload);
@@ -326,3 +601,68 @@ async fn post_comment(
         .insert_header((http::header::LOCATION, gist_link.as_str()))
         .finish())
 }
+
+#[my_codegen::post(path = "PAGES.gist.fork", wrap = "super::get_auth_middleware()")]
+async fn fork_gist(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, ViewGist> {
+    let page = view_util(&data, &db, &id, &path, None).await?;
+    let username = id.identity().unwrap();
+
+    let fork = data
+        .fork_gist(db.as_ref(), &username, &path.gist)
+        .await
+        .map_err(|e| PageError::new(page, e))?;
+
+    let fork_link = PAGES.gist.get_gist_route(&PostCommentPath {
+        username,
+        gist: fork.id,
+    });
+    Ok(HttpResponse::Found()
+        .insert_header((http::header::LOCATION, fork_link.as_str()))
+        .finish())
+}
+
+#[my_codegen::post(path = "PAGES.gist.star", wrap = "super::get_auth_middleware()")]
+async fn star_gist(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, ViewGist> {
+    let page = view_util(&data, &db, &id, &path, None).await?;
+    let username = id.identity().unwrap();
+
+    data.star_gist(db.as_ref(), &username, &path.gist)
+        .await
+        .map_err(|e| PageError::new(page, e))?;
+
+    let gist_link = PAGES.gist.get_gist_route(&path);
+    Ok(HttpResponse::Found()
+        .insert_header((http::header::LOCATION, gist_link.as_str()))
+        .finish())
+}
+
+#[my_codegen::post(path = "PAGES.gist.unstar", wrap = "super::get_auth_middleware()")]
+async fn unstar_gist(
+    data: AppData,
+    db: crate::DB,
+    id: Identity,
+    path: web::Path<PostCommentPath>,
+) -> PageResult<impl Responder, ViewGist> {
+    let page = view_util(&data, &db, &id, &path, None).await?;
+    let username = id.identity().unwrap();
+
+    db.unstar_gist(&username, &path.gist).await.map_err(|e| {
+        let e: ServiceError = e.into();
+        PageError::new(page, e)
+    })?;
+
+    let gist_link = PAGES.gist.get_gist_route(&path);
+    Ok(HttpResponse::Found()
+        .insert_header((http::header::LOCATION, gist_link.as_str()))
+        .finish())
+}
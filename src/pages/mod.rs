@@ -24,6 +24,7 @@ use crate::settings::Settings;
 use crate::static_assets::ASSETS;
 use crate::{GIT_COMMIT_HASH, VERSION};
 
+pub mod account;
 pub mod auth;
 pub mod errors;
 pub mod gists;
@@ -70,6 +71,7 @@ lazy_static! {
         errors::register_templates(&mut tera);
         tera.autoescape_on(vec![".html", ".sql"]);
         auth::register_templates(&mut tera);
+        account::register_templates(&mut tera);
         gists::register_templates(&mut tera);
         tera
     };
@@ -143,6 +145,7 @@ impl<'a> Footer<'a> {
 
 pub fn services(cfg: &mut web::ServiceConfig) {
     auth::services(cfg);
+    account::services(cfg);
     gists::services(cfg);
 }
 
@@ -164,6 +167,7 @@ mod tests {
             auth::AUTH_BASE,
             auth::login::LOGIN,
             auth::register::REGISTER,
+            account::tokens::ACCESS_TOKENS,
             errors::ERROR_TEMPLATE,
             gists::GIST_BASE,
             gists::GIST_EXPLORE,
@@ -204,7 +208,14 @@ mod http_page_tests {
     async fn templates_work(data: Arc<Data>, db: BoxDB) {
         let app = get_app!(data, db).await;
 
-        for file in [PAGES.auth.login, PAGES.auth.register].iter() {
+        for file in [
+            PAGES.auth.login,
+            PAGES.auth.register,
+            PAGES.gist.explore,
+            PAGES.gist.search,
+        ]
+        .iter()
+        {
             let resp = get_request!(&app, file);
             assert_eq!(resp.status(), StatusCode::OK);
         }
@@ -27,6 +27,8 @@ pub const PAGES: Pages = Pages::new();
 pub struct Pages {
     /// Authentication routes
     pub auth: Auth,
+    /// Account settings routes
+    pub account: Account,
     /// Gist routes
     pub gist: Gists,
     /// home page
@@ -40,6 +42,7 @@ impl Pages {
         let home = gist.new;
         Pages {
             auth: Auth::new(),
+            account: Account::new(),
             gist,
             home,
         }
@@ -71,6 +74,32 @@ impl Auth {
     }
 }
 
+#[derive(Serialize)]
+/// Account settings routes
+pub struct Account {
+    /// personal access tokens route
+    pub tokens: &'static str,
+    /// revoke personal access token route
+    pub delete_token: &'static str,
+}
+
+impl Account {
+    /// create new instance of Account route
+    pub const fn new() -> Account {
+        let tokens = "/settings/tokens";
+        let delete_token = "/settings/tokens/{id}/delete";
+        Account {
+            tokens,
+            delete_token,
+        }
+    }
+
+    /// get delete_token route with placeholders replaced with values provided.
+    pub fn get_delete_token_route(&self, id: i64) -> String {
+        self.delete_token.replace("{id}", &id.to_string())
+    }
+}
+
 #[derive(Deserialize)]
 pub struct GistProfilePathComponent<'a> {
     pub username: &'a str,
@@ -89,6 +118,26 @@ pub struct Gists {
     pub post_comment: &'static str,
     /// get file
     pub get_file: &'static str,
+    /// gist revision history
+    pub revisions: &'static str,
+    /// diff between two revisions of a gist
+    pub diff: &'static str,
+    /// edit gist
+    pub edit: &'static str,
+    /// delete gist
+    pub delete: &'static str,
+    /// fork gist
+    pub fork: &'static str,
+    /// star gist
+    pub star: &'static str,
+    /// unstar gist
+    pub unstar: &'static str,
+    /// gists starred by user
+    pub starred: &'static str,
+    /// public gists, across all users
+    pub explore: &'static str,
+    /// search gists
+    pub search: &'static str,
 }
 
 impl Gists {
@@ -97,7 +146,17 @@ impl Gists {
         let profile = "/~{username}";
         let view_gist = "/~{username}/{gist}";
         let post_comment = "/~{username}/{gist}/comment";
-        let get_file = "/~{username}/{gist}/contents/{file}";
+        let get_file = "/~{username}/{gist}/contents/{file:.*}";
+        let revisions = "/~{username}/{gist}/revisions";
+        let diff = "/~{username}/{gist}/diff";
+        let edit = "/~{username}/{gist}/edit";
+        let delete = "/~{username}/{gist}/delete";
+        let fork = "/~{username}/{gist}/fork";
+        let star = "/~{username}/{gist}/star";
+        let unstar = "/~{username}/{gist}/unstar";
+        let starred = "/~{username}/starred";
+        let explore = "/explore";
+        let search = "/search";
         let new = "/";
         Self {
             profile,
@@ -105,6 +164,16 @@ impl Gists {
             view_gist,
             post_comment,
             get_file,
+            revisions,
+            diff,
+            edit,
+            delete,
+            fork,
+            star,
+            unstar,
+            starred,
+            explore,
+            search,
         }
     }
 
@@ -113,6 +182,11 @@ impl Gists {
         self.profile.replace("{username}", components.username)
     }
 
+    /// get starred route with placeholders replaced with values provided.
+    pub fn get_starred_route(&self, components: GistProfilePathComponent) -> String {
+        self.starred.replace("{username}", components.username)
+    }
+
     /// get gist route route with placeholders replaced with values provided.
     pub fn get_gist_route(&self, components: &PostCommentPath) -> String {
         self.view_gist
@@ -120,6 +194,11 @@ impl Gists {
             .replace("{gist}", &components.gist)
     }
 
+    /// get route to gist pinned at revision `rev`
+    pub fn get_gist_revision_route(&self, components: &PostCommentPath, rev: &str) -> String {
+        format!("{}?rev={}", self.get_gist_route(components), rev)
+    }
+
     /// get post_comment route with placeholders replaced with values provided.
     pub fn get_post_comment_route(&self, components: &PostCommentPath) -> String {
         self.post_comment
@@ -128,12 +207,66 @@ impl Gists {
     }
 
     /// get file routes with placeholders replaced with values provided.
-    /// filename is auto-escaped using [urlencoding::encode]
+    /// each component of the file's path is auto-escaped using [urlencoding::encode]
     pub fn get_file_route(&self, components: &GetFilePath) -> String {
+        let file: Vec<_> = components
+            .file
+            .split('/')
+            .map(urlencoding::encode)
+            .collect();
         self.get_file
             .replace("{username}", &components.username)
             .replace("{gist}", &components.gist)
-            .replace("{file}", &urlencoding::encode(&components.file))
+            .replace("{file:.*}", &file.join("/"))
+    }
+
+    /// get revisions route with placeholders replaced with values provided.
+    pub fn get_revisions_route(&self, components: &PostCommentPath) -> String {
+        self.revisions
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get diff route with placeholders replaced with values provided.
+    pub fn get_diff_route(&self, components: &PostCommentPath) -> String {
+        self.diff
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get edit route with placeholders replaced with values provided.
+    pub fn get_edit_route(&self, components: &PostCommentPath) -> String {
+        self.edit
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get delete route with placeholders replaced with values provided.
+    pub fn get_delete_route(&self, components: &PostCommentPath) -> String {
+        self.delete
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get fork route with placeholders replaced with values provided.
+    pub fn get_fork_route(&self, components: &PostCommentPath) -> String {
+        self.fork
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get star route with placeholders replaced with values provided.
+    pub fn get_star_route(&self, components: &PostCommentPath) -> String {
+        self.star
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
+    }
+
+    /// get unstar route with placeholders replaced with values provided.
+    pub fn get_unstar_route(&self, components: &PostCommentPath) -> String {
+        self.unstar
+            .replace("{username}", &components.username)
+            .replace("{gist}", &components.gist)
     }
 }
 
@@ -163,14 +296,26 @@ mod tests {
         const NAME: &str = "bob";
         const GIST: &str = "foo";
         const FILE: &str = "README.md";
+        const REV: &str = "a1b2c3d";
         let get_profile = format!("/~{NAME}");
         let view_gist = format!("/~{NAME}/{GIST}");
         let post_comment = format!("/~{NAME}/{GIST}/comment");
         let get_file = format!("/~{NAME}/{GIST}/contents/{FILE}");
+        let revisions = format!("/~{NAME}/{GIST}/revisions");
+        let view_gist_revision = format!("/~{NAME}/{GIST}?rev={REV}");
+        let diff = format!("/~{NAME}/{GIST}/diff");
+        let edit = format!("/~{NAME}/{GIST}/edit");
+        let delete = format!("/~{NAME}/{GIST}/delete");
+        let fork = format!("/~{NAME}/{GIST}/fork");
+        let star = format!("/~{NAME}/{GIST}/star");
+        let unstar = format!("/~{NAME}/{GIST}/unstar");
+        let starred = format!("/~{NAME}/starred");
 
         let profile_component = GistProfilePathComponent { username: NAME };
 
         assert_eq!(get_profile, PAGES.gist.get_profile_route(profile_component));
+        let profile_component = GistProfilePathComponent { username: NAME };
+        assert_eq!(starred, PAGES.gist.get_starred_route(profile_component));
 
         let profile_component = PostCommentPath {
             username: NAME.into(),
@@ -178,6 +323,10 @@ mod tests {
         };
 
         assert_eq!(view_gist, PAGES.gist.get_gist_route(&profile_component));
+        assert_eq!(
+            view_gist_revision,
+            PAGES.gist.get_gist_revision_route(&profile_component, REV)
+        );
 
         let post_comment_path = PostCommentPath {
             gist: GIST.into(),
@@ -188,6 +337,16 @@ mod tests {
             post_comment,
             PAGES.gist.get_post_comment_route(&post_comment_path)
         );
+        assert_eq!(
+            revisions,
+            PAGES.gist.get_revisions_route(&post_comment_path)
+        );
+        assert_eq!(diff, PAGES.gist.get_diff_route(&post_comment_path));
+        assert_eq!(edit, PAGES.gist.get_edit_route(&post_comment_path));
+        assert_eq!(delete, PAGES.gist.get_delete_route(&post_comment_path));
+        assert_eq!(fork, PAGES.gist.get_fork_route(&post_comment_path));
+        assert_eq!(star, PAGES.gist.get_star_route(&post_comment_path));
+        assert_eq!(unstar, PAGES.gist.get_unstar_route(&post_comment_path));
 
         let file_component = GetFilePath {
             username: NAME.into(),
@@ -196,4 +355,12 @@ mod tests {
         };
         assert_eq!(get_file, PAGES.gist.get_file_route(&file_component));
     }
+
+    #[test]
+    fn account_route_substitution_works() {
+        assert_eq!(
+            "/settings/tokens/5/delete",
+            PAGES.account.get_delete_token_route(5)
+        );
+    }
 }
@@ -20,6 +20,8 @@ use crate::api::v1;
 
 pub fn services(cfg: &mut web::ServiceConfig) {
     v1::services(cfg);
+    crate::api::github::services(cfg);
+    crate::git::services(cfg);
     crate::pages::services(cfg);
     crate::static_assets::services(cfg);
 }
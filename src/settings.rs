@@ -68,6 +68,13 @@ impl LogLevel {
 #[derive(Serialize, Debug, Clone, Deserialize)]
 pub struct Repository {
     pub root: String,
+    /// maximum size of a pack that can be pushed to a gist, in bytes. 0, the default, means
+    /// unlimited.
+    #[serde(default)]
+    pub max_push_size: usize,
+    /// directory that git repositories can be imported from through the API. Imports
+    /// through the API are disabled when unset.
+    pub import_root: Option<String>,
 }
 
 impl Repository {
@@ -84,6 +91,33 @@ impl Repository {
     }
 }
 
+/// What the consistency checker does with the issues it finds
+#[derive(Deserialize, Serialize, Display, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FsckMode {
+    /// only report issues
+    #[default]
+    #[display(fmt = "check")]
+    Check,
+    /// delete database rows that don't have a repository, repositories that don't have a
+    /// database row are moved to a quarantine directory
+    #[display(fmt = "repair")]
+    Repair,
+    /// like [FsckMode::Repair], but repositories of gists are moved to a quarantine
+    /// directory instead of being deleted. Corrupt repositories are quarantined too.
+    #[display(fmt = "quarantine")]
+    Quarantine,
+}
+
+#[derive(Serialize, Debug, Clone, Default, Deserialize)]
+pub struct Fsck {
+    /// interval between consistency checks of the database and repositories, in seconds.
+    /// Checks are disabled when unset.
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub mode: FsckMode,
+}
+
 #[derive(Deserialize, Serialize, Display, PartialEq, Clone, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum DBType {
@@ -91,13 +125,16 @@ pub enum DBType {
     Postgres,
     #[display(fmt = "sqlite")]
     Sqlite,
+    #[display(fmt = "memory")]
+    Memory,
 }
 
 impl DBType {
-    fn from_url(url: &Url) -> Result<Self, ConfigError> {
+    pub fn from_url(url: &Url) -> Result<Self, ConfigError> {
         match url.scheme() {
             "sqlite" => Ok(Self::Sqlite),
             "postgres" => Ok(Self::Postgres),
+            "memory" => Ok(Self::Memory),
             _ => Err(ConfigError::Message("Unknown database type".into())),
         }
     }
@@ -150,6 +187,8 @@ pub struct Settings {
     pub repository: Repository,
     #[validate(email)]
     pub admin_email: String,
+    #[serde(default)]
+    pub fsck: Fsck,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -278,10 +317,12 @@ mod tests {
 
     #[test]
     fn database_type_test() {
-        for i in ["sqlite://foo", "postgres://bar", "unknown://"].iter() {
+        for i in ["sqlite://foo", "postgres://bar", "memory://", "unknown://"].iter() {
             let url = Url::parse(i).unwrap();
             if i.contains("sqlite") {
                 assert_eq!(DBType::from_url(&url).unwrap(), DBType::Sqlite);
+            } else if i.contains("memory") {
+                assert_eq!(DBType::from_url(&url).unwrap(), DBType::Memory);
             } else if i.contains("unknown") {
                 assert!(DBType::from_url(&url).is_err());
             } else {
@@ -307,6 +348,8 @@ mod tests {
 
         let repo = Repository {
             root: dir.to_str().unwrap().to_owned(),
+            max_push_size: 0,
+            import_root: None,
         };
 
         repo.create_root_dir();
@@ -323,6 +366,8 @@ mod tests {
 
         let repo = Repository {
             root: file.to_str().unwrap().to_owned(),
+            max_push_size: 0,
+            import_root: None,
         };
 
         repo.create_root_dir();
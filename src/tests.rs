@@ -91,6 +91,16 @@ macro_rules! post_request {
     };
 }
 
+#[macro_export]
+macro_rules! patch_request {
+    ($serializable:expr, $uri:expr) => {
+        test::TestRequest::patch()
+            .uri($uri)
+            .insert_header((actix_web::http::header::CONTENT_TYPE, "application/json"))
+            .set_payload(serde_json::to_string($serializable).unwrap())
+    };
+}
+
 #[macro_export]
 macro_rules! get_request {
     ($app:expr,$route:expr ) => {
@@ -36,6 +36,11 @@ pub fn escape_spaces(name: &str) -> String {
     }
 }
 
+/// Reverse [escape_spaces]
+pub fn unescape_spaces(name: &str) -> String {
+    name.replace("\\ ", " ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,5 +49,6 @@ mod tests {
     fn space_escape() {
         let space = "do re mi";
         assert_eq!(&escape_spaces(space), ("do\\ re\\ mi"));
+        assert_eq!(unescape_spaces(&escape_spaces(space)), space);
     }
 }